@@ -1,45 +1,47 @@
+use crate::assets;
 use crate::drawable::{DrawOptions, Drawable};
 use crate::editor::Editor;
 use crate::position::Position;
 use crate::renderer::Renderer;
 
 use std::cell::RefCell;
-use std::path::Path;
 use std::rc::Rc;
 
 use uuid::Uuid;
 
 use sdl2::event::Event;
-use sdl2::image::LoadSurface;
 use sdl2::mouse::MouseButton;
 use sdl2::rect::Rect;
 use sdl2::surface::Surface;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum ButtonState {
     OFF,
     HOVER,
     CLICKED,
 }
 
+// What a button does when clicked, given the renderer so that it can change what is drawn
+pub type OnClick = Box<dyn Fn(&Button, &mut Renderer) -> Result<(), String>>;
+
 pub struct Button {
     pub id: Uuid,
     position: Position,
     size: (u32, u32),
     state: ButtonState,
-    on_click: Box<dyn Fn(&Self) -> Result<(), String>>, // Boxed closure for button functionality
+    on_click: OnClick, // Boxed closure for button functionality
     pub(super) editor: Rc<RefCell<Editor>>, // Needed to change pages from within closure
 }
 
 impl Button {
     pub fn new(
         position: Position,
-        image_path: &Path,
+        image: &str, // Inside the assets folder
         renderer: &mut Renderer,
         editor: Rc<RefCell<Editor>>,
     ) -> Result<Button, String> {
         // Button images are split horizontally into three equal parts
-        let src = Surface::from_file(image_path)?;
+        let src = assets::surface(image)?;
         let (sfc_w, sfc_h) = (src.width() / 3, src.height());
 
         let mut surface_off = Surface::new(sfc_w, sfc_h, src.pixel_format_enum())?;
@@ -59,17 +61,33 @@ impl Button {
             None,
         )?;
 
+        Button::from_surfaces(
+            position,
+            [&surface_off, &surface_hover, &surface_click],
+            renderer,
+            editor,
+        )
+    }
+
+    // Makes a button out of surfaces for each of its states, in the order off, hover and clicked
+    // Used for buttons drawn by the app itself rather than loaded from an image
+    pub fn from_surfaces(
+        position: Position,
+        surfaces: [&Surface; 3],
+        renderer: &mut Renderer,
+        editor: Rc<RefCell<Editor>>,
+    ) -> Result<Button, String> {
         let id = Uuid::new_v4();
 
         // Each button has 3 associated textures that will be displayed depending on their state
-        renderer.create_textures(id, vec![&surface_off, &surface_hover, &surface_click])?;
+        renderer.create_textures(id, surfaces.to_vec())?;
 
         Ok(Button {
             id,
             position,
-            size: (sfc_w, sfc_h),
+            size: surfaces[0].size(),
             state: ButtonState::OFF,
-            on_click: Box::new(|_| Ok(())),
+            on_click: Box::new(|_, _| Ok(())),
             editor,
         })
     }
@@ -86,7 +104,7 @@ impl Button {
         self.position
     }
 
-    pub fn set_on_click(&mut self, on_click: Box<dyn Fn(&Self) -> Result<(), String>>) {
+    pub fn set_on_click(&mut self, on_click: OnClick) {
         self.on_click = on_click;
     }
 
@@ -114,9 +132,14 @@ impl Button {
     }
 
     // Handles any mouse event dealing with the button
-    // Requires screen_dimensions because mouse position is FreeOnScreen which may need to be
-    // converted to button position as AnchoredOnScreen
-    pub fn handle_event(&mut self, e: &Event, screen_dimensions: (u32, u32)) -> Result<(), String> {
+    // Requires the renderer's screen dimensions because mouse position is FreeOnScreen which may
+    // need to be converted to button position as AnchoredOnScreen, and the renderer itself for
+    // buttons that change what is drawn
+    // Returns whether the button changed state or was clicked, either of which needs a redraw
+    pub fn handle_event(&mut self, e: &Event, renderer: &mut Renderer) -> Result<bool, String> {
+        let screen_dimensions = renderer.dimensions();
+        let state = self.state;
+
         // Controls button "state machine"
         match e {
             Event::MouseMotion { x, y, .. } => {
@@ -129,7 +152,6 @@ impl Button {
                 {
                     self.state = ButtonState::OFF;
                 }
-                Ok(())
             }
             Event::MouseButtonDown {
                 x,
@@ -140,7 +162,6 @@ impl Button {
                 if self.contains_point(*x, *y, screen_dimensions)? {
                     self.state = ButtonState::CLICKED;
                 }
-                Ok(())
             }
             Event::MouseButtonUp {
                 x,
@@ -152,14 +173,15 @@ impl Button {
                     && matches!(self.state, ButtonState::CLICKED)
                 {
                     self.state = ButtonState::HOVER;
-                    (self.on_click)(self)
-                } else {
-                    self.state = ButtonState::OFF;
-                    Ok(())
+                    (self.on_click)(self, renderer)?;
+                    return Ok(true);
                 }
+                self.state = ButtonState::OFF;
             }
-            _ => Ok(()),
+            _ => (),
         }
+
+        Ok(self.state != state)
     }
 }
 
@@ -0,0 +1,158 @@
+use crate::drawable::{DrawOptions, Drawable};
+use crate::position::Position;
+use crate::renderer::Renderer;
+
+use uuid::Uuid;
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::ttf::FontStyle;
+
+const PROMPT: &str = "Go to page: ";
+const PROMPT_FONT: &str = "NotoSerif";
+const PROMPT_POINT: u16 = 30;
+const PROMPT_PADDING: (u32, u32) = (16, 8);
+const MAX_DIGITS: usize = 5;
+
+// A prompt at the top of the window for the number of a page to jump to, opened with Ctrl+G
+// Digits are typed into it, Enter jumps to the page and Escape closes it
+pub struct GoToPage {
+    id: Uuid,
+    digits: Option<String>, // None while the prompt is closed
+    size: (u32, u32),       // Of the prompt's text
+}
+
+impl GoToPage {
+    pub fn new() -> GoToPage {
+        GoToPage {
+            id: Uuid::new_v4(),
+            digits: None,
+            size: (0, 0),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.digits.is_some()
+    }
+
+    pub fn open(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        self.digits = Some(String::new());
+        self.update_text(renderer)
+    }
+
+    pub fn close(&mut self, renderer: &mut Renderer) {
+        self.digits = None;
+        renderer.remove_textures(self.id);
+    }
+
+    // Handles a key pressed while the prompt is open, returning the page number typed in once
+    // Enter is pressed
+    pub fn handle_event(
+        &mut self,
+        event: &Event,
+        renderer: &mut Renderer,
+    ) -> Result<Option<u32>, String> {
+        let keycode = match event {
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => *keycode,
+            _ => return Ok(None),
+        };
+        let digits = match &mut self.digits {
+            Some(digits) => digits,
+            None => return Ok(None),
+        };
+
+        match keycode {
+            Keycode::Return | Keycode::KpEnter => {
+                let page = digits.parse().ok();
+                self.close(renderer);
+                return Ok(page);
+            }
+            Keycode::Escape => {
+                self.close(renderer);
+                return Ok(None);
+            }
+            Keycode::Backspace => {
+                digits.pop();
+            }
+            _ => match digit(keycode) {
+                Some(d) if digits.len() < MAX_DIGITS => digits.push(d),
+                _ => return Ok(None),
+            },
+        }
+        self.update_text(renderer)?;
+
+        Ok(None)
+    }
+
+    fn update_text(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        let text = format!("{}{}", PROMPT, self.digits.as_deref().unwrap_or(""));
+        self.size = renderer.create_text(
+            self.id,
+            Some(0),
+            &text,
+            &PROMPT_FONT.to_string(),
+            FontStyle::NORMAL,
+            PROMPT_POINT,
+            Color::BLACK,
+        )?;
+
+        Ok(())
+    }
+}
+
+impl Default for GoToPage {
+    fn default() -> Self {
+        GoToPage::new()
+    }
+}
+
+impl Drawable for GoToPage {
+    fn draw(&self, renderer: &mut Renderer) -> Result<(), String> {
+        if !self.is_open() {
+            return Ok(());
+        }
+
+        let size = (
+            self.size.0 + 2 * PROMPT_PADDING.0,
+            self.size.1 + 2 * PROMPT_PADDING.1,
+        );
+        let position =
+            Position::FreeOnScreen((renderer.dimensions().0 as i32 - size.0 as i32) / 2, 20);
+        renderer.draw_fill_rect(position, size, Color::WHITE)?;
+        renderer.draw_rect(position, 2, size, Color::BLACK)?;
+
+        renderer.draw_texture(
+            self.id,
+            0,
+            DrawOptions {
+                src: None,
+                position: Position::add(position, PROMPT_PADDING.0 as i32, PROMPT_PADDING.1 as i32),
+                size: self.size,
+                rotation: None,
+                flip_h: false,
+                flip_v: false,
+            },
+        )
+    }
+}
+
+fn digit(keycode: Keycode) -> Option<char> {
+    let digit = match keycode {
+        Keycode::Num0 | Keycode::Kp0 => '0',
+        Keycode::Num1 | Keycode::Kp1 => '1',
+        Keycode::Num2 | Keycode::Kp2 => '2',
+        Keycode::Num3 | Keycode::Kp3 => '3',
+        Keycode::Num4 | Keycode::Kp4 => '4',
+        Keycode::Num5 | Keycode::Kp5 => '5',
+        Keycode::Num6 | Keycode::Kp6 => '6',
+        Keycode::Num7 | Keycode::Kp7 => '7',
+        Keycode::Num8 | Keycode::Kp8 => '8',
+        Keycode::Num9 | Keycode::Kp9 => '9',
+        _ => return None,
+    };
+    Some(digit)
+}
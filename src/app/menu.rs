@@ -46,6 +46,9 @@ impl Menu {
     pub fn padding(&self) -> (i32, i32) {
         self.padding
     }
+    pub fn buttons(&self) -> &Vec<Button> {
+        &self.buttons
+    }
 
     pub fn set_position(&mut self, position: Position) {
         self.position = position
@@ -91,17 +94,18 @@ impl Menu {
         self.buttons.push(new_button)
     }
 
-    // Passes on the event to all the buttons it contains
+    // Passes on the event to all the buttons it contains, returning whether any of them changed
     pub fn handle_button_events(
         &mut self,
         event: &Event,
-        screen_dimensions: (u32, u32),
-    ) -> Result<(), String> {
+        renderer: &mut Renderer,
+    ) -> Result<bool, String> {
+        let mut changed = false;
         for button in &mut self.buttons {
-            button.handle_event(event, screen_dimensions)?;
+            changed |= button.handle_event(event, renderer)?;
         }
 
-        Ok(())
+        Ok(changed)
     }
 }
 
@@ -1,31 +1,49 @@
 pub mod button;
+pub mod go_to_page;
 pub mod menu;
+pub mod notice;
 pub mod pages;
 pub mod setup;
 
 use self::button::Button;
+use self::go_to_page::GoToPage;
 use self::menu::Menu;
-use self::pages::Pages;
+use self::notice::Notice;
+use self::pages::{Pages, PaperSize};
+use crate::assets;
+use crate::config::Config;
 use crate::cursor::Cursor;
 use crate::drawable::Drawable;
 use crate::editor::Editor;
+use crate::fonts::{self, FontManager};
+use crate::mark::textbox::TEXT_COLORS;
 use crate::position::Position;
 use crate::renderer::Renderer;
 use crate::SdlContext;
 
 use std::cell::RefCell;
-use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use sdl2::clipboard::ClipboardUtil;
 use sdl2::event::{Event, WindowEvent};
-use sdl2::keyboard::TextInputUtil;
+use sdl2::keyboard::{Keycode, Mod, TextInputUtil};
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::{TextureCreator, WindowCanvas};
-use sdl2::ttf::Font;
+use sdl2::ttf::Sdl2TtfContext;
 use sdl2::video::WindowContext;
+use sdl2::VideoSubsystem;
+
+// The point sizes offered in the text menu, though fonts can be opened at any size
+pub const POINTS: [u16; 6] = [30, 32, 36, 48, 60, 72];
+
+const ZOOM_STEP: f32 = 1.25; // Zooming in multiplies the zoom by this, and zooming out divides by it
+const FIT_MARGIN: u32 = 40; // Pixels on the world left around pages zoomed to fit the window
+const TICK: Duration = Duration::from_micros(16_667); // Time between updates, 60 times a second
+const MAX_TICKS: u32 = 5; // Updates caught up on after a slow frame, rather than all of them
+const IDLE_WAIT: Duration = Duration::from_secs(1); // Longest wait for an event while idle
 
 // This struct controls the setup up and running stages of the application
 pub struct App<'a> {
@@ -34,12 +52,17 @@ pub struct App<'a> {
     event_pump: sdl2::EventPump,
     text_input: TextInputUtil, // Only used by the text tool
     clipboard: ClipboardUtil,  // Passed on to the editor which handles it
-    fonts: HashMap<String, Font<'a, 'a>>,
+    fonts: FontManager<'a>,
+    config: Config,
+    file_path: Option<PathBuf>, // Document opened on startup
+    video: VideoSubsystem,      // Used to render pages off-screen when exporting
+    ttf: &'a Sdl2TtfContext,
 }
 
 impl<'a> App<'a> {
     // Initializes the application
-    pub fn init(sdl_context: &SdlContext) -> Result<App, String> {
+    pub fn init(sdl_context: &SdlContext, file_path: Option<PathBuf>) -> Result<App<'_>, String> {
+        assets::check()?;
         let display_bounds = sdl_context.video_subsystem.display_usable_bounds(0)?;
 
         // Sets window to be maximized and resizable
@@ -52,64 +75,34 @@ impl<'a> App<'a> {
             .build()
             .map_err(|e| e.to_string())?;
 
-        let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+        // Smooths textures drawn larger or smaller than they are when zoomed in or out
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "linear");
+        // Presents frames in step with the display where the driver can, rather than tearing
+        let mut canvas = window
+            .into_canvas()
+            .present_vsync()
+            .build()
+            .map_err(|e| e.to_string())?;
         // Sets the canvas blend mode so that alpha values are rendered properly
         canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
 
         let tex_creator = canvas.texture_creator();
         let event_pump = sdl_context.sdl.event_pump()?;
 
-        // String has format FontName-Style_Point
-        let mut font_map = HashMap::new();
-        let points: Vec<u16> = vec![30, 32, 36, 48, 60, 72];
-
-        // Load all the fonts in assets/fonts
-        for entry in Path::new("assets/fonts")
-            .read_dir()
-            .map_err(|e| e.to_string())?
-        {
-            match entry {
-                Ok(folder) => {
-                    for entry in folder.path().read_dir().map_err(|e| e.to_string())? {
-                        match entry {
-                            Ok(font) => {
-                                if !font.path().extension().unwrap().eq_ignore_ascii_case("ttf") {
-                                    continue;
-                                }
-
-                                let mut font_name = font
-                                    .path()
-                                    .file_stem()
-                                    .unwrap()
-                                    .to_str()
-                                    .unwrap()
-                                    .to_string();
-                                for point in &points {
-                                    let temp = font_name.clone();
-                                    font_name.push('_');
-                                    font_name.push_str(&point.to_string());
-                                    font_map.insert(
-                                        font_name,
-                                        sdl_context.ttf.load_font(font.path(), *point)?,
-                                    );
-                                    font_name = temp;
-                                }
-                            }
-                            Err(e) => return Err(e.to_string()),
-                        }
-                    }
-                }
-                Err(e) => return Err(e.to_string()),
-            }
-        }
+        let config = Config::load()?;
+        let fonts = FontManager::new(&sdl_context.ttf, &config)?;
 
         Ok(App {
             canvas,
             tex_creator,
             event_pump,
-            fonts: font_map,
+            fonts,
+            config,
+            video: sdl_context.video_subsystem.clone(),
+            ttf: &sdl_context.ttf,
             text_input: sdl_context.video_subsystem.text_input(),
             clipboard: sdl_context.video_subsystem.clipboard(),
+            file_path,
         })
     }
 
@@ -119,22 +112,35 @@ impl<'a> App<'a> {
         tex_creator: &'tc TextureCreator<WindowContext>,
         text_input: TextInputUtil,
         clipboard: ClipboardUtil,
-        fonts: HashMap<String, Font<'ttf, 'ttf>>,
+        fonts: FontManager<'ttf>,
+        config: Config,
+        file_path: Option<PathBuf>,
     ) -> Result<(Renderer<'c, 'tc, 'ttf>, AppComponents), String> {
         let mut renderer = Renderer::new(canvas, tex_creator, fonts);
 
         // Pages will be handed off to the editor which will perform all changes to it
-        let pages = Pages::new((42, 59), &mut renderer)?;
+        // New documents start out as A4, and opened ones keep the size they were saved with
+        let pages = Pages::new(PaperSize::A4.page_squares(), &mut renderer)?;
         let editor = Rc::new(RefCell::new(Editor::new(pages, text_input, clipboard)));
+        editor.borrow_mut().set_embed_fonts(config.embed_fonts);
+
+        // Open the document if it exists, otherwise it will be created on the first save
+        if let Some(path) = file_path {
+            if path.exists() {
+                editor.borrow_mut().load(&path, &mut renderer)?;
+            } else {
+                editor.borrow_mut().set_file_path(path);
+            }
+        }
 
         let mut add_page_button = Button::new(
             Position::AnchoredRightBottom(220, 140),
-            Path::new("assets/images/add_page_button.png"),
+            "images/add_page_button.png",
             &mut renderer,
             Rc::clone(&editor),
         )?;
 
-        add_page_button.set_on_click(Box::new(|button| {
+        add_page_button.set_on_click(Box::new(|button, _| {
             button.editor.borrow_mut().add_page();
 
             Ok(())
@@ -142,19 +148,22 @@ impl<'a> App<'a> {
 
         let mut remove_page_button = Button::new(
             Position::AnchoredRightBottom(120, 140),
-            Path::new("assets/images/remove_page_button.png"),
+            "images/remove_page_button.png",
             &mut renderer,
             Rc::clone(&editor),
         )?;
 
-        remove_page_button.set_on_click(Box::new(|button| {
-            button.editor.borrow_mut().remove_page();
+        remove_page_button.set_on_click(Box::new(|button, renderer| {
+            button.editor.borrow_mut().remove_page(renderer);
 
             Ok(())
         }));
 
         let bottom_menu = crate::app::setup::setup_bottom_menu(&mut renderer, Rc::clone(&editor))?;
         let tool_menu = crate::app::setup::setup_tool_menu(&mut renderer, Rc::clone(&editor))?;
+        let font_families = renderer.fonts().library().families();
+        let text_menu =
+            crate::app::setup::setup_text_menu(&font_families, &mut renderer, Rc::clone(&editor))?;
 
         let cursor = Cursor::new(Rc::clone(&editor));
 
@@ -163,13 +172,22 @@ impl<'a> App<'a> {
             AppComponents {
                 editor,
                 cursor,
-                menus: vec![bottom_menu, tool_menu],
+                menus: vec![bottom_menu, tool_menu, text_menu],
                 buttons: vec![add_page_button, remove_page_button],
+                font_families,
+                go_to_page: GoToPage::new(),
+                notice: Notice::new(),
             },
         ))
     }
 
     pub fn run(mut self) -> Result<(), String> {
+        // Presenting a frame waits for the display when the driver could give vsync, and
+        // otherwise the loop waits out each tick itself
+        let vsync = self.canvas.info().flags
+            & sdl2::sys::SDL_RendererFlags::SDL_RENDERER_PRESENTVSYNC as u32
+            != 0;
+
         // First sets everything up
         let (mut renderer, mut ac) = App::setup(
             &mut self.canvas,
@@ -177,38 +195,226 @@ impl<'a> App<'a> {
             self.text_input,
             self.clipboard,
             self.fonts,
+            self.config,
+            self.file_path,
         )?;
 
-        // The main run loop
+        // The main run loop, which moves animations on in fixed steps of time however long each
+        // frame takes, and waits between frames rather than drawing as often as it can
+        // Frames are only drawn when something has changed, and while nothing is moving the loop
+        // sleeps until an event comes in, the caret blinks or a notice goes
+        let mut last_frame = Instant::now();
+        let mut lag = Duration::ZERO;
+        let mut dirty = true;
+        let mut drawn_view = (renderer.camera(), renderer.zoom());
+        let mut redraw_due = None;
         'main: loop {
-            for event in self.event_pump.poll_iter() {
+            let waited = if renderer.is_gliding() {
+                None
+            } else {
+                let wait = redraw_due.map_or(IDLE_WAIT, |at: Instant| {
+                    at.saturating_duration_since(Instant::now()).min(IDLE_WAIT)
+                });
+                // Rounded up so as not to wake just before the caret is due to blink
+                let wait_ms = wait.as_millis() as u32 + 1;
+                self.event_pump.wait_event_timeout(wait_ms)
+            };
+            let events: Vec<Event> = waited
+                .into_iter()
+                .chain(self.event_pump.poll_iter())
+                .collect();
+
+            // The scene is only redrawn for events that change it, with changes to the camera and
+            // zoom found after the events are handled
+            for event in events {
+                // The go to page prompt takes every key pressed while it is open
+                if ac.go_to_page.is_open()
+                    && matches!(event, Event::KeyDown { .. } | Event::TextInput { .. })
+                {
+                    dirty = true;
+                    if let Some(page) = ac.go_to_page.handle_event(&event, &mut renderer)? {
+                        let pages = ac.editor.borrow();
+                        let pages = pages.get_pages();
+                        go_to_page(page.clamp(1, pages.pages()) - 1, &mut renderer, pages);
+                    }
+                    continue;
+                }
+
                 match event {
                     Event::Quit { .. } => break 'main,
                     Event::Window { win_event: e, .. } => match e {
                         // Adjust camera based on window and the pages' dimensions
-                        WindowEvent::Resized(width, height) => renderer.set_camera(Rect::new(
-                            (ac.editor.borrow().get_pages().page_width() as i32 - width) / 2,
-                            renderer.camera().y(),
-                            width as u32,
-                            height as u32,
-                        )),
+                        WindowEvent::Resized(width, height) => {
+                            dirty = true;
+                            let camera = renderer.camera();
+                            renderer.set_camera(Rect::new(
+                                camera.x(),
+                                camera.y(),
+                                width as u32,
+                                height as u32,
+                            ));
+                            renderer.keep_in_bounds();
+                        }
+                        // Whatever was drawn may be lost while the window is hidden or covered
+                        WindowEvent::Exposed | WindowEvent::Shown | WindowEvent::Restored => {
+                            dirty = true
+                        }
                         _ => (),
                     },
-                    Event::MouseWheel { y, .. } => renderer.scroll(y),
+                    // Scrolls, sideways with Shift held or from a trackpad, or zooms in and out
+                    // around the mouse with Ctrl held
+                    Event::MouseWheel { x, y, .. } => {
+                        let keymod = self.video.sdl().keyboard().mod_state();
+                        if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+                            let zoom = renderer.zoom() * ZOOM_STEP.powi(y);
+                            renderer.set_zoom(zoom, ac.cursor.position())?;
+                            renderer.keep_in_bounds();
+                        } else if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            renderer.scroll(-y, 0)
+                        } else {
+                            renderer.scroll(x, y)
+                        }
+                    }
+                    Event::KeyDown {
+                        keycode:
+                            Some(
+                                keycode @ (Keycode::Equals
+                                | Keycode::Plus
+                                | Keycode::KpPlus
+                                | Keycode::Minus
+                                | Keycode::KpMinus
+                                | Keycode::Num0
+                                | Keycode::Kp0
+                                | Keycode::Num1
+                                | Keycode::Kp1
+                                | Keycode::Num2
+                                | Keycode::Kp2),
+                            ),
+                        keymod,
+                        ..
+                    } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                        zoom_key(keycode, &mut renderer, ac.editor.borrow().get_pages())?
+                    }
+                    // Home and End move the caret while typing, unless Ctrl is held
+                    Event::KeyDown {
+                        keycode:
+                            Some(
+                                keycode @ (Keycode::PageUp
+                                | Keycode::PageDown
+                                | Keycode::Home
+                                | Keycode::End),
+                            ),
+                        keymod,
+                        ..
+                    } if matches!(keycode, Keycode::PageUp | Keycode::PageDown)
+                        || keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+                        || !ac.editor.borrow().is_typing() =>
+                    {
+                        page_key(keycode, &mut renderer, ac.editor.borrow().get_pages())
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::G),
+                        keymod,
+                        ..
+                    } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                        dirty = true;
+                        ac.go_to_page.open(&mut renderer)?
+                    }
+                    // Exports every page as a PNG next to the document, or as a PDF with shift
+                    Event::KeyDown {
+                        keycode: Some(Keycode::E),
+                        keymod,
+                        ..
+                    } if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                        let editor = ac.editor.borrow();
+                        let path = editor.file_path().unwrap_or(Path::new("untitled"));
+                        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("page");
+                        let out_dir = path.parent().unwrap_or(Path::new(""));
+
+                        let result = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            crate::export::pdf::export(
+                                &editor.to_document(),
+                                &out_dir.join(format!("{}.pdf", name)),
+                            )
+                        } else {
+                            crate::export::png::export(
+                                &self.video,
+                                self.ttf,
+                                &editor.to_document(),
+                                out_dir,
+                                name,
+                            )
+                            .map(|_| ())
+                        };
+                        // A failed export shouldn't take the document down with it
+                        if let Err(e) = result {
+                            dirty = true;
+                            let message = format!("Could not export pages: {}", e);
+                            ac.notice.show(&message, &mut renderer)?;
+                        }
+                    }
                     _ => {
-                        ac.cursor.handle_event(&event, renderer.camera())?;
-                        ac.editor.borrow_mut().handle_event(&event, &mut renderer)?;
+                        // Dragging with the middle mouse button pans around the pages
+                        if let Event::MouseMotion {
+                            mousestate,
+                            xrel,
+                            yrel,
+                            ..
+                        } = event
+                        {
+                            if mousestate.middle() {
+                                renderer.pan(xrel, yrel);
+                            }
+                        }
+                        dirty |= ac.cursor.handle_event(&event, &mut renderer)?;
+                        dirty |= ac.editor.borrow_mut().handle_event(&event, &mut renderer)?;
 
                         for menu in &mut ac.menus {
-                            menu.handle_button_events(&event, renderer.dimensions())?;
+                            dirty |= menu.handle_button_events(&event, &mut renderer)?;
                         }
                         for button in &mut ac.buttons {
-                            button.handle_event(&event, renderer.dimensions())?;
+                            dirty |= button.handle_event(&event, &mut renderer)?;
+                        }
+
+                        // Typing, keys or buttons may have failed at something, such as saving
+                        let failure = ac.editor.borrow_mut().take_failure();
+                        if let Some(e) = failure {
+                            dirty = true;
+                            ac.notice.show(&e, &mut renderer)?;
                         }
                     }
                 }
             }
 
+            let now = Instant::now();
+            lag = (lag + (now - last_frame)).min(TICK * MAX_TICKS);
+            last_frame = now;
+            while lag >= TICK {
+                renderer.step_scroll(TICK);
+                lag -= TICK;
+            }
+
+            // The pages may have been added, removed, resized or laid out differently
+            renderer.set_scroll_area(ac.editor.borrow().get_pages().scroll_area());
+            renderer.keep_in_bounds();
+
+            if (renderer.camera(), renderer.zoom()) != drawn_view {
+                dirty = true;
+            }
+            if redraw_due.is_some_and(|at| Instant::now() >= at) {
+                dirty = true;
+            }
+            if !dirty {
+                continue;
+            }
+            dirty = false;
+            drawn_view = (renderer.camera(), renderer.zoom());
+            // The caret blinking or a notice going away needs the window drawn again
+            redraw_due = [ac.editor.borrow().next_blink(), ac.notice.expires()]
+                .into_iter()
+                .flatten()
+                .min();
+
             renderer.clear();
             ac.editor.borrow().get_pages().draw(&mut renderer)?;
             ac.editor.borrow().draw_marks(&mut renderer)?;
@@ -235,16 +441,149 @@ impl<'a> App<'a> {
                 Color::BLACK,
             )?;
 
+            // Draws rectangles around the font, point size and color that new text is given, or
+            // the color of new lines while drawing them
+            // The text menu lists the fonts, then the point sizes, then the colors
+            let editor = ac.editor.borrow();
+            let families = ac.font_families.len();
+            let chosen = [
+                ac.font_families
+                    .iter()
+                    .position(|family| fonts::same_family(family, editor.text_font())),
+                POINTS
+                    .iter()
+                    .position(|point| *point == editor.text_point())
+                    .map(|i| families + i),
+                TEXT_COLORS
+                    .iter()
+                    .position(|color| *color == editor.color())
+                    .map(|i| families + POINTS.len() + i),
+            ];
+            for button in chosen
+                .into_iter()
+                .flatten()
+                .filter_map(|i| ac.menus[2].buttons().get(i))
+            {
+                renderer.draw_rect(
+                    Position::add(button.position(), -1, -1),
+                    3,
+                    (button.width() + 2, button.height() + 2),
+                    Color::BLACK,
+                )?;
+            }
+
+            // Draws a rectangle around the paper size of the pages, if they are one
+            // The bottom menu lists the paper sizes after the page style and grid buttons
+            let paper = PaperSize::of(editor.get_pages().page_squares());
+            let paper_button = paper
+                .and_then(|paper| PaperSize::ALL.iter().position(|p| *p == paper))
+                .and_then(|i| ac.menus[0].buttons().get(2 + i));
+            if let Some(button) = paper_button {
+                renderer.draw_rect(
+                    Position::add(button.position(), -1, -1),
+                    2,
+                    (button.width() + 2, button.height() + 2),
+                    Color::BLACK,
+                )?;
+            }
+            drop(editor);
+            ac.notice.draw(&mut renderer)?;
+            ac.go_to_page.draw(&mut renderer)?;
+
             renderer.update();
+
+            // Without vsync, waits out the rest of the tick, as nothing changes any sooner
+            if !vsync {
+                if let Some(wait) = TICK.checked_sub(last_frame.elapsed()) {
+                    std::thread::sleep(wait);
+                }
+            }
         }
 
         Ok(())
     }
 }
 
+// Ctrl with +, - and 0 zooms in, out and back to the actual size, and with 1 or 2 fits the page
+// in the middle of the window, or the width of all the columns of pages, into the window
+fn zoom_key(keycode: Keycode, renderer: &mut Renderer, pages: &Pages) -> Result<(), String> {
+    let (width, height) = (renderer.camera().width(), renderer.camera().height());
+    let middle = Position::FreeOnScreen(width as i32 / 2, height as i32 / 2);
+    let fit_width = width as f32 / (pages.total_width() + 2 * FIT_MARGIN) as f32;
+
+    match keycode {
+        Keycode::Equals | Keycode::Plus | Keycode::KpPlus => {
+            renderer.set_zoom(renderer.zoom() * ZOOM_STEP, middle)?
+        }
+        Keycode::Minus | Keycode::KpMinus => {
+            renderer.set_zoom(renderer.zoom() / ZOOM_STEP, middle)?
+        }
+        Keycode::Num0 | Keycode::Kp0 => renderer.set_zoom(1.0, middle)?,
+        Keycode::Num1 | Keycode::Kp1 => {
+            let page = pages.nearest_page(renderer.to_world(middle)?);
+            let fit_page_width = width as f32 / (pages.page_width() + 2 * FIT_MARGIN) as f32;
+            let fit_height = height as f32 / (pages.page_height() + 2 * FIT_MARGIN) as f32;
+            renderer.set_zoom(fit_page_width.min(fit_height), middle)?;
+
+            // Centers the page in the window, as it may be narrower or shorter than it
+            let (visible_width, visible_height) = renderer.visible_size();
+            let space_x = visible_width as i32 - pages.page_width() as i32;
+            let space_y = visible_height as i32 - pages.page_height() as i32;
+            renderer.scroll_to(Position::add(
+                pages.get_page_position(page),
+                -space_x / 2,
+                -space_y / 2,
+            ));
+        }
+        Keycode::Num2 | Keycode::Kp2 => renderer.set_zoom(fit_width, middle)?,
+        _ => (),
+    }
+    renderer.keep_in_bounds();
+
+    Ok(())
+}
+
+// PageUp and PageDown glide to the row of pages above or below, and Home and End to the first and
+// last page
+fn page_key(keycode: Keycode, renderer: &mut Renderer, pages: &Pages) {
+    // Pages are counted from where the camera is gliding to, so that pressing a key again before
+    // it gets there goes on to the next row
+    let top = renderer.scroll_target().y() + FIT_MARGIN as i32;
+    let columns = pages.layout().columns();
+    let last = pages.pages() - 1;
+    let row = (0..pages.pages())
+        .step_by(columns as usize)
+        .take_while(|page| pages.get_page_position(*page).y() <= top)
+        .last()
+        .unwrap_or(0);
+
+    let page = match keycode {
+        Keycode::PageDown => (row + columns).min(last),
+        // Goes back to the top of the row first, if it has been scrolled past
+        Keycode::PageUp if pages.get_page_position(row).y() < top => row,
+        Keycode::PageUp => row.saturating_sub(columns),
+        Keycode::Home => 0,
+        _ => last,
+    };
+    go_to_page(page, renderer, pages)
+}
+
+// Glides to a page, with its top near the top of the window
+fn go_to_page(page: u32, renderer: &mut Renderer, pages: &Pages) {
+    let visible_width = renderer.visible_size().0 as i32;
+    renderer.glide_to(Position::add(
+        pages.get_page_position(page),
+        (pages.page_width() as i32 - visible_width) / 2,
+        -(FIT_MARGIN as i32),
+    ));
+}
+
 pub struct AppComponents {
     editor: Rc<RefCell<Editor>>,
     cursor: Cursor,
     menus: Vec<Menu>,
     buttons: Vec<Button>,
+    font_families: Vec<String>, // In the order they are listed in the text menu
+    go_to_page: GoToPage,
+    notice: Notice, // Says what went wrong, rather than letting it end the app
 }
@@ -0,0 +1,104 @@
+use crate::drawable::{DrawOptions, Drawable};
+use crate::position::Position;
+use crate::renderer::Renderer;
+
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use sdl2::pixels::Color;
+use sdl2::ttf::FontStyle;
+
+const NOTICE_FONT: &str = "NotoSerif";
+const NOTICE_POINT: u16 = 30;
+const NOTICE_PADDING: (u32, u32) = (16, 8);
+const NOTICE_TOP: i32 = 80; // Below where the go to page prompt opens
+const NOTICE_COLOR: Color = Color::RGB(180, 20, 20);
+const NOTICE_TIME: Duration = Duration::from_secs(6);
+
+// A message at the top of the window saying what went wrong, such as a save or export failing
+// It is shown for a few seconds, or until another one takes its place
+pub struct Notice {
+    id: Uuid,
+    shown_until: Option<Instant>,
+    size: (u32, u32), // Of the message's text
+}
+
+impl Notice {
+    pub fn new() -> Notice {
+        Notice {
+            id: Uuid::new_v4(),
+            shown_until: None,
+            size: (0, 0),
+        }
+    }
+
+    pub fn show(&mut self, message: &str, renderer: &mut Renderer) -> Result<(), String> {
+        self.size = renderer.create_text(
+            self.id,
+            Some(0),
+            &message.to_string(),
+            &NOTICE_FONT.to_string(),
+            FontStyle::NORMAL,
+            NOTICE_POINT,
+            NOTICE_COLOR,
+        )?;
+        self.shown_until = Some(Instant::now() + NOTICE_TIME);
+
+        Ok(())
+    }
+
+    // When the message is due to go, so that the window is drawn again without it
+    pub fn expires(&self) -> Option<Instant> {
+        self.shown_until.filter(|at| Instant::now() < *at)
+    }
+}
+
+impl Default for Notice {
+    fn default() -> Self {
+        Notice::new()
+    }
+}
+
+impl Drawable for Notice {
+    fn draw(&self, renderer: &mut Renderer) -> Result<(), String> {
+        if self.expires().is_none() {
+            return Ok(());
+        }
+
+        // Long messages are shrunk down to fit in the window
+        let room = renderer
+            .dimensions()
+            .0
+            .saturating_sub(2 * NOTICE_PADDING.0 + 40);
+        let scale = (room as f32 / self.size.0.max(1) as f32).min(1.0);
+        let text_size = (
+            (self.size.0 as f32 * scale) as u32,
+            (self.size.1 as f32 * scale) as u32,
+        );
+
+        let size = (
+            text_size.0 + 2 * NOTICE_PADDING.0,
+            text_size.1 + 2 * NOTICE_PADDING.1,
+        );
+        let position = Position::FreeOnScreen(
+            (renderer.dimensions().0 as i32 - size.0 as i32) / 2,
+            NOTICE_TOP,
+        );
+        renderer.draw_fill_rect(position, size, Color::WHITE)?;
+        renderer.draw_rect(position, 2, size, NOTICE_COLOR)?;
+
+        renderer.draw_texture(
+            self.id,
+            0,
+            DrawOptions {
+                src: None,
+                position: Position::add(position, NOTICE_PADDING.0 as i32, NOTICE_PADDING.1 as i32),
+                size: text_size,
+                rotation: None,
+                flip_h: false,
+                flip_v: false,
+            },
+        )
+    }
+}
@@ -1,12 +1,10 @@
+use crate::assets;
 use crate::drawable::{DrawOptions, Drawable};
 use crate::position::Position;
 use crate::renderer::Renderer;
 
-use std::path::Path;
-
 use uuid::Uuid;
 
-use sdl2::image::LoadSurface;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::surface::Surface;
@@ -14,8 +12,131 @@ use sdl2::surface::Surface;
 pub const SQUARE_SIZE: u32 = 31; // In pixels squared
 pub const PAGE_PADDING: i32 = 200; // Spacing between pages
 
+// The number of rows of squares that a line of text at a point size takes up
+// Text sits on the bottom of its last row, so larger text takes up more rows to fit
+pub fn line_rows(point: u16) -> u32 {
+    ((point as f32 / SQUARE_SIZE as f32).round() as u32).max(1)
+}
+
+// Pages can't be too small for their images to be stitched together, or too large for a texture
+pub const MIN_PAGE_SQUARES: u32 = 5;
+pub const MAX_PAGE_SQUARES: u32 = 200;
+
+pub fn check_page_squares(page_squares: (u32, u32)) -> Result<(), String> {
+    let range = MIN_PAGE_SQUARES..=MAX_PAGE_SQUARES;
+    if !range.contains(&page_squares.0) || !range.contains(&page_squares.1) {
+        return Err(format!(
+            "Pages must be between {} and {} squares wide and high, not {} x {}.",
+            MIN_PAGE_SQUARES, MAX_PAGE_SQUARES, page_squares.0, page_squares.1
+        ));
+    }
+
+    Ok(())
+}
+
+// Common sizes of paper, for pages printed with squares of 5 mm
+// Any other number of squares is a custom size
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PaperSize {
+    A4,
+    A5,
+    Letter,
+    IndexCard, // 5 x 3 inches
+}
+
+impl PaperSize {
+    pub const ALL: [PaperSize; 4] = [
+        PaperSize::A4,
+        PaperSize::A5,
+        PaperSize::Letter,
+        PaperSize::IndexCard,
+    ];
+
+    // The number of squares that fit on the paper in portrait, leaving out partial squares
+    pub fn page_squares(&self) -> (u32, u32) {
+        match *self {
+            PaperSize::A4 => (42, 59),
+            PaperSize::A5 => (29, 42),
+            PaperSize::Letter => (43, 55),
+            PaperSize::IndexCard => (15, 25),
+        }
+    }
+
+    // Name used on the command line
+    pub fn name(&self) -> &'static str {
+        match *self {
+            PaperSize::A4 => "a4",
+            PaperSize::A5 => "a5",
+            PaperSize::Letter => "letter",
+            PaperSize::IndexCard => "index-card",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<PaperSize> {
+        PaperSize::ALL.into_iter().find(|size| size.name() == name)
+    }
+
+    // Name shown in the page menu
+    pub fn label(&self) -> &'static str {
+        match *self {
+            PaperSize::A4 => "A4",
+            PaperSize::A5 => "A5",
+            PaperSize::Letter => "Letter",
+            PaperSize::IndexCard => "Index card",
+        }
+    }
+
+    // The paper size of pages in either orientation, or None if they are a custom size
+    pub fn of(page_squares: (u32, u32)) -> Option<PaperSize> {
+        PaperSize::ALL
+            .into_iter()
+            .find(|size| portrait(size.page_squares()) == portrait(page_squares))
+    }
+}
+
+// The same page size, taller than it is wide
+pub fn portrait(page_squares: (u32, u32)) -> (u32, u32) {
+    (
+        page_squares.0.min(page_squares.1),
+        page_squares.0.max(page_squares.1),
+    )
+}
+
+// The same page size, wider than it is tall
+pub fn landscape(page_squares: (u32, u32)) -> (u32, u32) {
+    let (w, h) = portrait(page_squares);
+    (h, w)
+}
+
+// How pages are arranged on the world, one after the other from left to right and top to bottom
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PageLayout {
+    Single, // One column of pages
+    Spread, // Two pages side by side, like an open notebook
+    Grid,   // Rows of several pages, for an overview of the document
+}
+
+impl PageLayout {
+    pub fn columns(&self) -> u32 {
+        match *self {
+            PageLayout::Single => 1,
+            PageLayout::Spread => 2,
+            PageLayout::Grid => 4,
+        }
+    }
+
+    // The layout after this one, for switching between them with a single button
+    pub fn next(&self) -> PageLayout {
+        match *self {
+            PageLayout::Single => PageLayout::Spread,
+            PageLayout::Spread => PageLayout::Grid,
+            PageLayout::Grid => PageLayout::Single,
+        }
+    }
+}
+
 // Order of page styles needs to be consistent everywhere
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum PageStyle {
     WhiteSquared = 0,
     WhitePlain = 1,
@@ -24,13 +145,50 @@ pub enum PageStyle {
 }
 
 impl PageStyle {
-    pub fn path(&self) -> &Path {
-        Path::new(match *self {
-            PageStyle::WhiteSquared => "assets/images/white_squared.png",
-            PageStyle::WhitePlain => "assets/images/white_plain.png",
-            PageStyle::BeigeSquared => "assets/images/beige_squared.png",
-            PageStyle::BeigePlain => "assets/images/beige_plain.png",
-        })
+    // The page image, inside the assets folder
+    pub fn image(&self) -> &'static str {
+        match *self {
+            PageStyle::WhiteSquared => "images/white_squared.png",
+            PageStyle::WhitePlain => "images/white_plain.png",
+            PageStyle::BeigeSquared => "images/beige_squared.png",
+            PageStyle::BeigePlain => "images/beige_plain.png",
+        }
+    }
+
+    // Name used when saving a document
+    pub fn name(&self) -> &'static str {
+        match *self {
+            PageStyle::WhiteSquared => "white-squared",
+            PageStyle::WhitePlain => "white-plain",
+            PageStyle::BeigeSquared => "beige-squared",
+            PageStyle::BeigePlain => "beige-plain",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<PageStyle> {
+        match name {
+            "white-squared" => Some(PageStyle::WhiteSquared),
+            "white-plain" => Some(PageStyle::WhitePlain),
+            "beige-squared" => Some(PageStyle::BeigeSquared),
+            "beige-plain" => Some(PageStyle::BeigePlain),
+            _ => None,
+        }
+    }
+
+    // Colors of the page images, for drawing pages without them
+    pub fn background(&self) -> Color {
+        match *self {
+            PageStyle::WhiteSquared | PageStyle::WhitePlain => Color::RGB(250, 250, 250),
+            PageStyle::BeigeSquared | PageStyle::BeigePlain => Color::RGB(253, 246, 227),
+        }
+    }
+
+    pub fn grid(&self) -> Option<Color> {
+        match *self {
+            PageStyle::WhiteSquared => Some(Color::RGB(230, 230, 230)),
+            PageStyle::BeigeSquared => Some(Color::RGB(212, 204, 184)),
+            PageStyle::WhitePlain | PageStyle::BeigePlain => None,
+        }
     }
 }
 
@@ -42,13 +200,14 @@ pub struct Pages {
     pages: u32,               // The number of pages
     square_size: u32,         // Inside of the square not counting the outline in pixels squared
     style: PageStyle,
+    layout: PageLayout,
 }
 
 impl Pages {
     // Create the page surface given a sheet image and a page size
-    fn create_surface(page_squares: (u32, u32), image_path: &Path) -> Result<Surface, String> {
+    fn create_surface(page_squares: (u32, u32), image: &str) -> Result<Surface<'_>, String> {
         // Page images come in 5x5 squares that need to be stitched together
-        let src = Surface::from_file(image_path)?;
+        let src = assets::surface(image)?;
         let mut surface = Surface::new(
             SQUARE_SIZE * page_squares.0 - 1,
             SQUARE_SIZE * page_squares.1 - 1,
@@ -93,11 +252,11 @@ impl Pages {
 
         // Create all the page style textures to switch between them
         let white_squared_sfc =
-            Pages::create_surface(page_squares, PageStyle::WhiteSquared.path())?;
-        let white_plain_sfc = Pages::create_surface(page_squares, PageStyle::WhitePlain.path())?;
+            Pages::create_surface(page_squares, PageStyle::WhiteSquared.image())?;
+        let white_plain_sfc = Pages::create_surface(page_squares, PageStyle::WhitePlain.image())?;
         let beige_squared_sfc =
-            Pages::create_surface(page_squares, PageStyle::BeigeSquared.path())?;
-        let beige_plain_sfc = Pages::create_surface(page_squares, PageStyle::BeigePlain.path())?;
+            Pages::create_surface(page_squares, PageStyle::BeigeSquared.image())?;
+        let beige_plain_sfc = Pages::create_surface(page_squares, PageStyle::BeigePlain.image())?;
 
         renderer.create_textures(
             id,
@@ -117,6 +276,7 @@ impl Pages {
             id,
             pages: 1,
             style: PageStyle::WhiteSquared,
+            layout: PageLayout::Single,
         })
     }
 
@@ -140,8 +300,31 @@ impl Pages {
         self.page_squares.1 * self.square_size - 1
     }
 
+    // The number of columns and rows of pages there are in the layout
+    fn grid_size(&self) -> (u32, u32) {
+        let columns = self.layout.columns();
+        (columns.min(self.pages), self.pages.div_ceil(columns))
+    }
+
+    pub fn total_width(&self) -> u32 {
+        let columns = self.grid_size().0;
+        columns * self.page_width() + PAGE_PADDING as u32 * (columns - 1)
+    }
+
     pub fn total_height(&self) -> u32 {
-        self.pages * self.page_height() + PAGE_PADDING as u32 * (self.pages - 1)
+        let rows = self.grid_size().1;
+        rows * self.page_height() + PAGE_PADDING as u32 * (rows - 1)
+    }
+
+    // The part of the world that can be scrolled through, which is every page with padding
+    // around them
+    pub fn scroll_area(&self) -> Rect {
+        Rect::new(
+            self.position.x() - PAGE_PADDING,
+            self.position.y() - PAGE_PADDING,
+            self.total_width() + 2 * PAGE_PADDING as u32,
+            self.total_height() + 2 * PAGE_PADDING as u32,
+        )
     }
 
     pub fn style(&self) -> PageStyle {
@@ -156,6 +339,15 @@ impl Pages {
         self.style = style
     }
 
+    pub fn layout(&self) -> PageLayout {
+        self.layout
+    }
+
+    // Marks keep where they were on the world, so they need to be placed again after this
+    pub fn set_layout(&mut self, layout: PageLayout) {
+        self.layout = layout
+    }
+
     pub fn add_page(&mut self) {
         self.pages += 1
     }
@@ -164,60 +356,103 @@ impl Pages {
         self.pages = 1.max(self.pages - 1)
     }
 
+    pub fn set_pages(&mut self, pages: u32) {
+        self.pages = 1.max(pages)
+    }
+
     // Get the FreeOnWorld position of the 0 indexed page
     pub fn get_page_position(&self, page_num: u32) -> Position {
+        let columns = self.layout.columns();
         Position::add(
             self.position(),
-            0,
-            (self.page_height() as i32 + PAGE_PADDING) * page_num as i32,
+            (self.page_width() as i32 + PAGE_PADDING) * (page_num % columns) as i32,
+            (self.page_height() as i32 + PAGE_PADDING) * (page_num / columns) as i32,
         )
     }
 
-    // Returns the 0 indexed page in which point give is located in on screen
+    // The 0 indexed page nearest to a FreeOnWorld point, which is the page the point is on if
+    // there is one, and otherwise the closest one to the gap between pages
+    pub fn nearest_page(&self, point: Position) -> u32 {
+        // Gaps between pages are split down the middle between the pages on either side
+        let nearest = |offset: i32, length: u32, count: u32| {
+            let stride = length as i32 + PAGE_PADDING;
+            (((offset + PAGE_PADDING / 2).max(0) / stride) as u32).min(count - 1)
+        };
+        let (columns, rows) = self.grid_size();
+        let column = nearest(point.x() - self.position.x(), self.page_width(), columns);
+        let row = nearest(point.y() - self.position.y(), self.page_height(), rows);
+
+        (row * self.layout.columns() + column).min(self.pages - 1)
+    }
+
+    // Returns the 0 indexed page in which a FreeOnWorld point is located
     // If it is outside any page, returns None
-    pub fn page_contains(&self, point: Position, camera: Rect) -> Option<u32> {
-        // Could be made more efficient without a for loop
-        for i in 0..self.pages {
-            let p = self
-                .get_page_position(i)
-                .to_free_on_screen(None, Some(camera))
-                .unwrap();
-
-            let rect = Rect::new(p.x(), p.y(), self.page_width(), self.page_height());
-
-            if rect.contains_point(point) {
-                return Some(i);
-            }
-        }
+    pub fn page_contains(&self, point: Position) -> Option<u32> {
+        let i = self.nearest_page(point);
+        let p = self.get_page_position(i);
+        let rect = Rect::new(p.x(), p.y(), self.page_width(), self.page_height());
 
-        None
+        rect.contains_point(point).then_some(i)
+    }
+
+    // The 0 indexed pages that are at least partly inside a FreeOnWorld rectangle, along with
+    // their outlines. Only the rows and columns of pages that the rectangle spans are looked at,
+    // so this takes as long for a hundred pages as it does for a few
+    pub fn pages_in(&self, rect: Rect) -> Vec<u32> {
+        // The columns or rows from the one holding start to the one holding end
+        let span = |start: i32, end: i32, length: u32, count: u32| {
+            let stride = length as i32 + PAGE_PADDING;
+            let first = (start.max(0) / stride) as u32;
+            let last = ((end.max(0) / stride) as u32).min(count - 1);
+            first..=last
+        };
+        let (columns, rows) = self.grid_size();
+        let x = self.position.x();
+        let y = self.position.y();
+        let columns = span(
+            rect.left() - x,
+            rect.right() - x,
+            self.page_width(),
+            columns,
+        );
+        let rows = span(rect.top() - y, rect.bottom() - y, self.page_height(), rows);
+
+        rows.flat_map(|row| {
+            columns
+                .clone()
+                .map(move |column| row * self.layout.columns() + column)
+        })
+        .filter(|page| *page < self.pages)
+        .filter(|page| {
+            let p = self.get_page_position(*page);
+            let outline = Rect::new(
+                p.x() - 3,
+                p.y() - 3,
+                self.page_width() + 6,
+                self.page_height() + 6,
+            );
+            outline.has_intersection(rect)
+        })
+        .collect()
     }
 }
 
 impl Drawable for Pages {
+    // Only pages in view are drawn
     fn draw(&self, renderer: &mut Renderer) -> Result<(), String> {
-        // Set maximum height scrollable depending on pages height
-        renderer.set_scroll_max(
-            (self.total_height() + 2 * PAGE_PADDING as u32 - renderer.dimensions().1) as i32,
-        );
+        for i in self.pages_in(renderer.view()) {
+            let position = self.get_page_position(i);
 
-        for i in 0..(self.pages as i32) {
             // Draw outline
             renderer.draw_fill_rect(
-                Position::FreeOnWorld(
-                    self.position.x() - 3,
-                    self.position.y() + (self.page_height() as i32 + PAGE_PADDING) * i - 3,
-                ),
+                Position::add(position, -3, -3),
                 (self.page_width() + 6, self.page_height() + 6),
                 Color::GRAY,
             )?;
 
             let options = DrawOptions {
                 src: None,
-                position: Position::FreeOnWorld(
-                    self.position.x(),
-                    self.position.y() + (self.page_height() as i32 + PAGE_PADDING) * i,
-                ),
+                position,
                 size: (self.page_width(), self.page_height()),
                 rotation: None,
                 flip_h: false,
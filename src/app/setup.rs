@@ -1,14 +1,31 @@
 use super::button::Button;
 use super::menu::Menu;
-use super::pages::PageStyle;
+use super::pages::{landscape, PageStyle, PaperSize};
+use super::POINTS;
 use crate::editor::{Editor, ToolType};
+use crate::mark::textbox::TEXT_COLORS;
 use crate::position::Position;
 use crate::renderer::Renderer;
 
 use std::cell::RefCell;
-use std::path::Path;
 use std::rc::Rc;
 
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::surface::Surface;
+use sdl2::ttf::FontStyle;
+
+const LABEL_FONT: &str = "NotoSerif"; // Used for labels that aren't in a font of their own
+const LABEL_SIZE: (u32, u32) = (180, 44);
+// The same size as the bottom menu's images
+const BOTTOM_LABEL_SIZE: (u32, u32) = (90, 30);
+// Backgrounds of buttons drawn by the app, when off, hovered over and clicked
+const LABEL_BACKGROUNDS: [Color; 3] = [
+    Color::WHITE,
+    Color::RGB(230, 230, 230),
+    Color::RGB(200, 200, 200),
+];
+
 // This file just sets up various UI components so that they don't clutter the App's setup function
 
 pub fn setup_bottom_menu(
@@ -27,12 +44,12 @@ pub fn setup_bottom_menu(
     // Toggles between white and beige backgrounds
     let mut page_style_button = Button::new(
         Position::FreeOnScreen(0, 0),
-        Path::new("assets/images/page_style_button.png"),
+        "images/page_style_button.png",
         renderer,
         Rc::clone(&editor),
     )?;
 
-    page_style_button.set_on_click(Box::new(|button| {
+    page_style_button.set_on_click(Box::new(|button, _| {
         let mut editor = button.editor.borrow_mut();
 
         match editor.get_pages().style() {
@@ -49,12 +66,12 @@ pub fn setup_bottom_menu(
     // Toggles the grid
     let mut grid_toggle_button = Button::new(
         Position::FreeOnScreen(0, 0),
-        Path::new("assets/images/grid_toggle_button.png"),
+        "images/grid_toggle_button.png",
         renderer,
         Rc::clone(&editor),
     )?;
 
-    grid_toggle_button.set_on_click(Box::new(|button| {
+    grid_toggle_button.set_on_click(Box::new(|button, _| {
         let mut editor = button.editor.borrow_mut();
 
         match editor.get_pages().style() {
@@ -68,9 +85,78 @@ pub fn setup_bottom_menu(
     }));
     bottom_menu.add_button(grid_toggle_button);
 
+    // Resizes the pages to a paper size, keeping them in the orientation they are in
+    for paper in PaperSize::ALL {
+        let mut paper_button = label_button(
+            paper.label(),
+            LABEL_FONT,
+            BOTTOM_LABEL_SIZE,
+            renderer,
+            Rc::clone(&editor),
+        )?;
+        paper_button.set_on_click(Box::new(move |button, renderer| {
+            let (w, h) = button.editor.borrow().get_pages().page_squares();
+            let page_squares = if w > h {
+                landscape(paper.page_squares())
+            } else {
+                paper.page_squares()
+            };
+            resize_pages(button, page_squares, renderer)
+        }));
+        bottom_menu.add_button(paper_button);
+    }
+
+    // Switches the pages between portrait and landscape
+    let mut rotate_button = label_button(
+        "Rotate",
+        LABEL_FONT,
+        BOTTOM_LABEL_SIZE,
+        renderer,
+        Rc::clone(&editor),
+    )?;
+    rotate_button.set_on_click(Box::new(|button, renderer| {
+        let (w, h) = button.editor.borrow().get_pages().page_squares();
+        resize_pages(button, (h, w), renderer)
+    }));
+    bottom_menu.add_button(rotate_button);
+
+    // Switches between one column of pages, two side by side and a grid of them
+    let mut layout_button = label_button(
+        "Layout",
+        LABEL_FONT,
+        BOTTOM_LABEL_SIZE,
+        renderer,
+        Rc::clone(&editor),
+    )?;
+    layout_button.set_on_click(Box::new(|button, renderer| {
+        let mut editor = button.editor.borrow_mut();
+        let layout = editor.get_pages().layout().next();
+        editor.set_page_layout(layout, renderer)
+    }));
+    bottom_menu.add_button(layout_button);
+
     Ok(bottom_menu)
 }
 
+// Resizes the pages, keeping the camera over them
+// Pages that can't be resized because of marks in the way are left as they are
+fn resize_pages(
+    button: &Button,
+    page_squares: (u32, u32),
+    renderer: &mut Renderer,
+) -> Result<(), String> {
+    let mut editor = button.editor.borrow_mut();
+    if let Err(e) = editor.set_page_squares(page_squares, renderer) {
+        editor.report_failure(format!("Could not resize pages: {}", e));
+        return Ok(());
+    }
+
+    renderer.set_scroll_area(editor.get_pages().scroll_area());
+    renderer.keep_in_bounds();
+
+    Ok(())
+}
+
 pub fn setup_tool_menu(
     renderer: &mut Renderer,
     editor: Rc<RefCell<Editor>>,
@@ -85,11 +171,11 @@ pub fn setup_tool_menu(
 
     let mut move_tool_button = Button::new(
         Position::FreeOnScreen(0, 0),
-        Path::new("assets/images/move_tool_button.png"),
+        "images/move_tool_button.png",
         renderer,
         Rc::clone(&editor),
     )?;
-    move_tool_button.set_on_click(Box::new(|button| {
+    move_tool_button.set_on_click(Box::new(|button, _| {
         button.editor.borrow_mut().set_tool(ToolType::Move);
         Ok(())
     }));
@@ -98,11 +184,11 @@ pub fn setup_tool_menu(
 
     let mut text_tool_button = Button::new(
         Position::FreeOnScreen(0, 0),
-        Path::new("assets/images/text_tool_button.png"),
+        "images/text_tool_button.png",
         renderer,
         Rc::clone(&editor),
     )?;
-    text_tool_button.set_on_click(Box::new(|button| {
+    text_tool_button.set_on_click(Box::new(|button, _| {
         button.editor.borrow_mut().set_tool(ToolType::Text);
         Ok(())
     }));
@@ -110,11 +196,11 @@ pub fn setup_tool_menu(
 
     let mut line_tool_button = Button::new(
         Position::FreeOnScreen(0, 0),
-        Path::new("assets/images/line_tool_button.png"),
+        "images/line_tool_button.png",
         renderer,
         Rc::clone(&editor),
     )?;
-    line_tool_button.set_on_click(Box::new(|button| {
+    line_tool_button.set_on_click(Box::new(|button, _| {
         button.editor.borrow_mut().set_tool(ToolType::Line);
         Ok(())
     }));
@@ -122,11 +208,11 @@ pub fn setup_tool_menu(
 
     let mut bullet_tool_button = Button::new(
         Position::FreeOnScreen(0, 0),
-        Path::new("assets/images/bullet_tool_button.png"),
+        "images/bullet_tool_button.png",
         renderer,
         Rc::clone(&editor),
     )?;
-    bullet_tool_button.set_on_click(Box::new(|button| {
+    bullet_tool_button.set_on_click(Box::new(|button, _| {
         button.editor.borrow_mut().set_tool(ToolType::Bullet);
         Ok(())
     }));
@@ -134,11 +220,11 @@ pub fn setup_tool_menu(
 
     let mut math_tool_button = Button::new(
         Position::FreeOnScreen(0, 0),
-        Path::new("assets/images/math_tool_button.png"),
+        "images/math_tool_button.png",
         renderer,
         Rc::clone(&editor),
     )?;
-    math_tool_button.set_on_click(Box::new(|button| {
+    math_tool_button.set_on_click(Box::new(|button, _| {
         button.editor.borrow_mut().set_tool(ToolType::Math);
         Ok(())
     }));
@@ -146,11 +232,11 @@ pub fn setup_tool_menu(
 
     let mut code_tool_button = Button::new(
         Position::FreeOnScreen(0, 0),
-        Path::new("assets/images/code_tool_button.png"),
+        "images/code_tool_button.png",
         renderer,
         Rc::clone(&editor),
     )?;
-    code_tool_button.set_on_click(Box::new(|button| {
+    code_tool_button.set_on_click(Box::new(|button, _| {
         button.editor.borrow_mut().set_tool(ToolType::Code);
         Ok(())
     }));
@@ -158,3 +244,115 @@ pub fn setup_tool_menu(
 
     Ok(tool_menu)
 }
+
+// Lists every font family, point size and text color, each as a button that makes it the one
+// used for new text and for the text selected
+// The colors are also those of new lines, while the line tool is selected
+pub fn setup_text_menu(
+    font_families: &[String],
+    renderer: &mut Renderer,
+    editor: Rc<RefCell<Editor>>,
+) -> Result<Menu, String> {
+    let count = (font_families.len() + POINTS.len() + TEXT_COLORS.len()) as u32;
+    let mut text_menu = Menu::new(
+        Position::FreeOnScreen(30, 100),
+        (LABEL_SIZE.0 + 20, count * (LABEL_SIZE.1 + 10) + 10),
+        crate::app::menu::MenuAlignment::Vertical,
+    );
+    text_menu.set_border_thickness(3);
+    text_menu.set_padding((10, 10));
+
+    // Each font's name is written in the font itself
+    for family in font_families {
+        let mut font_button =
+            label_button(family, family, LABEL_SIZE, renderer, Rc::clone(&editor))?;
+        let family = family.clone();
+        font_button.set_on_click(Box::new(move |button, renderer| {
+            button.editor.borrow_mut().set_text_font(&family, renderer)
+        }));
+        text_menu.add_button(font_button);
+    }
+
+    for point in POINTS {
+        let label = format!("{} pt", point);
+        let mut point_button =
+            label_button(&label, LABEL_FONT, LABEL_SIZE, renderer, Rc::clone(&editor))?;
+        point_button.set_on_click(Box::new(move |button, renderer| {
+            button.editor.borrow_mut().set_text_point(point, renderer)
+        }));
+        text_menu.add_button(point_button);
+    }
+
+    for color in TEXT_COLORS {
+        let mut color_button = swatch_button(color, renderer, Rc::clone(&editor))?;
+        color_button.set_on_click(Box::new(move |button, renderer| {
+            button.editor.borrow_mut().set_color(color, renderer)
+        }));
+        text_menu.add_button(color_button);
+    }
+
+    Ok(text_menu)
+}
+
+// Makes a button with a line of text on it, shrunk down if it's too large to fit
+fn label_button(
+    label: &str,
+    font_name: &str,
+    size: (u32, u32),
+    renderer: &mut Renderer,
+    editor: Rc<RefCell<Editor>>,
+) -> Result<Button, String> {
+    let text = renderer.render_text(label, font_name, FontStyle::NORMAL, 30, Color::BLACK)?;
+    let scale = ((size.0 - 20) as f32 / text.width() as f32)
+        .min(size.1 as f32 / text.height() as f32)
+        .min(1.0);
+    let (w, h) = (
+        (text.width() as f32 * scale) as u32,
+        (text.height() as f32 * scale) as u32,
+    );
+    let dst = Rect::new(
+        (size.0 as i32 - w as i32) / 2,
+        (size.1 as i32 - h as i32) / 2,
+        w,
+        h,
+    );
+
+    let mut surfaces = Vec::new();
+    for background in LABEL_BACKGROUNDS {
+        let mut surface = Surface::new(size.0, size.1, PixelFormatEnum::ARGB8888)?;
+        surface.fill_rect(None, background)?;
+        text.blit_scaled(None, &mut surface, dst)?;
+        surfaces.push(surface);
+    }
+
+    Button::from_surfaces(
+        Position::FreeOnScreen(0, 0),
+        [&surfaces[0], &surfaces[1], &surfaces[2]],
+        renderer,
+        editor,
+    )
+}
+
+// Makes a button showing a block of color
+fn swatch_button(
+    color: Color,
+    renderer: &mut Renderer,
+    editor: Rc<RefCell<Editor>>,
+) -> Result<Button, String> {
+    let swatch = Rect::new(10, 8, LABEL_SIZE.0 - 20, LABEL_SIZE.1 - 16);
+
+    let mut surfaces = Vec::new();
+    for background in LABEL_BACKGROUNDS {
+        let mut surface = Surface::new(LABEL_SIZE.0, LABEL_SIZE.1, PixelFormatEnum::ARGB8888)?;
+        surface.fill_rect(None, background)?;
+        surface.fill_rect(swatch, color)?;
+        surfaces.push(surface);
+    }
+
+    Button::from_surfaces(
+        Position::FreeOnScreen(0, 0),
+        [&surfaces[0], &surfaces[1], &surfaces[2]],
+        renderer,
+        editor,
+    )
+}
@@ -0,0 +1,188 @@
+// Finds the images and fonts that come with SquarePad, wherever it is run from
+//
+// The assets folder is the first of these that exists:
+//   $SQUAREPAD_ASSETS
+//   assets next to the executable, or next to any folder above it, which finds the repository's
+//   assets when running from target/debug
+//   squarepad/assets in each of the XDG data directories, for installed copies
+//   assets in the working directory
+//
+// With the bundled-assets feature the assets are compiled into the executable instead, and no
+// folder is needed.
+
+use crate::config;
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use sdl2::surface::Surface;
+
+pub const ASSETS_VAR: &str = "SQUAREPAD_ASSETS";
+
+// Declares every asset by its path inside the assets folder, so that missing ones can be listed
+// and so that they can all be bundled
+macro_rules! assets {
+    ($($name:literal),* $(,)?) => {
+        pub const ASSETS: &[&str] = &[$($name),*];
+
+        #[cfg(feature = "bundled-assets")]
+        const BUNDLED: &[(&str, &[u8])] = &[$(
+            ($name, include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/", $name))),
+        )*];
+    };
+}
+
+assets!(
+    "images/add_page_button.png",
+    "images/remove_page_button.png",
+    "images/page_style_button.png",
+    "images/grid_toggle_button.png",
+    "images/move_tool_button.png",
+    "images/text_tool_button.png",
+    "images/line_tool_button.png",
+    "images/bullet_tool_button.png",
+    "images/math_tool_button.png",
+    "images/code_tool_button.png",
+    "images/white_squared.png",
+    "images/white_plain.png",
+    "images/beige_squared.png",
+    "images/beige_plain.png",
+    "fonts/NotoSerif/NotoSerif.ttf",
+    "fonts/NotoSerif/NotoSerif-Bold.ttf",
+    "fonts/NotoSerif/NotoSerif-Italic.ttf",
+    "fonts/NotoSerif/NotoSerif-BoldItalic.ttf",
+    "fonts/DejaVuSansMono/DejaVuSansMono.ttf",
+    "fonts/DejaVuSansMono/DejaVuSansMono-Bold.ttf",
+    "fonts/DejaVuSansMono/DejaVuSansMono-Italic.ttf",
+    "fonts/DejaVuSansMono/DejaVuSansMono-BoldItalic.ttf",
+);
+
+// The assets folder, found the first time it is needed
+pub fn dir() -> Result<PathBuf, String> {
+    static DIR: OnceLock<Result<PathBuf, String>> = OnceLock::new();
+    DIR.get_or_init(find_dir).clone()
+}
+
+fn find_dir() -> Result<PathBuf, String> {
+    if let Some(dir) = std::env::var_os(ASSETS_VAR).filter(|dir| !dir.is_empty()) {
+        let dir = PathBuf::from(dir);
+        if !dir.is_dir() {
+            return Err(format!(
+                "{} is set to {}, which is not a folder.",
+                ASSETS_VAR,
+                dir.display()
+            ));
+        }
+        return Ok(dir);
+    }
+
+    let mut candidates = Vec::new();
+    if let Ok(exe) = std::env::current_exe().and_then(|exe| exe.canonicalize()) {
+        candidates.extend(exe.ancestors().skip(1).map(|dir| dir.join("assets")));
+    }
+    candidates.extend(
+        config::data_dirs()
+            .iter()
+            .map(|dir| dir.join("squarepad").join("assets")),
+    );
+    candidates.push(PathBuf::from("assets"));
+
+    candidates
+        .into_iter()
+        .find(|dir| dir.is_dir())
+        .ok_or_else(|| {
+            format!(
+                "Could not find SquarePad's assets folder. Set {} to where it is.",
+                ASSETS_VAR
+            )
+        })
+}
+
+// Makes sure every asset is there, so that a missing one is reported before anything is drawn
+// rather than partway through setting up the window
+pub fn check() -> Result<(), String> {
+    if cfg!(feature = "bundled-assets") {
+        return Ok(());
+    }
+
+    let dir = dir()?;
+    let missing: Vec<&str> = ASSETS
+        .iter()
+        .filter(|name| !dir.join(name).is_file())
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "Missing assets in {}:\n  {}\nSet {} to the folder holding SquarePad's assets.",
+            dir.display(),
+            missing.join("\n  "),
+            ASSETS_VAR
+        ));
+    }
+
+    Ok(())
+}
+
+// Loads an image asset, given its path inside the assets folder
+#[cfg(not(feature = "bundled-assets"))]
+pub fn surface(name: &str) -> Result<Surface<'static>, String> {
+    use sdl2::image::LoadSurface;
+
+    let path = dir()?.join(name);
+    if !path.is_file() {
+        return Err(format!(
+            "Missing asset {} in {}.",
+            name,
+            path.parent().unwrap().display()
+        ));
+    }
+    Surface::from_file(&path).map_err(|e| format!("Could not load {}: {}", path.display(), e))
+}
+
+#[cfg(feature = "bundled-assets")]
+pub fn surface(name: &str) -> Result<Surface<'static>, String> {
+    use sdl2::image::ImageRWops;
+    use sdl2::rwops::RWops;
+
+    let data = bundled(name)?;
+    RWops::from_bytes(data)?
+        .load()
+        .map_err(|e| format!("Could not load {}: {}", name, e))
+}
+
+#[cfg(feature = "bundled-assets")]
+fn bundled(name: &str) -> Result<&'static [u8], String> {
+    BUNDLED
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, data)| *data)
+        .ok_or_else(|| format!("Missing asset {}, which is not bundled.", name))
+}
+
+// The folder holding the fonts that come with SquarePad
+#[cfg(not(feature = "bundled-assets"))]
+pub fn font_dir() -> Result<PathBuf, String> {
+    Ok(dir()?.join("fonts"))
+}
+
+// Fonts are looked up by file, so bundled ones are written to the cache the first time they are
+// needed. The folder is named after the version so that an upgrade doesn't use older fonts
+#[cfg(feature = "bundled-assets")]
+pub fn font_dir() -> Result<PathBuf, String> {
+    let dir = config::cache_home()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("squarepad")
+        .join(concat!("assets-", env!("CARGO_PKG_VERSION")));
+
+    for (name, data) in BUNDLED.iter().filter(|(n, _)| n.starts_with("fonts/")) {
+        let path = dir.join(name);
+        if path.is_file() {
+            continue;
+        }
+        std::fs::create_dir_all(path.parent().unwrap())
+            .and_then(|_| std::fs::write(&path, data))
+            .map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+    }
+
+    Ok(dir.join("fonts"))
+}
@@ -0,0 +1,284 @@
+// Command-line use of SquarePad without opening its window
+//
+//   squarepad [notes.sqpd]
+//   squarepad export notes.sqpd --format png|pdf|md [--out dir]
+//   squarepad info notes.sqpd
+//   squarepad embed-fonts notes.sqpd
+//   squarepad page-size notes.sqpd a4|a5|letter|index-card|<width>x<height> [--landscape]
+
+use crate::app::pages::{check_page_squares, landscape, portrait, PaperSize};
+use crate::assets;
+use crate::config::Config;
+use crate::document::Document;
+use crate::export;
+use crate::fonts::FontLibrary;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "Usage:
+  squarepad [notes.sqpd]                                   Open the editor
+  squarepad export notes.sqpd --format png|pdf|md [--out dir]
+                                                           Convert a document
+  squarepad info notes.sqpd                                Describe a document
+  squarepad embed-fonts notes.sqpd                         Save a document's fonts into it
+  squarepad page-size notes.sqpd a4|a5|letter|index-card|<width>x<height> [--landscape]
+                                                           Resize a document's pages, where
+                                                           custom sizes are in squares";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Png,
+    Pdf,
+    Markdown,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "png" => Some(Format::Png),
+            "pdf" => Some(Format::Pdf),
+            "md" | "markdown" => Some(Format::Markdown),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Open(Option<PathBuf>), // Run the editor, with a document to open
+    Export {
+        path: PathBuf,
+        format: Format,
+        out_dir: PathBuf,
+    },
+    Info(PathBuf),
+    EmbedFonts(PathBuf),
+    PageSize {
+        path: PathBuf,
+        page_squares: (u32, u32),
+    },
+    Help,
+}
+
+impl Command {
+    // Reads a command from the program's arguments, without the program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+        let mut args = args.into_iter();
+
+        match args.next().as_deref() {
+            None => Ok(Command::Open(None)),
+            Some("-h" | "--help" | "help") => Ok(Command::Help),
+            Some("info") => {
+                let path = args.next().ok_or("info needs a document.")?;
+                if let Some(arg) = args.next() {
+                    return Err(format!("Unexpected argument '{}'.", arg));
+                }
+                Ok(Command::Info(PathBuf::from(path)))
+            }
+            Some("embed-fonts") => {
+                let path = args.next().ok_or("embed-fonts needs a document.")?;
+                if let Some(arg) = args.next() {
+                    return Err(format!("Unexpected argument '{}'.", arg));
+                }
+                Ok(Command::EmbedFonts(PathBuf::from(path)))
+            }
+            Some("page-size") => {
+                let path = args.next().ok_or("page-size needs a document.")?;
+                let size = args.next().ok_or("page-size needs a size.")?;
+                let mut landscaped = None; // Whether --landscape or --portrait was given
+                for arg in args {
+                    match arg.as_str() {
+                        "--landscape" => landscaped = Some(true),
+                        "--portrait" => landscaped = Some(false),
+                        _ => return Err(format!("Unexpected argument '{}'.", arg)),
+                    }
+                }
+
+                // Paper sizes are portrait unless asked otherwise, and custom sizes are as given
+                let page_squares = match PaperSize::from_name(&size) {
+                    Some(paper) => paper.page_squares(),
+                    None => size
+                        .split_once('x')
+                        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                        .ok_or_else(|| format!("Unknown page size '{}'.", size))?,
+                };
+                let page_squares = match landscaped {
+                    Some(true) => landscape(page_squares),
+                    Some(false) => portrait(page_squares),
+                    None => page_squares,
+                };
+                check_page_squares(page_squares)?;
+
+                Ok(Command::PageSize {
+                    path: PathBuf::from(path),
+                    page_squares,
+                })
+            }
+            Some("export") => {
+                let mut path = None;
+                let mut format = None;
+                let mut out_dir = None;
+
+                while let Some(arg) = args.next() {
+                    match arg.as_str() {
+                        "--format" | "-f" => {
+                            let name = args.next().ok_or("--format needs a value.")?;
+                            format = Some(
+                                Format::from_name(&name)
+                                    .ok_or_else(|| format!("Unknown format '{}'.", name))?,
+                            );
+                        }
+                        "--out" | "-o" => {
+                            out_dir =
+                                Some(PathBuf::from(args.next().ok_or("--out needs a value.")?));
+                        }
+                        _ if arg.starts_with('-') => {
+                            return Err(format!("Unknown option '{}'.", arg))
+                        }
+                        _ if path.is_none() => path = Some(PathBuf::from(arg)),
+                        _ => return Err(format!("Unexpected argument '{}'.", arg)),
+                    }
+                }
+
+                Ok(Command::Export {
+                    path: path.ok_or("export needs a document.")?,
+                    format: format.ok_or("export needs a --format.")?,
+                    out_dir: out_dir.unwrap_or_else(|| PathBuf::from(".")),
+                })
+            }
+            Some(arg) if arg.starts_with('-') => Err(format!("Unknown option '{}'.", arg)),
+            Some(path) => match args.next() {
+                None => Ok(Command::Open(Some(PathBuf::from(path)))),
+                Some(arg) => Err(format!("Unexpected argument '{}'.", arg)),
+            },
+        }
+    }
+}
+
+// Prints a summary of a document
+pub fn info(path: &Path) -> Result<(), String> {
+    let document = Document::read(path)?;
+
+    println!("{}", path.display());
+    let (w, h) = document.page_squares;
+    let paper = match PaperSize::of(document.page_squares) {
+        Some(paper) if w > h => format!("{} landscape", paper.label()),
+        Some(paper) => paper.label().to_string(),
+        None => "custom".to_string(),
+    };
+    println!(
+        "Pages: {} ({} x {} squares, {}, {})",
+        document.pages,
+        w,
+        h,
+        paper,
+        document.style.name()
+    );
+    println!("Marks: {}", document.marks.len());
+
+    let mut kinds: BTreeMap<&str, usize> = BTreeMap::new();
+    for mark in &document.marks {
+        *kinds.entry(mark.kind()).or_default() += 1;
+    }
+    for (kind, count) in kinds {
+        println!("  {}: {}", kind, count);
+    }
+    if !document.fonts.is_empty() {
+        println!("Embedded fonts: {}", document.fonts.len());
+    }
+
+    for page in 0..document.pages {
+        let count = document.marks.iter().filter(|m| m.page() == page).count();
+        println!("Page {}: {} marks", page + 1, count);
+    }
+
+    Ok(())
+}
+
+// Saves the fonts a document uses into it, replacing any it already had
+pub fn embed_fonts(path: &Path) -> Result<(), String> {
+    let mut document = Document::read(path)?;
+
+    // Fonts already embedded are kept for families that aren't installed on this machine
+    let mut library = FontLibrary::discover(&Config::load()?)?;
+    library.add_embedded(&document.fonts)?;
+    document.fonts = library.embed(&document)?;
+    document.write(path)?;
+
+    for font in &document.fonts {
+        println!("{} ({} bytes)", font.family, font.data.len());
+    }
+
+    Ok(())
+}
+
+// Resizes the pages of a document, as long as all of its marks still fit
+pub fn page_size(path: &Path, page_squares: (u32, u32)) -> Result<(), String> {
+    let mut document = Document::read(path)?;
+
+    // Text isn't laid out here, so only the squares that marks start on are checked
+    if let Some(mark) = document.mark_outside(page_squares) {
+        return Err(format!(
+            "The {} mark at square ({}, {}) of page {} doesn't fit on pages of {} x {} squares.",
+            mark.kind(),
+            mark.square().0,
+            mark.square().1,
+            mark.page() + 1,
+            page_squares.0,
+            page_squares.1
+        ));
+    }
+    document.page_squares = page_squares;
+
+    document.write(path)
+}
+
+// Writes a document out in another format, printing the path of every file written
+pub fn export(path: &Path, format: Format, out_dir: &Path) -> Result<(), String> {
+    let document = Document::read(path)?;
+    let name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("notes")
+        .to_string();
+
+    // Both draw text in the fonts that come with SquarePad, and PNGs use the page images too
+    if format != Format::Markdown {
+        assets::check()?;
+    }
+
+    std::fs::create_dir_all(out_dir)
+        .map_err(|e| format!("Could not create {}: {}", out_dir.display(), e))?;
+
+    let written = match format {
+        Format::Pdf => {
+            let out = out_dir.join(format!("{}.pdf", name));
+            export::pdf::export(&document, &out)?;
+            vec![out]
+        }
+        Format::Markdown => {
+            let out = out_dir.join(format!("{}.md", name));
+            export::markdown::export(&document, &out)?;
+            vec![out]
+        }
+        Format::Png => {
+            // Pages are drawn in a hidden window, which doesn't need a display with this driver
+            if std::env::var_os("SDL_VIDEODRIVER").is_none() {
+                std::env::set_var("SDL_VIDEODRIVER", "dummy");
+            }
+            let sdl = sdl2::init()?;
+            let video = sdl.video()?;
+            let ttf = sdl2::ttf::init().map_err(|e| e.to_string())?;
+            let _image = sdl2::image::init(sdl2::image::InitFlag::PNG)?;
+
+            export::png::export(&video, &ttf, &document, out_dir, &name)?
+        }
+    };
+
+    for path in written {
+        println!("{}", path.display());
+    }
+
+    Ok(())
+}
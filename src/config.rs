@@ -0,0 +1,107 @@
+// Settings that apply to every document, read from $XDG_CONFIG_HOME/squarepad/config
+// Each line is a setting followed by its value, and lines starting with # are comments:
+//
+//   font-dir <path>       Another folder to look for fonts in, which can be given more than once
+//   embed-fonts yes|no    Whether documents are saved along with the fonts they use
+//
+// A missing config file is the same as an empty one. Paths starting with ~/ are in the home
+// folder.
+
+use std::path::PathBuf;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    pub font_dirs: Vec<PathBuf>,
+    pub embed_fonts: bool,
+}
+
+impl Config {
+    pub fn load() -> Result<Config, String> {
+        let path = match config_home() {
+            Some(dir) => dir.join("squarepad").join("config"),
+            None => return Ok(Config::default()),
+        };
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        Config::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(contents: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (setting, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let value = value.trim();
+
+            match setting {
+                "font-dir" if !value.is_empty() => config.font_dirs.push(expand_home(value)),
+                "font-dir" => return Err(format!("Line {}: font-dir needs a path.", i + 1)),
+                "embed-fonts" => {
+                    config.embed_fonts = match value {
+                        "yes" | "true" => true,
+                        "no" | "false" => false,
+                        _ => return Err(format!("Line {}: embed-fonts must be yes or no.", i + 1)),
+                    }
+                }
+                _ => return Err(format!("Line {}: unknown setting '{}'.", i + 1, setting)),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+// An XDG base directory, or its default inside the home folder when it isn't set
+// Relative paths are ignored, as the XDG spec asks
+fn xdg_dir(var: &str, default: &str) -> Option<PathBuf> {
+    match std::env::var_os(var).map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => Some(dir),
+        _ => home_dir().map(|home| home.join(default)),
+    }
+}
+
+pub fn config_home() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+pub fn data_home() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+pub fn cache_home() -> Option<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+// The system-wide data directories, most important first
+pub fn data_dirs() -> Vec<PathBuf> {
+    let dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+    dirs.split(':')
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .collect()
+}
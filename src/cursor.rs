@@ -1,5 +1,5 @@
 use crate::drawable::Drawable;
-use crate::editor::Editor;
+use crate::editor::{Editor, ToolType};
 use crate::position::{PageSquare, Position};
 use crate::renderer::Renderer;
 
@@ -9,13 +9,16 @@ use std::rc::Rc;
 use sdl2::event::Event;
 use sdl2::mouse::MouseButton;
 use sdl2::pixels::Color;
-use sdl2::rect::Rect;
 
 pub struct Cursor {
     position: Position,
     editor: Rc<RefCell<Editor>>,
     current_page_square: Option<PageSquare>, // (page#, x, y) of the square that the cursor is on
     click_page_square: Option<PageSquare>,
+    page_offset: (i32, i32), // Pixels from the top-left of the page that the cursor is on
+    pressed_text: bool,      // Whether the last press was on a mark that can be typed into
+    current_corner: Option<(u32, (u32, u32))>, // (page#, (x, y)) of the nearest square corner
+    click_corner: Option<(u32, (u32, u32))>,
 }
 
 impl Cursor {
@@ -25,29 +28,50 @@ impl Cursor {
             editor,
             current_page_square: None,
             click_page_square: None,
+            page_offset: (0, 0),
+            pressed_text: false,
+            current_corner: None,
+            click_corner: None,
         }
     }
 
-    // Only updates the position of the cursor
-    pub fn handle_event(&mut self, e: &Event, camera: Rect) -> Result<(), String> {
+    // Only updates the position of the cursor, returning whether that changed what is drawn
+    pub fn handle_event(&mut self, e: &Event, renderer: &mut Renderer) -> Result<bool, String> {
         match e {
             // Updates cursor position
             Event::MouseMotion { x, y, .. } => {
                 self.position = Position::FreeOnScreen(*x, *y);
+                let last = (self.current_page_square, self.current_corner);
 
                 let editor = self.editor.borrow();
                 let pages = editor.get_pages();
                 let square_size = pages.square_size();
 
-                match pages.page_contains(self.position, camera) {
-                    None => self.current_page_square = None,
+                // Squares are found on the world, so that they are the same at any zoom
+                let world = renderer.to_world(self.position)?;
+                match pages.page_contains(world) {
+                    None => {
+                        self.current_page_square = None;
+                        self.current_corner = None;
+                    }
                     Some(i) => {
-                        // The FreeOnScreen position of the page that the cursor is on top of
-                        let p = pages
-                            .get_page_position(i)
-                            .to_free_on_screen(None, Some(camera))?;
+                        // The FreeOnWorld position of the page that the cursor is on top of
+                        let p = pages.get_page_position(i);
+
+                        let d = Position::add(world, -p.x(), -p.y());
+                        self.page_offset = (d.x(), d.y());
 
-                        let d = Position::add(self.position, -p.x(), -p.y());
+                        // Round to the closest corner rather than the square's top-left one
+                        let half = square_size as i32 / 2;
+                        self.current_corner = Some((
+                            i,
+                            (
+                                (((d.x() + half) / square_size as i32) as u32)
+                                    .min(pages.page_squares().0),
+                                (((d.y() + half) / square_size as i32) as u32)
+                                    .min(pages.page_squares().1),
+                            ),
+                        ));
 
                         self.current_page_square = Some(PageSquare::new(
                             i,
@@ -56,37 +80,90 @@ impl Cursor {
                         )?);
                     }
                 }
+                drop(editor);
+                self.editor
+                    .borrow_mut()
+                    .set_hovered(self.current_page_square);
+
+                // Dragging with a typing tool selects text, or the marks dragged across
+                if let (Some(click), Some(current)) =
+                    (self.click_page_square, self.current_page_square)
+                {
+                    self.editor.borrow_mut().drag_text(
+                        click,
+                        current,
+                        self.page_offset,
+                        renderer,
+                    )?;
+                }
 
-                Ok(())
+                // The cursor is drawn square by square, but text is selected up to the pixel
+                let moved = (self.current_page_square, self.current_corner) != last;
+                Ok(moved || (self.pressed_text && self.click_page_square.is_some()))
             }
             Event::MouseButtonDown {
                 mouse_btn: MouseButton::Left,
                 ..
             } => {
                 self.click_page_square = self.current_page_square;
-                Ok(())
+                self.click_corner = self.current_corner;
+
+                // Pressing on a mark picks it up, so it can be dragged straight away
+                let mut editor = self.editor.borrow_mut();
+                if let ToolType::Move = editor.get_tool() {
+                    editor.select_at(self.current_page_square)
+                }
+
+                // Pressing on text with a typing tool moves the caret there
+                self.pressed_text = match self.current_page_square {
+                    Some(current) => editor.press_text(current, self.page_offset, renderer)?,
+                    None => false,
+                };
+                Ok(true)
             }
             Event::MouseButtonUp {
                 mouse_btn: MouseButton::Left,
                 ..
             } => {
                 match self.current_page_square {
-                    Some(current) => match self.click_page_square {
+                    Some(current) => match self.click_page_square.take() {
                         Some(click) => {
+                            let tool = self.editor.borrow().get_tool();
                             if click.eq(&current) {
-                                self.editor.borrow_mut().handle_click(click)
+                                // Clicks on text only move the caret, rather than adding a mark
+                                if !self.pressed_text {
+                                    self.editor.borrow_mut().handle_click(click, renderer)?
+                                }
+                            } else if let ToolType::Move = tool {
+                                self.editor
+                                    .borrow_mut()
+                                    .move_selection(click, current, renderer)?
                             }
                         }
                         None => (),
                     },
-                    None => (),
+                    None => self.click_page_square = None,
                 }
-                Ok(())
+
+                // Dragging between two corners
+                if let (Some(click), Some(current)) =
+                    (self.click_corner.take(), self.current_corner)
+                {
+                    if click != current {
+                        self.editor.borrow_mut().handle_drag(click, current)
+                    }
+                }
+                Ok(true)
             }
-            _ => Ok(()),
+            _ => Ok(false),
         }
     }
 
+    // Where the mouse is on screen
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
     pub fn page_square(&self) -> Option<PageSquare> {
         self.current_page_square
     }
@@ -99,19 +176,60 @@ impl Drawable for Cursor {
         let pages = editor.get_pages();
         let square_size = pages.square_size();
 
-        // The FreeOnScreen position of the page that the cursor is on top of
-        let p;
-        match pages.page_contains(self.position, renderer.camera()) {
-            None => return Ok(()),
-            Some(i) => {
-                p = pages
-                    .get_page_position(i)
-                    .to_free_on_screen(None, Some(renderer.camera()))?
+        // Preview the line being dragged out by the line tool
+        if let (ToolType::Line, Some(click), Some(current)) =
+            (editor.get_tool(), self.click_corner, self.current_corner)
+        {
+            if click.0 == current.0 {
+                let p = pages.get_page_position(click.0);
+                let corner = |c: (u32, u32)| {
+                    Position::add(
+                        p,
+                        (c.0 * square_size) as i32 - 1,
+                        (c.1 * square_size) as i32 - 1,
+                    )
+                };
+                renderer.draw_line(
+                    corner(click.1),
+                    corner(current.1),
+                    editor.line_thickness(),
+                    editor.line_color(),
+                )?;
             }
         }
 
+        // Preview where the selected mark will be dropped
+        if let (ToolType::Move, Some(click), Some(current), Some((key, (w, h)))) = (
+            editor.get_tool(),
+            self.click_page_square,
+            self.current_page_square,
+            editor.selection(),
+        ) {
+            if click != current {
+                let dx = current.square.0 as i32 - click.square.0 as i32;
+                let dy = current.square.1 as i32 - click.square.1 as i32;
+                renderer.draw_rect(
+                    Position::add(
+                        pages.get_page_position(current.page),
+                        (key.square.0 as i32 + dx) * square_size as i32,
+                        (key.square.1 as i32 + dy) * square_size as i32,
+                    ),
+                    1,
+                    (w * square_size - 1, h * square_size - 1),
+                    Color::GRAY,
+                )?;
+            }
+        }
+
+        // The FreeOnWorld position of the page that the cursor is on top of
+        let world = renderer.to_world(self.position)?;
+        let p = match pages.page_contains(world) {
+            None => return Ok(()),
+            Some(i) => pages.get_page_position(i),
+        };
+
         // Vector math to calculate position of cursor box
-        let d = Position::add(self.position, -p.x(), -p.y());
+        let d = Position::add(world, -p.x(), -p.y());
         let s = Position::add(
             p,
             (d.x() / square_size as i32) * square_size as i32,
@@ -124,17 +242,17 @@ impl Drawable for Cursor {
         // Draw semi transparent rectangles in the four directions,
         // from the cursor box to the edeges of the page
         renderer.draw_fill_rect(
-            Position::FreeOnScreen(s.x(), p.y()),
+            Position::FreeOnWorld(s.x(), p.y()),
             (square_size - 1, (s.y() - p.y()) as u32),
             Color::RGBA(0, 0, 0, 50),
         )?;
         renderer.draw_fill_rect(
-            Position::FreeOnScreen(p.x(), s.y()),
+            Position::FreeOnWorld(p.x(), s.y()),
             ((s.x() - p.x()) as u32, square_size - 1),
             Color::RGBA(0, 0, 0, 50),
         )?;
         renderer.draw_fill_rect(
-            Position::FreeOnScreen(s.x(), s.y() + square_size as i32),
+            Position::FreeOnWorld(s.x(), s.y() + square_size as i32),
             (
                 square_size - 1,
                 (p.y() + pages.page_height() as i32 - s.y()) as u32 - (square_size - 1),
@@ -142,7 +260,7 @@ impl Drawable for Cursor {
             Color::RGBA(0, 0, 0, 50),
         )?;
         renderer.draw_fill_rect(
-            Position::FreeOnScreen(s.x() + square_size as i32, s.y()),
+            Position::FreeOnWorld(s.x() + square_size as i32, s.y()),
             (
                 (p.x() + pages.page_width() as i32 - s.x()) as u32 - (square_size - 1),
                 square_size - 1,
@@ -0,0 +1,1180 @@
+use crate::app::pages::{check_page_squares, PageStyle};
+use crate::mark::bullet_list::BulletKind;
+use crate::mark::textbox::{paragraphs, LineBreak, StyleSpan, TextStyle};
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use sdl2::pixels::Color;
+use sdl2::ttf::FontStyle;
+
+// SquarePad documents (.sqpd) are plain UTF-8 text, one record per line:
+//
+//   squarepad <version>
+//   pages <count> <squares wide> <squares high> <style>
+//   text <page> <x> <y> <point> <font style bits> <r> <g> <b> <a> <max width> <font name>
+//   line <text>
+//   wrap <text>
+//   hyphen <text>
+//   span <start> <end> <font style bits> <r> <g> <b> <a>
+//   end
+//   stroke <page> <x1> <y1> <x2> <y2> <thickness> <r> <g> <b> <a>
+//   bullets <page> <x> <y> <kind> <point> <font style bits> <r> <g> <b> <a> <max width> <font name>
+//   item <level>
+//   math <page> <x> <y> <point> <r> <g> <b> <a> <font name>
+//   code <page> <x> <y> <language> <point> <max width> <font name>
+//   image <page> <x> <y> <squares wide> <squares high> <path>
+//   font <font style bits> <family>
+//   data <base64>
+//
+// A `text` record is followed by one `line` record for each line of the text box and is closed
+// by `end`. Backslashes and newlines inside a line are escaped as `\\` and `\n`.
+// Lines of text boxes and bullet items that were wrapped onto the next line are written as `wrap`
+// records instead, or `hyphen` records when a word was split and the line ends in an added
+// hyphen, so that paragraphs can be joined back together.
+// Each of those records may be followed by `span` records for runs of the line, between two byte
+// indices, that are drawn in another style than the rest of the text box or bullet list.
+// A `stroke` record is a drawn line between two square corners, where corner (x, y) is the
+// top-left corner of square (x, y).
+// A `bullets` record is followed by an `item` record for each item of the list, each followed by
+// the item's `line` records, and the list is closed by `end`.
+// A `math` record is followed by a single `line` record holding the formula's LaTeX source and
+// is closed by `end`.
+// A `code` record is followed by one `line` record for each line of the code block, with tabs
+// kept as they were typed, and is closed by `end`.
+// An `image` record is followed by `data` records holding the image file in base64, so that the
+// document opens without the file, and is closed by `end`. Its path is where the image was
+// pasted from.
+// A `font` record is a font file embedded in the document, so that it looks the same on machines
+// without that font installed. It is followed by `data` records holding the file in base64, and
+// is closed by `end`. Fonts are written after all of the marks.
+// Files written by a newer version of SquarePad are rejected rather than partially read.
+// Version 2 added `wrap` and `hyphen` records, and version 3 the `data` records of images.
+// Files of earlier versions are still read, with their images loaded from their paths.
+
+pub const FORMAT_VERSION: u32 = 3;
+pub const FILE_EXTENSION: &str = "sqpd";
+
+const DATA_LINE_BYTES: usize = 57; // Bytes of file data per line, which is 76 base64 characters
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Everything needed to rebuild a notebook, independently of SDL textures
+#[derive(Clone, Debug, PartialEq)]
+pub struct Document {
+    pub page_squares: (u32, u32),
+    pub pages: u32,
+    pub style: PageStyle,
+    pub marks: Vec<MarkData>,
+    pub fonts: Vec<FontData>, // Font files saved along with the document
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FontData {
+    pub family: String, // As the marks name it, which may differ from the file's own name
+    pub style: FontStyle,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MarkData {
+    Text(TextData),
+    Line(LineData),
+    Bullets(BulletData),
+    Math(MathData),
+    Code(CodeData),
+    Image(ImageData),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextData {
+    pub page: u32,
+    pub square: (u32, u32),
+    pub font_name: String,
+    pub font_style: FontStyle,
+    pub point: u16,
+    pub color: Color,
+    pub max_width: u32,
+    pub lines: Vec<String>,
+    pub breaks: Vec<LineBreak>,     // How each of the lines ends
+    pub spans: Vec<Vec<StyleSpan>>, // Runs of each line in other styles
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineData {
+    pub page: u32,
+    pub start: (u32, u32),
+    pub end: (u32, u32),
+    pub thickness: u32,
+    pub color: Color,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BulletData {
+    pub page: u32,
+    pub square: (u32, u32),
+    pub kind: BulletKind,
+    pub font_name: String,
+    pub font_style: FontStyle,
+    pub point: u16,
+    pub color: Color,
+    pub max_width: u32,
+    pub items: Vec<BulletItemData>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BulletItemData {
+    pub level: u32,
+    pub lines: Vec<String>,
+    pub breaks: Vec<LineBreak>,
+    pub spans: Vec<Vec<StyleSpan>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MathData {
+    pub page: u32,
+    pub square: (u32, u32),
+    pub font_name: String,
+    pub point: u16,
+    pub color: Color,
+    pub source: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CodeData {
+    pub page: u32,
+    pub square: (u32, u32),
+    pub language: String,
+    pub font_name: String,
+    pub point: u16,
+    pub max_width: u32,
+    pub lines: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageData {
+    pub page: u32,
+    pub square: (u32, u32),
+    pub size: (u32, u32), // In squares
+    pub path: String,
+    pub data: Rc<[u8]>, // The image file, which is empty for documents that only had its path
+}
+
+impl MarkData {
+    pub fn page(&self) -> u32 {
+        match self {
+            MarkData::Text(data) => data.page,
+            MarkData::Line(data) => data.page,
+            MarkData::Bullets(data) => data.page,
+            MarkData::Math(data) => data.page,
+            MarkData::Code(data) => data.page,
+            MarkData::Image(data) => data.page,
+        }
+    }
+
+    // The square that the mark is stored under on its page
+    pub fn square(&self) -> (u32, u32) {
+        match self {
+            MarkData::Text(data) => data.square,
+            MarkData::Line(data) => crate::mark::line::Line::top_left(data.start, data.end),
+            MarkData::Bullets(data) => data.square,
+            MarkData::Math(data) => data.square,
+            MarkData::Code(data) => data.square,
+            MarkData::Image(data) => data.square,
+        }
+    }
+
+    // The mark's text as it is copied, for marks that have any
+    pub fn text(&self) -> Option<String> {
+        match self {
+            MarkData::Text(data) => Some(paragraphs(&data.lines, &data.breaks).join("\n")),
+            MarkData::Bullets(data) => {
+                let markers = data.kind.markers(data.items.iter().map(|item| item.level));
+                let items: Vec<String> = data
+                    .items
+                    .iter()
+                    .zip(markers)
+                    .map(|(item, marker)| {
+                        format!(
+                            "{}{} {}",
+                            "    ".repeat(item.level as usize),
+                            marker,
+                            paragraphs(&item.lines, &item.breaks).join(" ")
+                        )
+                    })
+                    .collect();
+                Some(items.join("\n"))
+            }
+            MarkData::Math(data) => Some(data.source.clone()),
+            MarkData::Code(data) => Some(data.lines.join("\n")),
+            MarkData::Line(_) | MarkData::Image(_) => None,
+        }
+    }
+
+    // The font family the mark's text is drawn in, for marks that have any
+    pub fn font_name(&self) -> Option<&str> {
+        match self {
+            MarkData::Text(data) => Some(&data.font_name),
+            MarkData::Bullets(data) => Some(&data.font_name),
+            MarkData::Math(data) => Some(&data.font_name),
+            MarkData::Code(data) => Some(&data.font_name),
+            MarkData::Line(_) | MarkData::Image(_) => None,
+        }
+    }
+
+    // The number of squares the mark covers, as far as can be told without laying out its text
+    // Marks with text cover at least their own square
+    pub fn min_size(&self) -> (u32, u32) {
+        match self {
+            MarkData::Line(data) => (
+                data.start.0.abs_diff(data.end.0).max(1),
+                data.start.1.abs_diff(data.end.1).max(1),
+            ),
+            MarkData::Image(data) => data.size,
+            MarkData::Text(_) | MarkData::Bullets(_) | MarkData::Math(_) | MarkData::Code(_) => {
+                (1, 1)
+            }
+        }
+    }
+
+    // The name of the mark's record in a saved document
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MarkData::Text(_) => "text",
+            MarkData::Line(_) => "stroke",
+            MarkData::Bullets(_) => "bullets",
+            MarkData::Math(_) => "math",
+            MarkData::Code(_) => "code",
+            MarkData::Image(_) => "image",
+        }
+    }
+
+    // The same mark, stored under another square, possibly on another page
+    pub fn moved(&self, page: u32, square: (u32, u32)) -> MarkData {
+        let mut data = self.clone();
+        match &mut data {
+            MarkData::Text(data) => {
+                data.page = page;
+                data.square = square;
+            }
+            MarkData::Line(data) => {
+                // Both corners keep their offset from the line's top-left square
+                let top_left = crate::mark::line::Line::top_left(data.start, data.end);
+                let shift = |corner: (u32, u32)| {
+                    (
+                        corner.0 - top_left.0 + square.0,
+                        corner.1 - top_left.1 + square.1,
+                    )
+                };
+                data.page = page;
+                data.start = shift(data.start);
+                data.end = shift(data.end);
+            }
+            MarkData::Bullets(data) => {
+                data.page = page;
+                data.square = square;
+            }
+            MarkData::Math(data) => {
+                data.page = page;
+                data.square = square;
+            }
+            MarkData::Code(data) => {
+                data.page = page;
+                data.square = square;
+            }
+            MarkData::Image(data) => {
+                data.page = page;
+                data.square = square;
+            }
+        }
+
+        data
+    }
+}
+
+impl Document {
+    pub fn read(path: &Path) -> Result<Document, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+
+        Document::parse(&contents)
+    }
+
+    // The document is written next to the file first and then moved over it, so that a write
+    // that fails part of the way through leaves the file as it was
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+
+        let written = fs::File::create(&temp)
+            .and_then(|mut file| {
+                file.write_all(self.serialize().as_bytes())?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&temp, path));
+        written.map_err(|e| {
+            let _ = fs::remove_file(&temp);
+            format!("Could not write {}: {}", path.display(), e)
+        })
+    }
+
+    // The first mark that goes past the edge of pages of a size
+    pub fn mark_outside(&self, page_squares: (u32, u32)) -> Option<&MarkData> {
+        self.marks.iter().find(|mark| {
+            let (w, h) = mark.min_size();
+            mark.square().0 + w > page_squares.0 || mark.square().1 + h > page_squares.1
+        })
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut out = format!("squarepad {}\n", FORMAT_VERSION);
+        out.push_str(&format!(
+            "pages {} {} {} {}\n",
+            self.pages,
+            self.page_squares.0,
+            self.page_squares.1,
+            self.style.name()
+        ));
+
+        for mark in &self.marks {
+            match mark {
+                MarkData::Text(data) => {
+                    out.push_str(&format!(
+                        "text {} {} {} {} {} {} {} {} {} {} {}\n",
+                        data.page,
+                        data.square.0,
+                        data.square.1,
+                        data.point,
+                        data.font_style.bits(),
+                        data.color.r,
+                        data.color.g,
+                        data.color.b,
+                        data.color.a,
+                        data.max_width,
+                        data.font_name
+                    ));
+                    write_text_lines(&mut out, &data.lines, &data.breaks, &data.spans);
+                    out.push_str("end\n");
+                }
+                MarkData::Line(data) => {
+                    out.push_str(&format!(
+                        "stroke {} {} {} {} {} {} {} {} {} {}\n",
+                        data.page,
+                        data.start.0,
+                        data.start.1,
+                        data.end.0,
+                        data.end.1,
+                        data.thickness,
+                        data.color.r,
+                        data.color.g,
+                        data.color.b,
+                        data.color.a
+                    ));
+                }
+                MarkData::Bullets(data) => {
+                    out.push_str(&format!(
+                        "bullets {} {} {} {} {} {} {} {} {} {} {} {}\n",
+                        data.page,
+                        data.square.0,
+                        data.square.1,
+                        data.kind.name(),
+                        data.point,
+                        data.font_style.bits(),
+                        data.color.r,
+                        data.color.g,
+                        data.color.b,
+                        data.color.a,
+                        data.max_width,
+                        data.font_name
+                    ));
+                    for item in &data.items {
+                        out.push_str(&format!("item {}\n", item.level));
+                        write_text_lines(&mut out, &item.lines, &item.breaks, &item.spans);
+                    }
+                    out.push_str("end\n");
+                }
+                MarkData::Math(data) => {
+                    out.push_str(&format!(
+                        "math {} {} {} {} {} {} {} {} {}\n",
+                        data.page,
+                        data.square.0,
+                        data.square.1,
+                        data.point,
+                        data.color.r,
+                        data.color.g,
+                        data.color.b,
+                        data.color.a,
+                        data.font_name
+                    ));
+                    write_lines(&mut out, std::slice::from_ref(&data.source));
+                    out.push_str("end\n");
+                }
+                MarkData::Code(data) => {
+                    out.push_str(&format!(
+                        "code {} {} {} {} {} {} {}\n",
+                        data.page,
+                        data.square.0,
+                        data.square.1,
+                        data.language,
+                        data.point,
+                        data.max_width,
+                        data.font_name
+                    ));
+                    write_lines(&mut out, &data.lines);
+                    out.push_str("end\n");
+                }
+                MarkData::Image(data) => {
+                    out.push_str(&format!(
+                        "image {} {} {} {} {} {}\n",
+                        data.page,
+                        data.square.0,
+                        data.square.1,
+                        data.size.0,
+                        data.size.1,
+                        escape(&data.path)
+                    ));
+                    write_data(&mut out, &data.data);
+                }
+            }
+        }
+
+        for font in &self.fonts {
+            out.push_str(&format!("font {} {}\n", font.style.bits(), font.family));
+            write_data(&mut out, &font.data);
+        }
+
+        out
+    }
+
+    pub fn parse(contents: &str) -> Result<Document, String> {
+        let mut lines = contents.lines().enumerate().map(|(i, l)| (i + 1, l));
+
+        // The header must come first so that the rest of the file is read with the right version
+        let version = match lines.next() {
+            Some((_, header)) => match header.strip_prefix("squarepad ") {
+                Some(v) => v
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| "Line 1: invalid format version.".to_string())?,
+                None => return Err("Not a SquarePad document.".to_string()),
+            },
+            None => return Err("Not a SquarePad document.".to_string()),
+        };
+        if version == 0 || version > FORMAT_VERSION {
+            return Err(format!(
+                "Document format version {} is not supported (latest is {}).",
+                version, FORMAT_VERSION
+            ));
+        }
+
+        let mut pages = None;
+        let mut marks = Vec::new();
+        let mut fonts = Vec::new();
+
+        while let Some((n, line)) = lines.next() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (tag, rest) = line.split_once(' ').unwrap_or((line, ""));
+
+            match tag {
+                "pages" => {
+                    let fields: Vec<&str> = rest.split_whitespace().collect();
+                    if fields.len() != 4 {
+                        return Err(format!("Line {}: expected 4 fields in pages record.", n));
+                    }
+                    let style = PageStyle::from_name(fields[3])
+                        .ok_or_else(|| format!("Line {}: unknown page style.", n))?;
+                    pages = Some((
+                        parse_field(fields[0], n)?,
+                        (parse_field(fields[1], n)?, parse_field(fields[2], n)?),
+                        style,
+                    ));
+                }
+                "text" => {
+                    let fields: Vec<&str> = rest.splitn(11, ' ').collect();
+                    if fields.len() != 11 {
+                        return Err(format!("Line {}: expected 11 fields in text record.", n));
+                    }
+
+                    let mut data = TextData {
+                        page: parse_field(fields[0], n)?,
+                        square: (parse_field(fields[1], n)?, parse_field(fields[2], n)?),
+                        point: parse_field(fields[3], n)?,
+                        font_style: FontStyle::from_bits_truncate(parse_field(fields[4], n)?),
+                        color: Color::RGBA(
+                            parse_field(fields[5], n)?,
+                            parse_field(fields[6], n)?,
+                            parse_field(fields[7], n)?,
+                            parse_field(fields[8], n)?,
+                        ),
+                        max_width: parse_field(fields[9], n)?,
+                        font_name: fields[10].to_string(),
+                        lines: Vec::new(),
+                        breaks: Vec::new(),
+                        spans: Vec::new(),
+                    };
+
+                    // Read the text box's lines until its end record
+                    loop {
+                        match lines.next() {
+                            Some((_, "end")) => break,
+                            Some((n, line)) if line.starts_with("span ") => {
+                                let span = parse_span(line, data.lines.last(), n)?;
+                                data.spans.last_mut().unwrap().push(span);
+                            }
+                            Some((n, line)) => {
+                                let (text, line_break) = parse_text_line(line, n)?;
+                                data.lines.push(text);
+                                data.breaks.push(line_break);
+                                data.spans.push(Vec::new());
+                            }
+                            None => {
+                                return Err("Unexpected end of file in text record.".to_string())
+                            }
+                        }
+                    }
+
+                    marks.push(MarkData::Text(data));
+                }
+                "bullets" => {
+                    let fields: Vec<&str> = rest.splitn(12, ' ').collect();
+                    if fields.len() != 12 {
+                        return Err(format!("Line {}: expected 12 fields in bullets record.", n));
+                    }
+
+                    let mut data = BulletData {
+                        page: parse_field(fields[0], n)?,
+                        square: (parse_field(fields[1], n)?, parse_field(fields[2], n)?),
+                        kind: BulletKind::from_name(fields[3])
+                            .ok_or_else(|| format!("Line {}: unknown bullet kind.", n))?,
+                        point: parse_field(fields[4], n)?,
+                        font_style: FontStyle::from_bits_truncate(parse_field(fields[5], n)?),
+                        color: Color::RGBA(
+                            parse_field(fields[6], n)?,
+                            parse_field(fields[7], n)?,
+                            parse_field(fields[8], n)?,
+                            parse_field(fields[9], n)?,
+                        ),
+                        max_width: parse_field(fields[10], n)?,
+                        font_name: fields[11].to_string(),
+                        items: Vec::new(),
+                    };
+
+                    // Read the list's items until its end record
+                    loop {
+                        match lines.next() {
+                            Some((_, "end")) => break,
+                            Some((n, line)) => match line.split_once(' ') {
+                                Some(("item", level)) => data.items.push(BulletItemData {
+                                    level: parse_field(level, n)?,
+                                    lines: Vec::new(),
+                                    breaks: Vec::new(),
+                                    spans: Vec::new(),
+                                }),
+                                Some(("span", _)) => match data.items.last_mut() {
+                                    Some(item) => {
+                                        let span = parse_span(line, item.lines.last(), n)?;
+                                        item.spans.last_mut().unwrap().push(span);
+                                    }
+                                    None => return Err(format!("Line {}: expected item.", n)),
+                                },
+                                _ => match data.items.last_mut() {
+                                    Some(item) => {
+                                        let (text, line_break) = parse_text_line(line, n)?;
+                                        item.lines.push(text);
+                                        item.breaks.push(line_break);
+                                        item.spans.push(Vec::new());
+                                    }
+                                    None => return Err(format!("Line {}: expected item.", n)),
+                                },
+                            },
+                            None => {
+                                return Err("Unexpected end of file in bullets record.".to_string())
+                            }
+                        }
+                    }
+
+                    marks.push(MarkData::Bullets(data));
+                }
+                "math" => {
+                    let fields: Vec<&str> = rest.splitn(9, ' ').collect();
+                    if fields.len() != 9 {
+                        return Err(format!("Line {}: expected 9 fields in math record.", n));
+                    }
+
+                    let source = match lines.next() {
+                        Some((n, line)) => parse_line(line, n)?,
+                        None => return Err("Unexpected end of file in math record.".to_string()),
+                    };
+                    match lines.next() {
+                        Some((_, "end")) => (),
+                        Some((n, _)) => return Err(format!("Line {}: expected end.", n)),
+                        None => return Err("Unexpected end of file in math record.".to_string()),
+                    }
+
+                    marks.push(MarkData::Math(MathData {
+                        page: parse_field(fields[0], n)?,
+                        square: (parse_field(fields[1], n)?, parse_field(fields[2], n)?),
+                        point: parse_field(fields[3], n)?,
+                        color: Color::RGBA(
+                            parse_field(fields[4], n)?,
+                            parse_field(fields[5], n)?,
+                            parse_field(fields[6], n)?,
+                            parse_field(fields[7], n)?,
+                        ),
+                        font_name: fields[8].to_string(),
+                        source,
+                    }));
+                }
+                "code" => {
+                    let fields: Vec<&str> = rest.splitn(7, ' ').collect();
+                    if fields.len() != 7 {
+                        return Err(format!("Line {}: expected 7 fields in code record.", n));
+                    }
+                    if crate::highlight::language(fields[3]).is_none() {
+                        return Err(format!("Line {}: unknown language '{}'.", n, fields[3]));
+                    }
+
+                    let mut data = CodeData {
+                        page: parse_field(fields[0], n)?,
+                        square: (parse_field(fields[1], n)?, parse_field(fields[2], n)?),
+                        language: fields[3].to_string(),
+                        point: parse_field(fields[4], n)?,
+                        max_width: parse_field(fields[5], n)?,
+                        font_name: fields[6].to_string(),
+                        lines: Vec::new(),
+                    };
+
+                    // Read the code block's lines until its end record
+                    loop {
+                        match lines.next() {
+                            Some((_, "end")) => break,
+                            Some((n, line)) => data.lines.push(parse_line(line, n)?),
+                            None => {
+                                return Err("Unexpected end of file in code record.".to_string())
+                            }
+                        }
+                    }
+
+                    marks.push(MarkData::Code(data));
+                }
+                "image" => {
+                    let fields: Vec<&str> = rest.splitn(6, ' ').collect();
+                    if fields.len() != 6 {
+                        return Err(format!("Line {}: expected 6 fields in image record.", n));
+                    }
+
+                    let data = match version {
+                        1 | 2 => Vec::new(),
+                        _ => read_data(&mut lines, "image")?,
+                    };
+                    marks.push(MarkData::Image(ImageData {
+                        page: parse_field(fields[0], n)?,
+                        square: (parse_field(fields[1], n)?, parse_field(fields[2], n)?),
+                        size: (parse_field(fields[3], n)?, parse_field(fields[4], n)?),
+                        path: unescape(fields[5], n)?,
+                        data: data.into(),
+                    }));
+                }
+                "stroke" => {
+                    let fields: Vec<&str> = rest.split_whitespace().collect();
+                    if fields.len() != 10 {
+                        return Err(format!("Line {}: expected 10 fields in stroke record.", n));
+                    }
+
+                    marks.push(MarkData::Line(LineData {
+                        page: parse_field(fields[0], n)?,
+                        start: (parse_field(fields[1], n)?, parse_field(fields[2], n)?),
+                        end: (parse_field(fields[3], n)?, parse_field(fields[4], n)?),
+                        thickness: parse_field(fields[5], n)?,
+                        color: Color::RGBA(
+                            parse_field(fields[6], n)?,
+                            parse_field(fields[7], n)?,
+                            parse_field(fields[8], n)?,
+                            parse_field(fields[9], n)?,
+                        ),
+                    }));
+                }
+                "font" => {
+                    let fields: Vec<&str> = rest.splitn(2, ' ').collect();
+                    if fields.len() != 2 || fields[1].is_empty() {
+                        return Err(format!("Line {}: expected 2 fields in font record.", n));
+                    }
+
+                    fonts.push(FontData {
+                        family: fields[1].to_string(),
+                        style: FontStyle::from_bits_truncate(parse_field(fields[0], n)?),
+                        data: read_data(&mut lines, "font")?,
+                    });
+                }
+                _ => return Err(format!("Line {}: unknown record '{}'.", n, tag)),
+            }
+        }
+
+        let (pages, page_squares, style) =
+            pages.ok_or_else(|| "Document is missing its pages record.".to_string())?;
+        if pages == 0 {
+            return Err("Document must have at least one page.".to_string());
+        }
+        check_page_squares(page_squares)?;
+        if let Some(mark) = marks.iter().find(|m| m.page() >= pages) {
+            return Err(format!(
+                "Mark on page {} is outside of the document's {} pages.",
+                mark.page() + 1,
+                pages
+            ));
+        }
+
+        let document = Document {
+            page_squares,
+            pages,
+            style,
+            marks,
+            fonts,
+        };
+        if let Some(mark) = document.mark_outside(page_squares) {
+            return Err(format!(
+                "The {} mark at square ({}, {}) of page {} is outside of the document's {} x {} \
+                 squares.",
+                mark.kind(),
+                mark.square().0,
+                mark.square().1,
+                mark.page() + 1,
+                page_squares.0,
+                page_squares.1
+            ));
+        }
+
+        Ok(document)
+    }
+}
+
+fn write_lines(out: &mut String, lines: &[String]) {
+    for line in lines {
+        out.push_str("line ");
+        out.push_str(&escape(line));
+        out.push('\n');
+    }
+}
+
+// Writes lines of wrapped text, each as the record for how it ends followed by its spans
+fn write_text_lines(
+    out: &mut String,
+    lines: &[String],
+    breaks: &[LineBreak],
+    spans: &[Vec<StyleSpan>],
+) {
+    for (i, line) in lines.iter().enumerate() {
+        let line_break = breaks.get(i).copied().unwrap_or(LineBreak::Hard);
+        out.push_str(line_break.name());
+        out.push(' ');
+        out.push_str(&escape(line));
+        out.push('\n');
+
+        for span in spans.get(i).map_or(&[][..], Vec::as_slice) {
+            out.push_str(&format!(
+                "span {} {} {} {} {} {} {}\n",
+                span.start,
+                span.end,
+                span.style.font_style.bits(),
+                span.style.color.r,
+                span.style.color.g,
+                span.style.color.b,
+                span.style.color.a
+            ));
+        }
+    }
+}
+
+fn parse_field<T: std::str::FromStr>(field: &str, line: usize) -> Result<T, String> {
+    field
+        .parse::<T>()
+        .map_err(|_| format!("Line {}: invalid value '{}'.", line, field))
+}
+
+// Reads the text out of a line record
+fn parse_line(line: &str, n: usize) -> Result<String, String> {
+    match line.strip_prefix("line ") {
+        Some(text) => unescape(text, n),
+        None if line == "line" => Ok(String::new()),
+        None => Err(format!("Line {}: expected line or end.", n)),
+    }
+}
+
+// Reads the text out of a line, wrap or hyphen record, along with how the line ends
+fn parse_text_line(line: &str, n: usize) -> Result<(String, LineBreak), String> {
+    let (tag, text) = line.split_once(' ').unwrap_or((line, ""));
+    match LineBreak::from_name(tag) {
+        Some(line_break) => Ok((unescape(text, n)?, line_break)),
+        None => Err(format!("Line {}: expected line or end.", n)),
+    }
+}
+
+// Reads a span record, which must cover whole characters of the line before it
+fn parse_span(record: &str, line: Option<&String>, n: usize) -> Result<StyleSpan, String> {
+    let line = line.ok_or_else(|| format!("Line {}: span without a line.", n))?;
+    let fields: Vec<&str> = record.split_whitespace().skip(1).collect();
+    if fields.len() != 7 {
+        return Err(format!("Line {}: expected 7 fields in span record.", n));
+    }
+
+    let span = StyleSpan {
+        start: parse_field(fields[0], n)?,
+        end: parse_field(fields[1], n)?,
+        style: TextStyle {
+            font_style: FontStyle::from_bits_truncate(parse_field(fields[2], n)?),
+            color: Color::RGBA(
+                parse_field(fields[3], n)?,
+                parse_field(fields[4], n)?,
+                parse_field(fields[5], n)?,
+                parse_field(fields[6], n)?,
+            ),
+        },
+    };
+    let within = span.start <= span.end && span.end <= line.len();
+    if !within || !line.is_char_boundary(span.start) || !line.is_char_boundary(span.end) {
+        return Err(format!("Line {}: span is outside of its line.", n));
+    }
+
+    Ok(span)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(text: &str, line: usize) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => out.push('\\'),
+            Some('n') => out.push('\n'),
+            _ => return Err(format!("Line {}: invalid escape sequence.", line)),
+        }
+    }
+
+    Ok(out)
+}
+
+// Writes a file held in a document as `data` records, closed by `end`
+fn write_data(out: &mut String, data: &[u8]) {
+    for chunk in data.chunks(DATA_LINE_BYTES) {
+        out.push_str("data ");
+        out.push_str(&base64_encode(chunk));
+        out.push('\n');
+    }
+    out.push_str("end\n");
+}
+
+// Reads the `data` records of a file held in a document, up to their end record
+fn read_data<'a>(
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+    record: &str,
+) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    loop {
+        match lines.next() {
+            Some((_, "end")) => return Ok(data),
+            Some((n, line)) => {
+                let chunk = line
+                    .strip_prefix("data ")
+                    .ok_or_else(|| format!("Line {}: expected a data record.", n))?;
+                data.extend(base64_decode(chunk.trim(), n)?);
+            }
+            None => return Err(format!("Unexpected end of file in {} record.", record)),
+        }
+    }
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+fn base64_decode(text: &str, line: usize) -> Result<Vec<u8>, String> {
+    let invalid = || format!("Line {}: invalid base64 data.", line);
+    if !text.len().is_multiple_of(4) {
+        return Err(invalid());
+    }
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+
+    for chunk in text.as_bytes().chunks(4) {
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 {
+            return Err(invalid());
+        }
+
+        let mut bits = 0u32;
+        for &c in &chunk[..4 - padding] {
+            let value = BASE64.iter().position(|&b| b == c).ok_or_else(invalid)?;
+            bits = bits << 6 | value as u32;
+        }
+        bits <<= 6 * padding;
+
+        out.extend_from_slice(&bits.to_be_bytes()[1..4 - padding]);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(start: usize, end: usize) -> StyleSpan {
+        StyleSpan {
+            start,
+            end,
+            style: TextStyle {
+                font_style: FontStyle::BOLD,
+                color: Color::RGB(200, 30, 30),
+            },
+        }
+    }
+
+    // A document with one of each kind of mark, and a font
+    fn sample() -> Document {
+        Document {
+            page_squares: (29, 42),
+            pages: 2,
+            style: PageStyle::BeigePlain,
+            marks: vec![
+                MarkData::Text(TextData {
+                    page: 0,
+                    square: (1, 2),
+                    font_name: "Noto Serif".to_string(),
+                    font_style: FontStyle::ITALIC,
+                    point: 32,
+                    color: Color::RGBA(10, 20, 30, 255),
+                    max_width: 400,
+                    lines: vec![
+                        "A back\\slash and a".to_string(),
+                        "new\nline".to_string(),
+                        "split".to_string(),
+                    ],
+                    breaks: vec![LineBreak::Soft, LineBreak::Hyphen, LineBreak::Hard],
+                    spans: vec![vec![span(0, 1)], vec![], vec![span(1, 3)]],
+                }),
+                MarkData::Line(LineData {
+                    page: 0,
+                    start: (3, 20),
+                    end: (10, 25),
+                    thickness: 4,
+                    color: Color::BLACK,
+                }),
+                MarkData::Bullets(BulletData {
+                    page: 1,
+                    square: (2, 2),
+                    kind: BulletKind::Numbered,
+                    font_name: "NotoSerif".to_string(),
+                    font_style: FontStyle::NORMAL,
+                    point: 30,
+                    color: Color::BLACK,
+                    max_width: 500,
+                    items: vec![
+                        BulletItemData {
+                            level: 0,
+                            lines: vec!["First".to_string()],
+                            breaks: vec![LineBreak::Hard],
+                            spans: vec![vec![span(0, 5)]],
+                        },
+                        BulletItemData {
+                            level: 1,
+                            lines: vec!["Second".to_string()],
+                            breaks: vec![LineBreak::Hard],
+                            spans: vec![vec![]],
+                        },
+                    ],
+                }),
+                MarkData::Math(MathData {
+                    page: 1,
+                    square: (2, 10),
+                    font_name: "NotoSerif".to_string(),
+                    point: 36,
+                    color: Color::BLACK,
+                    source: "\\frac{a}{b} + x^2".to_string(),
+                }),
+                MarkData::Code(CodeData {
+                    page: 1,
+                    square: (2, 15),
+                    language: "rust".to_string(),
+                    font_name: "DejaVuSansMono".to_string(),
+                    point: 30,
+                    max_width: 600,
+                    lines: vec![
+                        "fn main() {".to_string(),
+                        "\tprintln!();".to_string(),
+                        "}".to_string(),
+                    ],
+                }),
+                MarkData::Image(ImageData {
+                    page: 1,
+                    square: (5, 25),
+                    size: (4, 3),
+                    path: "/tmp/a picture.png".to_string(),
+                    data: (0..=255).collect::<Vec<u8>>().into(),
+                }),
+            ],
+            fonts: vec![FontData {
+                family: "Noto Serif".to_string(),
+                style: FontStyle::ITALIC,
+                data: (0..=255).collect(),
+            }],
+        }
+    }
+
+    #[test]
+    fn round_trips_every_kind_of_mark() {
+        let document = sample();
+        assert_eq!(Document::parse(&document.serialize()), Ok(document));
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let document = sample();
+        let path = std::env::temp_dir().join(format!("squarepad-{}.sqpd", std::process::id()));
+        document.write(&path).unwrap();
+        let read = Document::read(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read, Ok(document));
+    }
+
+    #[test]
+    fn failed_writes_leave_the_file_as_it_was() {
+        let document = sample();
+        let dir = std::env::temp_dir().join(format!("squarepad-write-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.sqpd");
+        document.write(&path).unwrap();
+
+        // A folder in the way of the temporary file makes the next write fail
+        std::fs::create_dir(dir.join("notes.sqpd.tmp")).unwrap();
+        let mut changed = sample();
+        changed.pages = 1;
+        changed.marks.retain(|mark| mark.page() == 0);
+        assert!(changed.write(&path).is_err());
+        let read = Document::read(&path);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(read, Ok(document));
+    }
+
+    #[test]
+    fn writes_leave_no_temporary_file_behind() {
+        let dir = std::env::temp_dir().join(format!("squarepad-temp-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.sqpd");
+        sample().write(&path).unwrap();
+        sample().write(&path).unwrap();
+        let files = std::fs::read_dir(&dir).unwrap().count();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files, 1);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let contents = format!(
+            "squarepad {}\npages 1 29 42 white-squared\n",
+            FORMAT_VERSION + 1
+        );
+        let error = Document::parse(&contents).unwrap_err();
+        assert!(error.contains("not supported"), "{}", error);
+    }
+
+    #[test]
+    fn reads_version_1_files() {
+        let contents = "squarepad 1\n\
+            pages 1 29 42 white-squared\n\
+            text 0 2 2 30 0 0 0 0 255 400 NotoSerif\n\
+            line hello\n\
+            line world\n\
+            end\n";
+        let document = Document::parse(contents).unwrap();
+        assert_eq!(document.pages, 1);
+        assert_eq!(document.marks[0].text().as_deref(), Some("hello\nworld"));
+    }
+
+    #[test]
+    fn reads_images_of_version_2_files_by_their_path() {
+        let contents = "squarepad 2\n\
+            pages 1 29 42 white-squared\n\
+            image 0 2 2 4 3 /tmp/a picture.png\n";
+        let document = Document::parse(contents).unwrap();
+        match &document.marks[0] {
+            MarkData::Image(image) => {
+                assert_eq!(image.path, "/tmp/a picture.png");
+                assert!(image.data.is_empty());
+            }
+            mark => panic!("Expected an image, found {:?}", mark),
+        }
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(Document::parse("").is_err());
+        assert!(Document::parse("hello\n").is_err());
+        assert!(Document::parse("squarepad x\n").is_err());
+    }
+
+    #[test]
+    fn rejects_a_missing_pages_record() {
+        let contents = format!("squarepad {}\n", FORMAT_VERSION);
+        assert_eq!(
+            Document::parse(&contents),
+            Err("Document is missing its pages record.".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_marks_on_missing_pages() {
+        let mut document = sample();
+        document.pages = 1;
+        let error = Document::parse(&document.serialize()).unwrap_err();
+        assert!(
+            error.contains("outside of the document's 1 pages"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn rejects_marks_off_the_page() {
+        let mut document = sample();
+        document.page_squares = (29, 20);
+        let error = Document::parse(&document.serialize()).unwrap_err();
+        assert!(
+            error.contains("outside of the document's 29 x 20 squares"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use crate::app::pages::PageStyle;
+use crate::document::MarkData;
+
+use std::time::{Duration, Instant};
+
+// Edits made after a pause this long start a new undo step
+const COALESCE_PAUSE: Duration = Duration::from_secs(1);
+
+// A reversible change made to the document
+// Marks are stored by their saved state so that they can be rebuilt after being removed
+#[derive(Clone, Debug)]
+pub enum Action {
+    AddPage,
+    RemovePage(Vec<MarkData>), // With the marks that were on the page
+    SetStyle {
+        before: PageStyle,
+        after: PageStyle,
+    },
+    Resize {
+        before: (u32, u32),
+        after: (u32, u32),
+    }, // In squares per page
+    AddMark(MarkData),
+    RemoveMark(MarkData),
+    MoveMark {
+        before: MarkData,
+        after: MarkData,
+    },
+    EditMark {
+        before: MarkData,
+        after: MarkData,
+    },
+}
+
+// Keeps track of the actions that can be undone and redone
+pub struct History {
+    undo_stack: Vec<Action>,
+    redo_stack: Vec<Action>,
+    coalescing: bool, // Whether the next edit may be merged with the previous one
+    last_edit: Instant,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            coalescing: false,
+            last_edit: Instant::now(),
+        }
+    }
+
+    // Records a new action, which invalidates anything that was undone before it
+    pub fn record(&mut self, action: Action) {
+        self.redo_stack.clear();
+
+        // Consecutive edits to the same mark are undone in one step, until there is a pause
+        if let Action::EditMark { after, .. } = &action {
+            let paused = self.last_edit.elapsed() >= COALESCE_PAUSE;
+            self.last_edit = Instant::now();
+            if self.coalescing && !paused {
+                if let Some(Action::EditMark {
+                    after: last_after, ..
+                }) = self.undo_stack.last_mut()
+                {
+                    if last_after.page() == after.page() && last_after.square() == after.square() {
+                        *last_after = after.clone();
+                        return;
+                    }
+                }
+            }
+            self.coalescing = true;
+        } else {
+            self.coalescing = false;
+        }
+
+        self.undo_stack.push(action);
+    }
+
+    // Stops the next edit from being merged into the previous one
+    pub fn break_coalescing(&mut self) {
+        self.coalescing = false
+    }
+
+    // Returns the action to reverse, if any
+    pub fn undo(&mut self) -> Option<Action> {
+        self.coalescing = false;
+        let action = self.undo_stack.pop()?;
+        self.redo_stack.push(action.clone());
+
+        Some(action)
+    }
+
+    // Returns the action to apply again, if any
+    pub fn redo(&mut self) -> Option<Action> {
+        self.coalescing = false;
+        let action = self.redo_stack.pop()?;
+        self.undo_stack.push(action.clone());
+
+        Some(action)
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.coalescing = false;
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::MathData;
+
+    use sdl2::pixels::Color;
+
+    fn math(source: &str) -> MarkData {
+        MarkData::Math(MathData {
+            page: 0,
+            square: (1, 1),
+            font_name: "NotoSerif".to_string(),
+            point: 30,
+            color: Color::BLACK,
+            source: source.to_string(),
+        })
+    }
+
+    fn edit(before: &str, after: &str) -> Action {
+        Action::EditMark {
+            before: math(before),
+            after: math(after),
+        }
+    }
+
+    #[test]
+    fn consecutive_edits_are_undone_together() {
+        let mut history = History::new();
+        history.record(edit("", "a"));
+        history.record(edit("a", "ab"));
+
+        match history.undo() {
+            Some(Action::EditMark { before, after }) => {
+                assert_eq!(before, math(""));
+                assert_eq!(after, math("ab"));
+            }
+            action => panic!("unexpected {:?}", action),
+        }
+        assert!(history.undo().is_none());
+    }
+
+    #[test]
+    fn words_are_undone_separately() {
+        let mut history = History::new();
+        history.record(edit("", "a "));
+        history.break_coalescing();
+        history.record(edit("a ", "a b"));
+
+        assert!(
+            matches!(history.undo(), Some(Action::EditMark { before, .. }) if before == math("a "))
+        );
+        assert!(
+            matches!(history.undo(), Some(Action::EditMark { before, .. }) if before == math(""))
+        );
+    }
+
+    #[test]
+    fn recording_clears_redo() {
+        let mut history = History::new();
+        history.record(Action::AddPage);
+        history.undo();
+        history.record(Action::AddPage);
+
+        assert!(history.redo().is_none());
+    }
+}
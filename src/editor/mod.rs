@@ -1,19 +1,30 @@
+pub mod history;
 pub mod text_tool;
 
+use self::history::{Action, History};
 use self::text_tool::TextTool;
-use crate::app::pages::{PageStyle, Pages};
-use crate::mark::{textbox::TextBox, Mark};
+use crate::app::pages::{check_page_squares, PageLayout, PageStyle, Pages};
+use crate::document::{Document, MarkData};
+use crate::fonts;
+use crate::mark::bullet_list::{BulletKind, BulletList};
+use crate::mark::codeblock::{self, CodeBlock};
+use crate::mark::image::ImageMark;
+use crate::mark::line::{self, Line, LineEnds};
+use crate::mark::{mathbox::MathBox, textbox::TextBox, Mark, TextInput};
 use crate::position::PageSquare;
 use crate::renderer::Renderer;
 
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::Instant;
 
 use sdl2::clipboard::ClipboardUtil;
 use sdl2::event::Event;
 use sdl2::keyboard::{Keycode, Mod, TextInputUtil};
 use sdl2::pixels::Color;
+use sdl2::rect::Rect;
 use sdl2::ttf::FontStyle;
 
 #[derive(Clone, Copy)]
@@ -26,6 +37,16 @@ pub enum ToolType {
     Code = 5,
 }
 
+impl ToolType {
+    // Whether the tool is used to type into marks
+    pub fn types_text(&self) -> bool {
+        matches!(
+            self,
+            ToolType::Text | ToolType::Bullet | ToolType::Math | ToolType::Code
+        )
+    }
+}
+
 // Handles all changes made to the document
 // This means that it also acts as a wrapper for Pages
 // This will prevent two changes from happening concurrently
@@ -35,6 +56,22 @@ pub struct Editor {
     tool_selected: ToolType,
     text_tool: TextTool,
     marks: HashMap<PageSquare, Rc<RefCell<dyn Mark>>>, // Could convert to 3D Vector
+    text_marks: HashMap<PageSquare, Rc<RefCell<dyn TextInput>>>, // Marks that can be typed into
+    page_marks: HashMap<u32, Vec<PageSquare>>,         // The keys of the marks on each page
+    lines: HashMap<u32, Vec<Line>>, // Kept apart from marks, as lines can meet at a corner or cross
+    file_path: Option<PathBuf>,     // Where the document is saved to
+    history: History,
+    line_thickness: u32, // Used for new lines drawn with the line tool
+    line_color: Color,
+    text_font: String, // Used for new text boxes, bullet lists and math boxes
+    text_point: u16,
+    text_color: Color,
+    embed_fonts: bool, // Whether the fonts of the document are saved along with it
+    selected: Option<PageSquare>, // The mark selected with the move tool
+    selected_line: Option<LineEnds>, // Or the line selected with it
+    copy_selection: Vec<PageSquare>, // Marks dragged across with a typing tool, to be copied
+    hovered: Option<PageSquare>, // The square under the cursor, where pasted marks are placed
+    failure: Option<String>, // What last went wrong without stopping the editor, if anything
 }
 
 impl Editor {
@@ -45,9 +82,52 @@ impl Editor {
             text_tool: TextTool::new(text_input),
             clipboard,
             marks: HashMap::new(),
+            text_marks: HashMap::new(),
+            page_marks: HashMap::new(),
+            lines: HashMap::new(),
+            file_path: None,
+            history: History::new(),
+            line_thickness: 3,
+            line_color: Color::BLACK,
+            text_font: "NotoSerif".to_string(),
+            text_point: 30,
+            text_color: Color::BLACK,
+            embed_fonts: false,
+            selected: None,
+            selected_line: None,
+            copy_selection: Vec::new(),
+            hovered: None,
+            failure: None,
         }
     }
 
+    // Takes what last went wrong, such as a failed save, for the app to report
+    pub fn take_failure(&mut self) -> Option<String> {
+        self.failure.take()
+    }
+
+    // Keeps something that went wrong outside the editor, such as a button failing, for the app to
+    // report along with the editor's own failures
+    pub fn report_failure(&mut self, failure: String) {
+        self.failure = Some(failure)
+    }
+
+    pub fn file_path(&self) -> Option<&Path> {
+        self.file_path.as_deref()
+    }
+
+    pub fn set_file_path(&mut self, path: PathBuf) {
+        self.file_path = Some(path)
+    }
+
+    pub fn embed_fonts(&self) -> bool {
+        self.embed_fonts
+    }
+
+    pub fn set_embed_fonts(&mut self, embed_fonts: bool) {
+        self.embed_fonts = embed_fonts
+    }
+
     // Only allows immutable behavior to be done on pages
     // All mutable behavior is done through wrapper functions
     pub fn get_pages(&self) -> &Pages {
@@ -58,27 +138,358 @@ impl Editor {
         self.tool_selected
     }
 
+    // Whether a mark is being typed into, which then takes keys like Home and End
+    pub fn is_typing(&self) -> bool {
+        self.text_tool.text_box().is_some()
+    }
+
+    // The app redraws when the caret blinks, even if nothing else has changed
+    pub fn next_blink(&self) -> Option<Instant> {
+        self.text_tool.next_blink()
+    }
+
     pub fn set_tool(&mut self, tool: ToolType) {
-        if self.tool_selected as isize == 1 {
+        if self.tool_selected.types_text() {
             // Temporary
             self.text_tool.stop_input();
         }
+        self.selected = None;
+        self.selected_line = None;
+        self.copy_selection.clear();
         self.tool_selected = tool
     }
 
+    pub fn line_thickness(&self) -> u32 {
+        self.line_thickness
+    }
+
+    pub fn line_color(&self) -> Color {
+        self.line_color
+    }
+
+    pub fn set_line_thickness(&mut self, thickness: u32) {
+        self.line_thickness = thickness.max(1)
+    }
+
+    pub fn set_line_color(&mut self, color: Color) {
+        self.line_color = color
+    }
+
+    pub fn text_font(&self) -> &str {
+        &self.text_font
+    }
+
+    pub fn text_point(&self) -> u16 {
+        self.text_point
+    }
+
+    pub fn text_color(&self) -> Color {
+        self.text_color
+    }
+
+    // Text properties change the text being typed into and the marks selected for copying too
+    pub fn set_text_font(
+        &mut self,
+        font_name: &str,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        self.text_font = font_name.to_string();
+        let point = self.text_point;
+        self.change_text(
+            |mark, _, r| mark.set_text_font(font_name, point, r),
+            renderer,
+        )
+    }
+
+    pub fn set_text_point(&mut self, point: u16, renderer: &mut Renderer) -> Result<(), String> {
+        self.text_point = point;
+        let font_name = self.text_font.clone();
+        self.change_text(
+            |mark, _, r| mark.set_text_font(&font_name, point, r),
+            renderer,
+        )
+    }
+
+    // Only the selected part of the text being typed into changes color, but the marks selected
+    // for copying change color entirely
+    pub fn set_text_color(&mut self, color: Color, renderer: &mut Renderer) -> Result<(), String> {
+        self.text_color = color;
+        self.change_text(|mark, all, r| mark.set_text_color(color, all, r), renderer)
+    }
+
+    // The color buttons pick the color of new lines while the line tool is selected, and of text
+    // otherwise
+    pub fn color(&self) -> Color {
+        match self.tool_selected {
+            ToolType::Line => self.line_color,
+            _ => self.text_color,
+        }
+    }
+
+    pub fn set_color(&mut self, color: Color, renderer: &mut Renderer) -> Result<(), String> {
+        match self.tool_selected {
+            ToolType::Line => {
+                self.set_line_color(color);
+                Ok(())
+            }
+            _ => self.set_text_color(color, renderer),
+        }
+    }
+
+    // Makes a change to every mark selected for copying, or otherwise to the mark being typed
+    // into, recording each mark that it changes
+    // The change is told whether it applies to the whole of the mark
+    fn change_text(
+        &mut self,
+        change: impl Fn(&mut dyn TextInput, bool, &mut Renderer) -> Result<(), String>,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        let marks: Vec<(Rc<RefCell<dyn TextInput>>, bool)> = if self.copy_selection.is_empty() {
+            self.text_tool
+                .text_box()
+                .map(|text_box| (Rc::clone(text_box), false))
+                .into_iter()
+                .collect()
+        } else {
+            self.copy_selection
+                .iter()
+                .filter_map(|key| Some((Rc::clone(self.text_marks.get(key)?), true)))
+                .collect()
+        };
+
+        for (mark, all) in marks {
+            let before = mark.borrow().to_data();
+            change(&mut *mark.borrow_mut(), all, renderer)?;
+            let after = mark.borrow().to_data();
+            if before != after {
+                self.history.record(Action::EditMark { before, after });
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn set_pages_style(&mut self, style: PageStyle) {
+        self.history.record(Action::SetStyle {
+            before: self.pages.style(),
+            after: style,
+        });
         self.pages.set_style(style)
     }
 
     pub fn add_page(&mut self) {
+        self.history.record(Action::AddPage);
         self.pages.add_page()
     }
 
-    pub fn remove_page(&mut self) {
-        self.pages.remove_page()
+    // Removes the last page along with its marks, which undoing puts back
+    // The last page can't be removed, so there is nothing to undo then
+    pub fn remove_page(&mut self, renderer: &mut Renderer) {
+        if self.pages.pages() > 1 {
+            let marks = self.take_last_page(renderer);
+            self.history.record(Action::RemovePage(marks));
+            self.pages.remove_page()
+        }
+    }
+
+    // Takes the marks off of the last page, returning what they were
+    fn take_last_page(&mut self, renderer: &mut Renderer) -> Vec<MarkData> {
+        let page = self.pages.pages() - 1;
+        let keys: Vec<PageSquare> = self.marks_on(page).map(|(key, _)| key).collect();
+
+        let mut marks = Vec::new();
+        for key in keys {
+            marks.push(self.marks[&key].borrow().to_data());
+            self.remove_mark(key, renderer);
+        }
+        for line in self.lines.remove(&page).unwrap_or_default() {
+            marks.push(line.to_data());
+        }
+        if self.selected_line.is_some_and(|ends| ends.0 == page) {
+            self.selected_line = None;
+        }
+        marks
+    }
+
+    // Resizes the pages, rebuilding every mark on its square of the resized pages
+    // Nothing changes if a mark wouldn't fit, since marks aren't moved or cropped to make room
+    // Earlier changes can't be undone afterwards, as they may refer to squares that are gone
+    pub fn set_page_squares(
+        &mut self,
+        page_squares: (u32, u32),
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        if page_squares == self.pages.page_squares() {
+            return Ok(());
+        }
+        check_page_squares(page_squares)?;
+
+        let fits = |key: PageSquare, size: (u32, u32)| {
+            key.square.0 + size.0 <= page_squares.0 && key.square.1 + size.1 <= page_squares.1
+        };
+        let outside = self
+            .marks
+            .iter()
+            .find(|(key, mark)| !fits(**key, mark.borrow().size()))
+            .map(|(key, mark)| (*key, mark.borrow().to_data()))
+            .or_else(|| {
+                self.lines
+                    .values()
+                    .flatten()
+                    .find(|line| !fits(line.page_square(), line.size()))
+                    .map(|line| (line.page_square(), line.to_data()))
+            });
+        if let Some((key, data)) = outside {
+            return Err(format!(
+                "The {} mark at square ({}, {}) of page {} doesn't fit on pages of {} x {} squares.",
+                data.kind(),
+                key.square.0,
+                key.square.1,
+                key.page + 1,
+                page_squares.0,
+                page_squares.1
+            ));
+        }
+
+        let before = self.pages.page_squares();
+        self.resize(page_squares, renderer)?;
+        self.history.record(Action::Resize {
+            before,
+            after: page_squares,
+        });
+
+        Ok(())
     }
 
-    pub fn handle_event(&mut self, event: &Event, renderer: &mut Renderer) -> Result<(), String> {
+    // Rebuilds the pages at a new size, with every mark on the same square as before
+    fn resize(&mut self, page_squares: (u32, u32), renderer: &mut Renderer) -> Result<(), String> {
+        let mut pages = Pages::new(page_squares, renderer)?;
+        pages.set_layout(self.pages.layout());
+        pages.set_pages(self.pages.pages());
+        pages.set_style(self.pages.style());
+
+        let marks = self.to_document().marks;
+        renderer.remove_textures(self.pages.id);
+        self.pages = pages;
+        self.rebuild_marks(&marks, renderer)
+    }
+
+    // Lays the pages out in a different number of columns, rebuilding every mark where its page
+    // now is
+    pub fn set_page_layout(
+        &mut self,
+        layout: PageLayout,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        if layout == self.pages.layout() {
+            return Ok(());
+        }
+
+        let marks = self.to_document().marks;
+        self.pages.set_layout(layout);
+        self.rebuild_marks(&marks, renderer)
+    }
+
+    // Puts every mark back where it is on the pages after they've been changed, dropping any
+    // selection or typing, which refers to marks by where they were
+    fn rebuild_marks(&mut self, marks: &[MarkData], renderer: &mut Renderer) -> Result<(), String> {
+        self.text_tool.stop_input();
+        self.selected = None;
+        self.selected_line = None;
+        self.copy_selection.clear();
+        self.hovered = None;
+        self.clear_marks(renderer);
+
+        for data in marks {
+            self.insert_mark(data, renderer)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn undo(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        match self.history.undo() {
+            Some(action) => self.apply(action, true, renderer),
+            None => Ok(()),
+        }
+    }
+
+    pub fn redo(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        match self.history.redo() {
+            Some(action) => self.apply(action, false, renderer),
+            None => Ok(()),
+        }
+    }
+
+    // Performs an action from the history, or its opposite when reversing it
+    fn apply(
+        &mut self,
+        action: Action,
+        reverse: bool,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        match action {
+            Action::AddPage | Action::RemovePage(_) => {
+                if matches!(action, Action::AddPage) != reverse {
+                    self.pages.add_page();
+                    if let Action::RemovePage(marks) = &action {
+                        for data in marks {
+                            self.insert_mark(data, renderer)?;
+                        }
+                    }
+                } else if self.pages.pages() > 1 {
+                    self.take_last_page(renderer);
+                    self.pages.remove_page()
+                }
+            }
+            Action::SetStyle { before, after } => {
+                self.pages.set_style(if reverse { before } else { after })
+            }
+            Action::Resize { before, after } => {
+                self.resize(if reverse { before } else { after }, renderer)?
+            }
+            Action::AddMark(data) | Action::RemoveMark(data)
+                if reverse == matches!(action, Action::AddMark(_)) =>
+            {
+                self.remove_data(&data, renderer)?;
+            }
+            Action::AddMark(data) | Action::RemoveMark(data) => {
+                self.insert_mark(&data, renderer)?;
+            }
+            Action::MoveMark { before, after } => {
+                let (from, to) = if reverse {
+                    (after, before)
+                } else {
+                    (before, after)
+                };
+                self.remove_data(&from, renderer)?;
+                self.insert_mark(&to, renderer)?;
+            }
+            Action::EditMark { before, after } => {
+                let data = if reverse { before } else { after };
+                let page_square = PageSquare::new(data.page(), data.square(), &self.pages)?;
+                if let Some(mark) = self.marks.get(&page_square) {
+                    mark.borrow_mut().restore(&data, renderer)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Handles an event, returning whether it may have changed what is drawn
+    // Only keys and typed text change anything here, and any of them may, if only by restarting
+    // the caret's blink
+    pub fn handle_event(&mut self, event: &Event, renderer: &mut Renderer) -> Result<bool, String> {
+        if !matches!(event, Event::KeyDown { .. } | Event::TextInput { .. }) {
+            return Ok(false);
+        }
+        self.handle_key(event, renderer)?;
+
+        Ok(true)
+    }
+
+    fn handle_key(&mut self, event: &Event, renderer: &mut Renderer) -> Result<(), String> {
         match event {
             Event::KeyDown {
                 keycode: Some(Keycode::V),
@@ -86,51 +497,1110 @@ impl Editor {
                 ..
             } => match *keymod & (Mod::LCTRLMOD | Mod::RCTRLMOD) {
                 // If holding down either control
-                Mod::LCTRLMOD | Mod::RCTRLMOD => self.paste()?,
+                // A failed paste, such as of a broken image, is kept for the app to report
+                Mod::LCTRLMOD | Mod::RCTRLMOD => {
+                    if let Err(e) = self.paste(renderer) {
+                        self.failure = Some(format!("Could not paste: {}", e));
+                    }
+                    return Ok(());
+                }
+                _ => (),
+            },
+            Event::KeyDown {
+                keycode: Some(keycode @ (Keycode::C | Keycode::X)),
+                keymod,
+                ..
+            } => match *keymod & (Mod::LCTRLMOD | Mod::RCTRLMOD) {
+                Mod::LCTRLMOD | Mod::RCTRLMOD => {
+                    return self.copy(*keycode == Keycode::X, renderer);
+                }
+                _ => (),
+            },
+            Event::KeyDown {
+                keycode: Some(Keycode::Z),
+                keymod,
+                ..
+            } => match *keymod & (Mod::LCTRLMOD | Mod::RCTRLMOD) {
+                // Holding shift as well redoes instead
+                Mod::LCTRLMOD | Mod::RCTRLMOD => {
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        self.redo(renderer)?
+                    } else {
+                        self.undo(renderer)?
+                    }
+                    return Ok(());
+                }
                 _ => (),
             },
+            Event::KeyDown {
+                keycode: Some(Keycode::S),
+                keymod,
+                ..
+            } => match *keymod & (Mod::LCTRLMOD | Mod::RCTRLMOD) {
+                Mod::LCTRLMOD | Mod::RCTRLMOD => {
+                    // Documents without a path are saved in the working directory
+                    let path = self.file_path.clone().unwrap_or_else(|| {
+                        PathBuf::from(format!("untitled.{}", crate::document::FILE_EXTENSION))
+                    });
+                    // A failed save is kept for the app to report, rather than taking the
+                    // document down with it
+                    match self.save(&path, renderer) {
+                        Ok(()) => self.file_path = Some(path),
+                        Err(e) => self.failure = Some(format!("Could not save: {}", e)),
+                    }
+                }
+                _ => (),
+            },
+            // Delete removes the mark selected with the move tool
+            Event::KeyDown {
+                keycode: Some(Keycode::Delete),
+                ..
+            } if matches!(self.tool_selected, ToolType::Move) => {
+                if let Some(data) = self.selected_data() {
+                    self.remove_data(&data, renderer)?;
+                    self.history.record(Action::RemoveMark(data));
+                }
+            }
+            // Number keys pick the thickness of new lines
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } if matches!(self.tool_selected, ToolType::Line) => {
+                let thickness = *keycode as i32 - Keycode::Num0 as i32;
+                if (1..=9).contains(&thickness) {
+                    self.set_line_thickness(thickness as u32)
+                }
+            }
             _ => (),
         }
+
+        // Only events that can change the text being edited are recorded, so that they can be
+        // undone, and each word typed is undone on its own
+        if !TextTool::edits(event) {
+            return self.text_tool.handle_event(event, renderer);
+        }
+        let before = self.text_tool.text_box().map(|t| t.borrow().to_data());
         self.text_tool.handle_event(event, renderer)?;
 
+        if let (Some(before), Some(text_box)) = (before, self.text_tool.text_box()) {
+            let after = text_box.borrow().to_data();
+            if before != after {
+                self.history.record(Action::EditMark { before, after });
+            }
+            if TextTool::ends_word(event) {
+                self.history.break_coalescing();
+            }
+        }
+
         Ok(())
     }
 
-    pub fn handle_click(&mut self, page_square: PageSquare) {
+    pub fn handle_click(
+        &mut self,
+        page_square: PageSquare,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        // New marks aren't placed on top of ones already there
+        if self.overlaps(page_square.page, page_square.square, (1, 1), None) {
+            return Ok(());
+        }
+
         match self.tool_selected {
             ToolType::Text => {
-                let max_width = self.pages.page_width()
-                    - (page_square.position.x() - self.pages.position().x()) as u32;
+                let max_width =
+                    self.pages.page_width() - page_square.square.0 * self.pages.square_size();
 
-                let text_box = Rc::new(RefCell::new(TextBox::new(
+                let text_box = self.insert_text_mark(TextBox::new(
                     page_square,
-                    "NotoSerif".to_string(),
+                    self.text_font.clone(),
                     FontStyle::NORMAL,
-                    30,
-                    Color::BLACK,
+                    self.text_point,
+                    self.text_color,
                     max_width,
-                )));
-                self.text_tool.start_input(Rc::clone(&text_box));
+                ));
+                self.text_tool
+                    .start_input(Rc::clone(&text_box) as Rc<RefCell<dyn TextInput>>);
 
-                self.marks.insert(page_square, text_box);
+                self.history
+                    .record(Action::AddMark(text_box.borrow().to_data()));
+            }
+            ToolType::Bullet => {
+                let max_width =
+                    self.pages.page_width() - page_square.square.0 * self.pages.square_size();
+
+                let mut list = BulletList::new(
+                    page_square,
+                    BulletKind::Dot,
+                    self.text_font.clone(),
+                    FontStyle::NORMAL,
+                    self.text_point,
+                    self.text_color,
+                    max_width,
+                );
+                list.update_markers(renderer)?;
+
+                let list = self.insert_text_mark(list);
+                self.text_tool
+                    .start_input(Rc::clone(&list) as Rc<RefCell<dyn TextInput>>);
+
+                self.history
+                    .record(Action::AddMark(list.borrow().to_data()));
+            }
+            ToolType::Math => {
+                let math_box = self.insert_text_mark(MathBox::new(
+                    page_square,
+                    self.text_font.clone(),
+                    self.text_point,
+                    self.text_color,
+                ));
+                self.text_tool
+                    .start_input(Rc::clone(&math_box) as Rc<RefCell<dyn TextInput>>);
+
+                self.history
+                    .record(Action::AddMark(math_box.borrow().to_data()));
+            }
+            ToolType::Code => {
+                let max_width =
+                    self.pages.page_width() - page_square.square.0 * self.pages.square_size();
+
+                let mut code_block = CodeBlock::new(
+                    page_square,
+                    &crate::highlight::PLAIN,
+                    fonts::FALLBACK_FAMILY.to_string(),
+                    codeblock::POINT,
+                    max_width,
+                );
+                code_block.update(renderer)?;
+
+                let code_block = self.insert_text_mark(code_block);
+                self.text_tool
+                    .start_input(Rc::clone(&code_block) as Rc<RefCell<dyn TextInput>>);
+
+                self.history
+                    .record(Action::AddMark(code_block.borrow().to_data()));
             }
             _ => (),
         }
+
+        Ok(())
     }
 
-    pub fn paste(&mut self) -> Result<(), String> {
-        match self.tool_selected {
-            ToolType::Text => self.text_tool.paste(self.clipboard.clipboard_text()?),
-            _ => (),
+    // Called when the mouse is dragged from one square corner to another
+    // Corners are given as (page#, (x, y)), see Line
+    pub fn handle_drag(&mut self, start: (u32, (u32, u32)), end: (u32, (u32, u32))) {
+        // Lines can't cross from one page to another
+        if !matches!(self.tool_selected, ToolType::Line) || start.0 != end.0 {
+            return;
+        }
+
+        let line = match Line::new(
+            start.0,
+            start.1,
+            end.1,
+            self.line_thickness,
+            self.line_color,
+            &self.pages,
+        ) {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        if self.line_blocked(&line, None) {
+            return;
+        }
+
+        self.history.record(Action::AddMark(line.to_data()));
+        self.lines.entry(start.0).or_default().push(line);
+    }
+
+    // The key of the mark covering a square, if there is one
+    pub fn mark_at(&self, page_square: PageSquare) -> Option<PageSquare> {
+        self.marks_on(page_square.page).find_map(|(key, mark)| {
+            let (w, h) = mark.borrow().size();
+            let (x, y) = page_square.square;
+
+            let covers = (key.square.0..key.square.0 + w).contains(&x)
+                && (key.square.1..key.square.1 + h).contains(&y);
+            covers.then_some(key)
+        })
+    }
+
+    // The ends of a line passing through a square, if there is one
+    fn line_at(&self, page_square: PageSquare) -> Option<LineEnds> {
+        self.lines
+            .get(&page_square.page)?
+            .iter()
+            .find(|line| line.squares().contains(&page_square.square))
+            .map(Line::ends)
+    }
+
+    // Whether an area of squares would overlap any mark other than the one stored under ignore,
+    // or any of the squares that a line passes through
+    fn overlaps(
+        &self,
+        page: u32,
+        square: (u32, u32),
+        size: (u32, u32),
+        ignore: Option<PageSquare>,
+    ) -> bool {
+        let in_area = |(x, y): (u32, u32)| {
+            (square.0..square.0 + size.0).contains(&x) && (square.1..square.1 + size.1).contains(&y)
+        };
+
+        self.overlapping(page, square, size)
+            .iter()
+            .any(|key| Some(*key) != ignore)
+            || self
+                .lines
+                .get(&page)
+                .into_iter()
+                .flatten()
+                .any(|line| line.squares().into_iter().any(in_area))
+    }
+
+    // Whether a line would pass through a square taken up by another mark, or be drawn twice
+    // Lines may meet or cross each other, other than the one with the ends given by ignore
+    fn line_blocked(&self, line: &Line, ignore: Option<LineEnds>) -> bool {
+        let page = line.page_square().page;
+        let twice = self
+            .lines
+            .get(&page)
+            .into_iter()
+            .flatten()
+            .any(|other| other.ends() == line.ends() && Some(other.ends()) != ignore);
+
+        twice
+            || line
+                .squares()
+                .into_iter()
+                .any(|square| !self.overlapping(page, square, (1, 1)).is_empty())
+    }
+
+    // The keys of every mark that covers part of an area of squares
+    fn overlapping(&self, page: u32, square: (u32, u32), size: (u32, u32)) -> Vec<PageSquare> {
+        self.marks_on(page)
+            .filter(|(key, mark)| {
+                let (w, h) = mark.borrow().size();
+
+                key.square.0 < square.0 + size.0
+                    && square.0 < key.square.0 + w
+                    && key.square.1 < square.1 + size.1
+                    && square.1 < key.square.1 + h
+            })
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    // Selects the mark or line under a square, or nothing if there isn't one
+    pub fn select_at(&mut self, page_square: Option<PageSquare>) {
+        self.selected = page_square.and_then(|page_square| self.mark_at(page_square));
+        self.selected_line = match self.selected {
+            Some(_) => None,
+            None => page_square.and_then(|page_square| self.line_at(page_square)),
+        };
+    }
+
+    // Pressing on a mark that can be typed into, with a typing tool, starts typing into it with
+    // the caret where it was pressed
+    // The offset is in pixels from the top-left of the page, and the return value is whether
+    // there was such a mark
+    pub fn press_text(
+        &mut self,
+        page_square: PageSquare,
+        offset: (i32, i32),
+        renderer: &Renderer,
+    ) -> Result<bool, String> {
+        self.copy_selection.clear();
+        if !self.tool_selected.types_text() {
+            return Ok(false);
         }
+        let (key, mark) = match self
+            .mark_at(page_square)
+            .and_then(|key| Some((key, Rc::clone(self.text_marks.get(&key)?))))
+        {
+            Some(found) => found,
+            None => return Ok(false),
+        };
+
+        if !self.text_tool.is_editing(key) {
+            self.text_tool.start_input(Rc::clone(&mark));
+        }
+        let (x, y) = self.offset_in_mark(key, offset);
+        mark.borrow_mut().place_caret(x, y, false, renderer)?;
+
+        Ok(true)
+    }
+
+    // Dragging with a typing tool selects text up to the cursor while it stays on the mark being
+    // typed into, and otherwise selects every mark that the drag covers so they can be copied
+    pub fn drag_text(
+        &mut self,
+        from: PageSquare,
+        to: PageSquare,
+        offset: (i32, i32),
+        renderer: &Renderer,
+    ) -> Result<(), String> {
+        if !self.tool_selected.types_text() || from.page != to.page {
+            return Ok(());
+        }
+
+        let editing = self
+            .mark_at(from)
+            .filter(|key| self.text_tool.is_editing(*key));
+        if let (Some(key), Some(text_box)) = (editing, self.text_tool.text_box()) {
+            if self.mark_at(to) == Some(key) {
+                self.copy_selection.clear();
+                let (x, y) = self.offset_in_mark(key, offset);
+                return text_box.borrow_mut().place_caret(x, y, true, renderer);
+            }
+        }
+
+        let left = from.square.0.min(to.square.0);
+        let top = from.square.1.min(to.square.1);
+        let size = (
+            from.square.0.abs_diff(to.square.0) + 1,
+            from.square.1.abs_diff(to.square.1) + 1,
+        );
+        self.copy_selection = self.overlapping(from.page, (left, top), size);
+        self.copy_selection
+            .sort_by_key(|key| (key.square.1, key.square.0));
+
         Ok(())
     }
 
-    pub fn draw_marks(&self, renderer: &mut Renderer) -> Result<(), String> {
+    // Turns an offset from the top-left of a page into one from the top-left of a mark on it
+    fn offset_in_mark(&self, key: PageSquare, offset: (i32, i32)) -> (i32, i32) {
+        let square_size = self.pages.square_size() as i32;
+        (
+            offset.0 - key.square.0 as i32 * square_size,
+            offset.1 - key.square.1 as i32 * square_size,
+        )
+    }
+
+    // Puts the selected text on the clipboard, removing it from its mark when cutting
+    // Marks selected by dragging across them are copied as text, in reading order, but never cut
+    fn copy(&mut self, cut: bool, renderer: &mut Renderer) -> Result<(), String> {
+        if !self.copy_selection.is_empty() {
+            let texts: Vec<String> = self
+                .copy_selection
+                .iter()
+                .filter_map(|key| self.marks.get(key)?.borrow().to_data().text())
+                .collect();
+            return self.clipboard.set_clipboard_text(&texts.join("\n\n"));
+        }
+
+        let text_box = match self.text_tool.text_box() {
+            Some(text_box) => Rc::clone(text_box),
+            None => return Ok(()),
+        };
+        let text = match text_box.borrow().selected_text() {
+            Some(text) => text,
+            None => return Ok(()),
+        };
+        self.clipboard.set_clipboard_text(&text)?;
+
+        if cut {
+            let before = text_box.borrow().to_data();
+            text_box.borrow_mut().delete_selection(renderer)?;
+            let after = text_box.borrow().to_data();
+            self.history.record(Action::EditMark { before, after });
+        }
+
+        Ok(())
+    }
+
+    // The key and size of the selected mark, or the top-left square and size of the selected
+    // line's bounding box
+    pub fn selection(&self) -> Option<(PageSquare, (u32, u32))> {
+        if let Some(ends) = self.selected_line {
+            let line = self.line(ends)?;
+            return Some((line.page_square(), line.size()));
+        }
+        let key = self.selected?;
+        let size = self.marks.get(&key)?.borrow().size();
+
+        Some((key, size))
+    }
+
+    // What the selected mark or line is like now
+    fn selected_data(&self) -> Option<MarkData> {
+        match self.selected_line {
+            Some(ends) => Some(self.line(ends)?.to_data()),
+            None => Some(self.marks.get(&self.selected?)?.borrow().to_data()),
+        }
+    }
+
+    fn line(&self, ends: LineEnds) -> Option<&Line> {
+        self.lines
+            .get(&ends.0)?
+            .iter()
+            .find(|line| line.ends() == ends)
+    }
+
+    // Moves the selected mark by the number of squares between from and to
+    // The mark may end up on another page, but not off of one or on top of another mark
+    pub fn move_selection(
+        &mut self,
+        from: PageSquare,
+        to: PageSquare,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        let (key, size) = match self.selection() {
+            Some(selection) => selection,
+            None => return Ok(()),
+        };
+
+        let x = key.square.0 as i32 + to.square.0 as i32 - from.square.0 as i32;
+        let y = key.square.1 as i32 + to.square.1 as i32 - from.square.1 as i32;
+        let page_squares = self.pages.page_squares();
+        if x < 0
+            || y < 0
+            || x as u32 + size.0 > page_squares.0
+            || y as u32 + size.1 > page_squares.1
+        {
+            return Ok(());
+        }
+        let square = (x as u32, y as u32);
+        if (to.page, square) == (key.page, key.square) {
+            return Ok(());
+        }
+
+        let before = match self.selected_data() {
+            Some(data) => data,
+            None => return Ok(()),
+        };
+        let after = before.moved(to.page, square);
+        let blocked = match &after {
+            MarkData::Line(data) => match Line::from_data(data, &self.pages) {
+                Ok(line) => self.line_blocked(&line, self.selected_line),
+                Err(_) => true,
+            },
+            _ => self.overlaps(to.page, square, size, Some(key)),
+        };
+        if blocked {
+            return Ok(());
+        }
+
+        let selected = (self.selected, self.selected_line);
+        self.remove_data(&before, renderer)?;
+        if let Err(e) = self.insert_mark(&after, renderer) {
+            self.insert_mark(&before, renderer)?;
+            (self.selected, self.selected_line) = selected;
+            return Err(e);
+        }
+
+        match &after {
+            MarkData::Line(data) => {
+                self.selected_line = Some(line::ends(data.page, data.start, data.end))
+            }
+            _ => self.selected = Some(PageSquare::new(to.page, square, &self.pages)?),
+        }
+        self.history.record(Action::MoveMark { before, after });
+
+        Ok(())
+    }
+
+    pub fn set_hovered(&mut self, page_square: Option<PageSquare>) {
+        self.hovered = page_square
+    }
+
+    // Pastes the clipboard's text into the mark being typed into, at its caret
+    // Otherwise it is placed as a new mark on the square under the cursor, as an image if it is
+    // the path of an image file and as a text box if not
+    pub fn paste(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        if !self.clipboard.has_clipboard_text() {
+            return Ok(());
+        }
+        let text = self.clipboard.clipboard_text()?;
+
+        if let Some(text_box) = self.text_tool.text_box() {
+            let text_box = Rc::clone(text_box);
+            let before = text_box.borrow().to_data();
+            self.text_tool.paste(&text, renderer)?;
+            let after = text_box.borrow().to_data();
+            if before != after {
+                self.history.record(Action::EditMark { before, after });
+            }
+            return Ok(());
+        }
+
+        let page_square = match self.hovered {
+            Some(page_square)
+                if !self.overlaps(page_square.page, page_square.square, (1, 1), None) =>
+            {
+                page_square
+            }
+            _ => return Ok(()),
+        };
+        let page_squares = self.pages.page_squares();
+        let space = (
+            page_squares.0 - page_square.square.0,
+            page_squares.1 - page_square.square.1,
+        );
+
+        if let Some(path) = image_path(&text) {
+            let image = ImageMark::new(page_square, &path, space, renderer)?;
+            if self.overlaps(page_square.page, page_square.square, image.size(), None) {
+                image.free_textures(renderer);
+                return Ok(());
+            }
+
+            self.history.record(Action::AddMark(image.to_data()));
+            self.place_mark(page_square, Rc::new(RefCell::new(image)));
+            return Ok(());
+        }
+
+        let text_box = self.insert_text_mark(TextBox::new(
+            page_square,
+            self.text_font.clone(),
+            FontStyle::NORMAL,
+            self.text_point,
+            self.text_color,
+            space.0 * self.pages.square_size(),
+        ));
+        text_box.borrow_mut().push_str(&text, renderer)?;
+        self.history
+            .record(Action::AddMark(text_box.borrow().to_data()));
+
+        // Carry on typing after the pasted text
+        if self.tool_selected.types_text() {
+            self.text_tool
+                .start_input(Rc::clone(&text_box) as Rc<RefCell<dyn TextInput>>);
+        }
+
+        Ok(())
+    }
+
+    // Captures the document's current state so it can be saved or exported
+    pub fn to_document(&self) -> Document {
+        let mut marks: Vec<MarkData> = self.marks.values().map(|m| m.borrow().to_data()).collect();
+        marks.extend(self.lines.values().flatten().map(Line::to_data));
+        // Keep the file stable between saves by ordering marks by where they are on the pages
+        marks.sort_by_key(|m| (m.page(), m.square().1, m.square().0));
+
+        Document {
+            page_squares: self.pages.page_squares(),
+            pages: self.pages.pages(),
+            style: self.pages.style(),
+            marks,
+            fonts: Vec::new(),
+        }
+    }
+
+    // Replaces the current document, rebuilding the pages and every mark's textures
+    pub fn load_document(
+        &mut self,
+        document: &Document,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        self.text_tool.stop_input();
+        self.selected = None;
+        self.copy_selection.clear();
+
+        // Documents saved with their fonts keep being saved with them
+        renderer.fonts_mut().add_embedded(&document.fonts)?;
+        if !document.fonts.is_empty() {
+            self.embed_fonts = true;
+        }
+
+        if document.page_squares != self.pages.page_squares() {
+            // The layout is the app's, not the document's, so it stays as it is
+            let layout = self.pages.layout();
+            renderer.remove_textures(self.pages.id);
+            self.pages = Pages::new(document.page_squares, renderer)?;
+            self.pages.set_layout(layout);
+        }
+        self.pages.set_pages(document.pages);
+        self.pages.set_style(document.style);
+        self.clear_marks(renderer);
+
+        for data in &document.marks {
+            self.insert_mark(data, renderer)?;
+        }
+        self.history.clear();
+
+        Ok(())
+    }
+
+    // Rebuilds a mark from its saved state and places it on the pages
+    fn insert_mark(&mut self, data: &MarkData, renderer: &mut Renderer) -> Result<(), String> {
+        match data {
+            MarkData::Text(text_data) => {
+                let text_box = TextBox::from_data(text_data, &self.pages, renderer)?;
+                self.insert_text_mark(text_box);
+            }
+            MarkData::Line(line_data) => {
+                let line = Line::from_data(line_data, &self.pages)?;
+                self.lines.entry(line_data.page).or_default().push(line);
+            }
+            MarkData::Bullets(bullet_data) => {
+                let list = BulletList::from_data(bullet_data, &self.pages, renderer)?;
+                self.insert_text_mark(list);
+            }
+            MarkData::Math(math_data) => {
+                let math_box = MathBox::from_data(math_data, &self.pages, renderer)?;
+                self.insert_text_mark(math_box);
+            }
+            MarkData::Code(code_data) => {
+                let code_block = CodeBlock::from_data(code_data, &self.pages, renderer)?;
+                self.insert_text_mark(code_block);
+            }
+            MarkData::Image(image_data) => {
+                let image = ImageMark::from_data(image_data, &self.pages, renderer)?;
+                self.place_mark(image.page_square(), Rc::new(RefCell::new(image)));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Places a mark that can be typed into, so that typing tools can find it again later
+    fn insert_text_mark<T: TextInput + 'static>(&mut self, mark: T) -> Rc<RefCell<T>> {
+        let page_square = mark.page_square();
+        let mark = Rc::new(RefCell::new(mark));
+
+        self.place_mark(page_square, Rc::clone(&mark) as Rc<RefCell<dyn Mark>>);
+        self.text_marks
+            .insert(page_square, Rc::clone(&mark) as Rc<RefCell<dyn TextInput>>);
+
+        mark
+    }
+
+    fn place_mark(&mut self, page_square: PageSquare, mark: Rc<RefCell<dyn Mark>>) {
+        // A mark replacing another under the same key is already listed on its page
+        if self.marks.insert(page_square, mark).is_none() {
+            self.page_marks
+                .entry(page_square.page)
+                .or_default()
+                .push(page_square);
+        }
+    }
+
+    // The marks on a page, with their keys
+    fn marks_on(&self, page: u32) -> impl Iterator<Item = (PageSquare, &Rc<RefCell<dyn Mark>>)> {
+        self.page_marks
+            .get(&page)
+            .into_iter()
+            .flatten()
+            .filter_map(|key| Some((*key, self.marks.get(key)?)))
+    }
+
+    // Takes a mark off of the pages, freeing its textures
+    fn remove_mark(&mut self, page_square: PageSquare, renderer: &mut Renderer) {
+        if let Some(mark) = self.marks.remove(&page_square) {
+            self.text_marks.remove(&page_square);
+            if let Some(keys) = self.page_marks.get_mut(&page_square.page) {
+                keys.retain(|key| *key != page_square);
+            }
+            self.copy_selection.retain(|key| *key != page_square);
+            if self.text_tool.is_editing(page_square) {
+                self.text_tool.stop_input();
+            }
+            if self.selected == Some(page_square) {
+                self.selected = None;
+            }
+            mark.borrow().free_textures(renderer);
+        }
+    }
+
+    // Takes the mark or line that was saved as data off of the pages
+    fn remove_data(&mut self, data: &MarkData, renderer: &mut Renderer) -> Result<(), String> {
+        match data {
+            MarkData::Line(data) => {
+                let ends = line::ends(data.page, data.start, data.end);
+                if let Some(lines) = self.lines.get_mut(&data.page) {
+                    lines.retain(|line| line.ends() != ends);
+                }
+                if self.selected_line == Some(ends) {
+                    self.selected_line = None;
+                }
+            }
+            _ => {
+                let page_square = PageSquare::new(data.page(), data.square(), &self.pages)?;
+                self.remove_mark(page_square, renderer);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Takes every mark off of the pages, freeing their textures
+    fn clear_marks(&mut self, renderer: &mut Renderer) {
         for mark in self.marks.values() {
-            mark.borrow().draw(renderer)?;
+            mark.borrow().free_textures(renderer);
+        }
+        self.marks.clear();
+        self.text_marks.clear();
+        self.page_marks.clear();
+        self.lines.clear();
+        self.selected_line = None;
+    }
+
+    pub fn save(&self, path: &Path, renderer: &Renderer) -> Result<(), String> {
+        let mut document = self.to_document();
+        if self.embed_fonts {
+            document.fonts = renderer.fonts().library().embed(&document)?;
         }
+        document.write(path)
+    }
+
+    pub fn load(&mut self, path: &Path, renderer: &mut Renderer) -> Result<(), String> {
+        let document = Document::read(path)?;
+        self.load_document(&document, renderer)?;
+        self.file_path = Some(path.to_path_buf());
 
         Ok(())
     }
+
+    // Only marks on pages in view are drawn, and of those only the ones that reach into view
+    pub fn draw_marks(&self, renderer: &mut Renderer) -> Result<(), String> {
+        let view = renderer.view();
+        let square_size = self.pages.square_size();
+        // Lines are given a square around them, as they may be thicker than their squares
+        let in_view = |key: PageSquare, (w, h): (u32, u32)| {
+            Rect::new(
+                key.position.x() - square_size as i32,
+                key.position.y() - square_size as i32,
+                (w + 2) * square_size,
+                (h + 2) * square_size,
+            )
+            .has_intersection(view)
+        };
+        for page in self.pages.pages_in(view) {
+            for (key, mark) in self.marks_on(page) {
+                if in_view(key, mark.borrow().size()) {
+                    mark.borrow().draw(renderer)?;
+                }
+            }
+            for line in self.lines.get(&page).into_iter().flatten() {
+                if in_view(line.page_square(), line.size()) {
+                    line.draw(renderer)?;
+                }
+            }
+        }
+        self.text_tool.draw(renderer)?;
+
+        // Shade the squares covered by marks selected for copying
+        for key in &self.copy_selection {
+            if let Some(mark) = self.marks.get(key) {
+                let (w, h) = mark.borrow().size();
+                renderer.draw_fill_rect(
+                    key.position,
+                    (w * square_size - 1, h * square_size - 1),
+                    text_tool::SELECTION_COLOR,
+                )?;
+            }
+        }
+
+        // Outline the squares covered by the selected mark
+        if let Some((key, (w, h))) = self.selection() {
+            renderer.draw_rect(
+                key.position,
+                2,
+                (w * square_size - 1, h * square_size - 1),
+                Color::RGB(30, 120, 220),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// The image file that clipboard text refers to, if it is the path of one
+// SDL's clipboard only holds text, so images are pasted from the paths or file:// URIs that file
+// managers copy
+fn image_path(text: &str) -> Option<PathBuf> {
+    let line = text.lines().next()?.trim();
+    let path = match line.strip_prefix("file://") {
+        // Skip over the host, which is usually empty
+        Some(uri) => PathBuf::from(percent_decode(&uri[uri.find('/')?..])),
+        None => PathBuf::from(line),
+    };
+
+    (ImageMark::is_image(&path) && path.is_file()).then_some(path)
+}
+
+// Decodes the %XX escapes in a URI, leaving anything that isn't a valid escape as it is
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mark::textbox::TEXT_COLORS;
+    use crate::testing::{temp_path, with_editor};
+
+    fn type_text(editor: &mut Editor, text: &str, renderer: &mut Renderer) {
+        let event = Event::TextInput {
+            timestamp: 0,
+            window_id: 0,
+            text: text.to_string(),
+        };
+        editor.handle_event(&event, renderer).unwrap();
+    }
+
+    fn add_text(editor: &mut Editor, page: u32, text: &str, renderer: &mut Renderer) {
+        editor.set_tool(ToolType::Text);
+        let page_square = PageSquare::new(page, (2, 2), editor.get_pages()).unwrap();
+        editor.handle_click(page_square, renderer).unwrap();
+        type_text(editor, text, renderer);
+        editor.set_tool(ToolType::Move);
+    }
+
+    #[test]
+    fn removing_a_page_removes_its_marks() {
+        with_editor(|editor, renderer| {
+            add_text(editor, 0, "first", renderer);
+            add_text(editor, 1, "second", renderer);
+
+            editor.remove_page(renderer);
+            let document = editor.to_document();
+            assert_eq!(document.pages, 1);
+            assert_eq!(document.marks.len(), 1);
+
+            // What is saved can be opened again
+            let path = temp_path("remove-page.sqpd");
+            editor.save(&path, renderer).unwrap();
+            editor.load(&path, renderer).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(editor.to_document(), document);
+        });
+    }
+
+    #[test]
+    fn resizing_pages_can_be_undone_along_with_what_came_before() {
+        with_editor(|editor, renderer| {
+            add_text(editor, 1, "second", renderer);
+            draw_line(editor, (2, 8), (6, 8));
+            let before = editor.to_document();
+
+            editor.set_page_squares((30, 20), renderer).unwrap();
+            let resized = editor.to_document();
+            assert_eq!(resized.page_squares, (30, 20));
+            assert_eq!(resized.marks, before.marks);
+
+            editor.undo(renderer).unwrap();
+            assert_eq!(editor.to_document(), before);
+            // The edits made before resizing are still there to undo
+            editor.undo(renderer).unwrap();
+            assert_eq!(lines(editor), 0);
+
+            editor.redo(renderer).unwrap();
+            editor.redo(renderer).unwrap();
+            assert_eq!(editor.to_document(), resized);
+        });
+    }
+
+    #[test]
+    fn undoing_a_page_removal_restores_its_marks() {
+        with_editor(|editor, renderer| {
+            add_text(editor, 1, "second", renderer);
+            let before = editor.to_document();
+
+            editor.remove_page(renderer);
+            editor.undo(renderer).unwrap();
+            assert_eq!(editor.to_document(), before);
+
+            editor.redo(renderer).unwrap();
+            assert_eq!(editor.to_document().pages, 1);
+            assert!(editor.to_document().marks.is_empty());
+        });
+    }
+
+    #[test]
+    fn marks_are_not_placed_over_others() {
+        with_editor(|editor, renderer| {
+            add_text(editor, 0, "first", renderer);
+            let before = editor.to_document();
+
+            editor.set_tool(ToolType::Math);
+            let page_square = PageSquare::new(0, (2, 2), editor.get_pages()).unwrap();
+            editor.handle_click(page_square, renderer).unwrap();
+            assert_eq!(editor.to_document(), before);
+            assert_eq!(editor.marks_on(0).count(), 1);
+        });
+    }
+
+    fn draw_line(editor: &mut Editor, start: (u32, u32), end: (u32, u32)) {
+        editor.set_tool(ToolType::Line);
+        editor.handle_drag((0, start), (0, end));
+    }
+
+    fn lines(editor: &Editor) -> usize {
+        editor
+            .to_document()
+            .marks
+            .iter()
+            .filter(|mark| matches!(mark, MarkData::Line(_)))
+            .count()
+    }
+
+    #[test]
+    fn lines_can_meet_and_cross() {
+        with_editor(|editor, _| {
+            // An L shape, and an X
+            draw_line(editor, (2, 2), (6, 2));
+            draw_line(editor, (2, 2), (2, 6));
+            draw_line(editor, (8, 2), (12, 6));
+            draw_line(editor, (8, 6), (12, 2));
+            assert_eq!(lines(editor), 4);
+
+            // The same line again, drawn the other way round
+            draw_line(editor, (6, 2), (2, 2));
+            assert_eq!(lines(editor), 4);
+        });
+    }
+
+    #[test]
+    fn lines_and_marks_only_block_the_squares_lines_pass_through() {
+        with_editor(|editor, renderer| {
+            draw_line(editor, (2, 2), (6, 6));
+
+            // Inside the line's bounding box, but off of the line itself
+            editor.set_tool(ToolType::Math);
+            let beside = PageSquare::new(0, (5, 2), editor.get_pages()).unwrap();
+            editor.handle_click(beside, renderer).unwrap();
+            assert_eq!(editor.marks_on(0).count(), 1);
+
+            let on = PageSquare::new(0, (3, 3), editor.get_pages()).unwrap();
+            editor.handle_click(on, renderer).unwrap();
+            assert_eq!(editor.marks_on(0).count(), 1);
+
+            // A line can't be drawn through the math box either
+            draw_line(editor, (4, 1), (6, 3));
+            assert_eq!(lines(editor), 1);
+        });
+    }
+
+    #[test]
+    fn color_buttons_color_new_lines_while_drawing_them() {
+        with_editor(|editor, renderer| {
+            let (red, blue) = (TEXT_COLORS[1], TEXT_COLORS[2]);
+            editor.set_tool(ToolType::Line);
+            editor.set_color(red, renderer).unwrap();
+            draw_line(editor, (2, 2), (5, 2));
+            assert_eq!(editor.color(), red);
+
+            // Other tools keep coloring text
+            editor.set_tool(ToolType::Text);
+            editor.set_color(blue, renderer).unwrap();
+            assert_eq!((editor.line_color(), editor.text_color()), (red, blue));
+            match &editor.to_document().marks[0] {
+                MarkData::Line(line) => assert_eq!(line.color, red),
+                mark => panic!("Expected a line, found {:?}", mark),
+            }
+        });
+    }
+
+    #[test]
+    fn lines_are_moved_and_deleted_with_the_move_tool() {
+        with_editor(|editor, renderer| {
+            draw_line(editor, (2, 2), (5, 2));
+            editor.set_tool(ToolType::Move);
+
+            let from = PageSquare::new(0, (3, 2), editor.get_pages()).unwrap();
+            let to = PageSquare::new(0, (3, 4), editor.get_pages()).unwrap();
+            editor.select_at(Some(from));
+            editor.move_selection(from, to, renderer).unwrap();
+            match &editor.to_document().marks[0] {
+                MarkData::Line(line) => assert_eq!((line.start, line.end), ((2, 4), (5, 4))),
+                mark => panic!("Expected a line, found {:?}", mark),
+            }
+
+            let delete = Event::KeyDown {
+                timestamp: 0,
+                window_id: 0,
+                keycode: Some(Keycode::Delete),
+                scancode: None,
+                keymod: Mod::NOMOD,
+                repeat: false,
+            };
+            editor.handle_event(&delete, renderer).unwrap();
+            assert_eq!(lines(editor), 0);
+
+            editor.undo(renderer).unwrap();
+            assert_eq!(lines(editor), 1);
+        });
+    }
+
+    #[test]
+    fn failed_saves_are_reported_without_ending_the_editor() {
+        with_editor(|editor, renderer| {
+            let path = temp_path("missing-folder").join("notes.sqpd");
+            editor.set_file_path(path.clone());
+
+            let save = Event::KeyDown {
+                timestamp: 0,
+                window_id: 0,
+                keycode: Some(Keycode::S),
+                scancode: None,
+                keymod: Mod::LCTRLMOD,
+                repeat: false,
+            };
+            assert!(editor.handle_event(&save, renderer).is_ok());
+            assert!(editor.take_failure().is_some());
+            assert_eq!(editor.file_path(), Some(path.as_path()));
+        });
+    }
+
+    #[test]
+    fn images_that_cannot_be_loaded_still_open() {
+        with_editor(|editor, renderer| {
+            let image = |path: &str, data: &[u8]| {
+                MarkData::Image(crate::document::ImageData {
+                    page: 0,
+                    square: (2, 2),
+                    size: (3, 3),
+                    path: path.to_string(),
+                    data: data.into(),
+                })
+            };
+            let mut document = editor.to_document();
+            document.marks = vec![image("/missing/picture.png", &[])];
+            editor.load_document(&document, renderer).unwrap();
+            assert_eq!(editor.to_document(), document);
+
+            document.marks = vec![image("picture.png", b"not an image")];
+            editor.load_document(&document, renderer).unwrap();
+            assert_eq!(editor.to_document(), document);
+        });
+    }
+
+    #[test]
+    fn typing_is_undone_a_word_at_a_time() {
+        with_editor(|editor, renderer| {
+            editor.set_tool(ToolType::Text);
+            let page_square = PageSquare::new(0, (2, 2), editor.get_pages()).unwrap();
+            editor.handle_click(page_square, renderer).unwrap();
+            for text in ["o", "n", "e", " ", "t", "w", "o"] {
+                type_text(editor, text, renderer);
+            }
+
+            editor.undo(renderer).unwrap();
+            let text = |editor: &Editor| editor.to_document().marks[0].text().unwrap();
+            assert_eq!(text(editor), "one ");
+            editor.undo(renderer).unwrap();
+            assert_eq!(text(editor), "");
+        });
+    }
 }
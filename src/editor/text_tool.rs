@@ -1,16 +1,24 @@
-use crate::mark::textbox::TextBox;
+use crate::mark::TextInput;
+use crate::position::{PageSquare, Position};
 use crate::renderer::Renderer;
 
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use sdl2::event::Event;
-use sdl2::keyboard::{Keycode, TextInputUtil};
+use sdl2::keyboard::{Keycode, Mod, TextInputUtil};
+use sdl2::pixels::Color;
+
+// How long the caret is shown, and then hidden, for when blinking
+pub const CARET_BLINK: Duration = Duration::from_millis(530);
+pub const SELECTION_COLOR: Color = Color::RGBA(30, 120, 220, 70);
 
 // Contains the state of any text being inputted
 pub struct TextTool {
     text_input: TextInputUtil, // May need to share this later with code tool
-    text_box: Option<Rc<RefCell<TextBox>>>,
+    text_box: Option<Rc<RefCell<dyn TextInput>>>, // The mark being typed into
+    blink_start: Instant,      // The caret is shown without blinking for a moment after each edit
 }
 
 impl TextTool {
@@ -18,11 +26,13 @@ impl TextTool {
         TextTool {
             text_input,
             text_box: None,
+            blink_start: Instant::now(),
         }
     }
 
-    pub fn start_input(&mut self, text_box: Rc<RefCell<TextBox>>) {
+    pub fn start_input(&mut self, text_box: Rc<RefCell<dyn TextInput>>) {
         self.text_box = Some(text_box);
+        self.blink_start = Instant::now();
         self.text_input.start()
     }
     pub fn stop_input(&mut self) {
@@ -30,7 +40,69 @@ impl TextTool {
         self.text_input.stop()
     }
 
+    pub fn text_box(&self) -> Option<&Rc<RefCell<dyn TextInput>>> {
+        self.text_box.as_ref()
+    }
+
+    // Whether the text box at page_square is the one receiving input
+    pub fn is_editing(&self, page_square: PageSquare) -> bool {
+        match &self.text_box {
+            Some(text_box) => text_box.borrow().page_square() == page_square,
+            None => false,
+        }
+    }
+
+    // Whether an event can change the text of the mark being typed into, rather than just moving
+    // its caret or selection
+    pub fn edits(event: &Event) -> bool {
+        match event {
+            Event::TextInput { .. } => true,
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => !matches!(
+                keycode,
+                Keycode::Left
+                    | Keycode::Right
+                    | Keycode::Up
+                    | Keycode::Down
+                    | Keycode::Home
+                    | Keycode::End
+                    | Keycode::LShift
+                    | Keycode::RShift
+                    | Keycode::LCtrl
+                    | Keycode::RCtrl
+                    | Keycode::LAlt
+                    | Keycode::RAlt
+            ),
+            _ => false,
+        }
+    }
+
+    // Whether an event ends a word, after which typing is undone separately
+    pub fn ends_word(event: &Event) -> bool {
+        match event {
+            Event::TextInput { text, .. } => text.ends_with(char::is_whitespace),
+            Event::KeyDown {
+                keycode: Some(Keycode::Return),
+                ..
+            } => true,
+            _ => false,
+        }
+    }
+
+    // When the caret next blinks on or off, while a mark is being typed into
+    pub fn next_blink(&self) -> Option<Instant> {
+        self.text_box.as_ref()?;
+        let blinks = self.blink_start.elapsed().as_millis() / CARET_BLINK.as_millis();
+        Some(self.blink_start + CARET_BLINK * (blinks as u32 + 1))
+    }
+
     pub fn handle_event(&mut self, event: &Event, renderer: &mut Renderer) -> Result<(), String> {
+        if matches!(event, Event::TextInput { .. } | Event::KeyDown { .. }) {
+            self.blink_start = Instant::now();
+        }
+
         match &self.text_box {
             Some(text_box) => match event {
                 Event::TextInput { text, .. } => text_box.borrow_mut().push_str(text, renderer),
@@ -41,12 +113,76 @@ impl TextTool {
                 Event::KeyDown {
                     keycode: Some(Keycode::Return),
                     ..
-                } => Ok(text_box.borrow_mut().new_line()),
+                } => text_box.borrow_mut().new_line(renderer),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Delete),
+                    ..
+                } => text_box.borrow_mut().delete(renderer),
+                Event::KeyDown {
+                    keycode:
+                        Some(
+                            keycode @ (Keycode::Left
+                            | Keycode::Right
+                            | Keycode::Up
+                            | Keycode::Down
+                            | Keycode::Home
+                            | Keycode::End),
+                        ),
+                    keymod,
+                    ..
+                } => {
+                    // Holding shift selects the text that the caret moves over
+                    let select = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    text_box.borrow_mut().move_caret(*keycode, select, renderer)
+                }
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    keymod,
+                    ..
+                } => text_box
+                    .borrow_mut()
+                    .handle_key(*keycode, *keymod, renderer),
                 _ => Ok(()),
             },
             None => Ok(()),
         }
     }
 
-    pub fn paste(&mut self, text: String) {}
+    // Draws the selection and caret of the mark being typed into, while the caret is blinked on
+    pub fn draw(&self, renderer: &mut Renderer) -> Result<(), String> {
+        let text_box = match &self.text_box {
+            Some(text_box) => text_box,
+            None => return Ok(()),
+        };
+
+        let rects = text_box.borrow().selection_rects(renderer)?;
+        for (position, size) in rects {
+            renderer.draw_fill_rect(position, size, SELECTION_COLOR)?;
+        }
+        let blinks = self.blink_start.elapsed().as_millis() / CARET_BLINK.as_millis();
+        if blinks % 2 == 1 {
+            return Ok(());
+        }
+
+        let text_box = text_box.borrow();
+        if let Some(position) = text_box.caret_position(renderer)? {
+            renderer.draw_fill_rect(
+                Position::add(position, 0, 4),
+                (2, text_box.line_height() - 6),
+                Color::BLACK,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // Types text from the clipboard into the mark at its caret, with each new line starting a
+    // new paragraph
+    pub fn paste(&mut self, text: &str, renderer: &mut Renderer) -> Result<(), String> {
+        self.blink_start = Instant::now();
+        match &self.text_box {
+            Some(text_box) => text_box.borrow_mut().push_str(text, renderer),
+            None => Ok(()),
+        }
+    }
 }
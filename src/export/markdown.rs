@@ -0,0 +1,147 @@
+use crate::document::{Document, MarkData};
+use crate::mark::bullet_list::BulletKind;
+use crate::mark::textbox::{self, StyleSpan, TextStyle};
+
+use sdl2::ttf::FontStyle;
+
+use std::fmt::Write;
+use std::path::Path;
+
+// Writes a document as Markdown, one section per page with marks in reading order
+// Lines drawn with the line tool have no Markdown equivalent and are left out, as are text colors
+pub fn export(document: &Document, path: &Path) -> Result<(), String> {
+    std::fs::write(path, to_markdown(document))
+        .map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+pub fn to_markdown(document: &Document) -> String {
+    let mut out = String::new();
+
+    for page in 0..document.pages {
+        if page > 0 {
+            out.push('\n');
+        }
+        writeln!(out, "## Page {}", page + 1).unwrap();
+
+        // Top to bottom, then left to right
+        let mut marks: Vec<&MarkData> =
+            document.marks.iter().filter(|m| m.page() == page).collect();
+        marks.sort_by_key(|m| (m.square().1, m.square().0));
+
+        for mark in marks {
+            let block = match mark {
+                MarkData::Text(data) => {
+                    let default = TextStyle {
+                        font_style: data.font_style,
+                        color: data.color,
+                    };
+                    let spans = textbox::paragraph_spans(&data.lines, &data.breaks, &data.spans);
+                    textbox::paragraphs(&data.lines, &data.breaks)
+                        .iter()
+                        .zip(spans)
+                        .map(|(text, spans)| emphasize(text, &spans, default))
+                        .collect::<Vec<String>>()
+                        .join("\n\n")
+                }
+                MarkData::Bullets(data) => {
+                    let markers = data.kind.markers(data.items.iter().map(|item| item.level));
+                    let default = TextStyle {
+                        font_style: data.font_style,
+                        color: data.color,
+                    };
+                    let mut list = String::new();
+                    for (item, marker) in data.items.iter().zip(markers) {
+                        let marker = match data.kind {
+                            BulletKind::Dot => "-".to_string(),
+                            BulletKind::Numbered => marker,
+                            // Markdown has no lettered lists
+                            BulletKind::Lettered => format!("- {}", marker),
+                        };
+                        writeln!(
+                            list,
+                            "{}{} {}",
+                            "    ".repeat(item.level as usize),
+                            marker,
+                            textbox::paragraphs(&item.lines, &item.breaks)
+                                .iter()
+                                .zip(textbox::paragraph_spans(
+                                    &item.lines,
+                                    &item.breaks,
+                                    &item.spans
+                                ))
+                                .map(|(text, spans)| emphasize(text, &spans, default))
+                                .collect::<Vec<String>>()
+                                .join(" ")
+                        )
+                        .unwrap();
+                    }
+                    list.trim_end().to_string()
+                }
+                MarkData::Math(data) => format!("$$\n{}\n$$", data.source),
+                MarkData::Code(data) => {
+                    let language = if data.language == crate::highlight::PLAIN.name {
+                        ""
+                    } else {
+                        &data.language
+                    };
+                    format!("```{}\n{}\n```", language, data.lines.join("\n"))
+                }
+                MarkData::Image(data) => {
+                    let name = Path::new(&data.path)
+                        .file_stem()
+                        .map(|s| s.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    format!("![{}](<{}>)", name, data.path)
+                }
+                MarkData::Line(_) => continue,
+            };
+
+            if !block.trim().is_empty() {
+                write!(out, "\n{}\n", block).unwrap();
+            }
+        }
+    }
+
+    out
+}
+
+// Marks up the bold and italic runs of a paragraph
+// Spaces at either end of a run are kept outside of its markers, or they wouldn't be read as
+// emphasis
+fn emphasize(text: &str, spans: &[StyleSpan], default: TextStyle) -> String {
+    let marker = |font_style: FontStyle| match (
+        font_style.contains(FontStyle::BOLD),
+        font_style.contains(FontStyle::ITALIC),
+    ) {
+        (true, true) => "***",
+        (true, false) => "**",
+        (false, true) => "*",
+        (false, false) => "",
+    };
+
+    // Runs that only differ in color are joined, so their markers don't run into each other
+    let mut runs: Vec<(usize, usize, &str)> = Vec::new();
+    for (start, end, style) in textbox::line_runs(text.len(), spans, default) {
+        let marker = marker(style.font_style);
+        match runs.last_mut() {
+            Some(run) if run.2 == marker => run.1 = end,
+            _ => runs.push((start, end, marker)),
+        }
+    }
+
+    let mut out = String::new();
+    for (start, end, marker) in runs {
+        let run = &text[start..end];
+        let inner = run.trim();
+        if marker.is_empty() || inner.is_empty() {
+            out.push_str(run);
+            continue;
+        }
+        let leading = run.len() - run.trim_start().len();
+        out.push_str(&run[..leading]);
+        write!(out, "{}{}{}", marker, inner, marker).unwrap();
+        out.push_str(&run[leading + inner.len()..]);
+    }
+
+    out
+}
@@ -0,0 +1,8 @@
+pub mod markdown;
+pub mod pdf;
+pub mod png;
+pub(crate) mod ttf;
+pub(crate) mod zlib;
+
+// Writing documents out to other formats
+// Exporters work from a Document, so they don't need the app or its window to be running
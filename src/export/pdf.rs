@@ -0,0 +1,863 @@
+use super::ttf::TrueType;
+use super::zlib;
+use crate::app::pages::{line_rows, SQUARE_SIZE};
+use crate::config::Config;
+use crate::document::{
+    BulletData, CodeData, Document, FontData, ImageData, LineData, MarkData, MathData, TextData,
+};
+use crate::fonts::{self, FontLibrary};
+use crate::highlight;
+use crate::mark::codeblock::{self, CodeBlock};
+use crate::mark::textbox::{self, StyleSpan, TextStyle};
+use crate::math::layout::{self, Item, Metrics};
+use crate::math::parser::{self, Node, SymbolKind};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+use sdl2::image::ImageRWops;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rwops::RWops;
+use sdl2::ttf::FontStyle;
+
+// Squares are 5mm across, so the default page of 42 by 59 squares comes out close to A4
+const SQUARE_MM: f32 = 5.0;
+
+// A font used somewhere in the document, along with every glyph that was used from it
+struct PdfFont {
+    name: String, // File name of the font, without its extension
+    font: TrueType,
+    glyphs: BTreeMap<u16, char>, // The character each glyph was first used for
+}
+
+#[derive(Default)]
+struct Fonts<'d> {
+    fonts: Vec<PdfFont>,
+    indices: HashMap<String, usize>,
+    library: Option<FontLibrary>, // Read the first time a font is needed
+    embedded: &'d [FontData],     // Fonts saved in the document, used before installed ones
+}
+
+impl Fonts<'_> {
+    // Loads a font the first time it is used, finding its file by family and style the same way
+    // as the app does
+    fn get(&mut self, font_name: &str, font_style: FontStyle) -> Result<usize, String> {
+        if self.library.is_none() {
+            let mut library = FontLibrary::discover(&Config::load()?)?;
+            library.add_embedded(self.embedded)?;
+            self.library = Some(library);
+        }
+        let library = self.library.as_ref().unwrap();
+        let path = library
+            .find(font_name, font_style)
+            .or_else(|| library.find(fonts::DEFAULT_FAMILY, font_style))
+            .map(|face| face.path.clone())
+            .ok_or_else(|| format!("The font {} is not installed.", font_name))?;
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+
+        if let Some(&i) = self.indices.get(&name) {
+            return Ok(i);
+        }
+
+        let data = std::fs::read(&path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        let font = TrueType::parse(data).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        self.fonts.push(PdfFont {
+            name: name.clone(),
+            font,
+            glyphs: BTreeMap::new(),
+        });
+        self.indices.insert(name, self.fonts.len() - 1);
+
+        Ok(self.fonts.len() - 1)
+    }
+
+    // The width of text at a point size, in pixels
+    fn width(&self, font: usize, text: &str, point: f32) -> f32 {
+        let font = &self.fonts[font].font;
+        font.text_width(text) as f32 * point / font.units_per_em as f32
+    }
+
+    // Text as a hex string of glyph ids, remembering the glyphs so they are embedded
+    fn encode(&mut self, font: usize, text: &str) -> String {
+        let font = &mut self.fonts[font];
+        let mut hex = String::new();
+        for c in text.chars() {
+            let glyph = font.font.glyph(c);
+            font.glyphs.entry(glyph).or_insert(c);
+            write!(hex, "{:04X}", glyph).unwrap();
+        }
+
+        hex
+    }
+}
+
+// An image file in a form that PDF viewers can decode themselves
+struct PdfImage {
+    dictionary: String, // Entries describing the image data, other than its length
+    data: Vec<u8>,
+    pixels: (u32, u32),
+    alpha: Option<Vec<u8>>, // Compressed 8-bit soft mask, for images that aren't opaque
+}
+
+#[derive(Default)]
+struct Images {
+    images: Vec<PdfImage>,
+    indices: HashMap<(String, Rc<[u8]>), Option<usize>>, // None for files that can't be embedded
+}
+
+impl Images {
+    // Reads an image the first time it is used, from its file for documents that don't hold it
+    // JPEGs and most PNGs are copied as they are, and other PNGs are decoded and compressed again
+    fn get(&mut self, data: &ImageData) -> Option<usize> {
+        let key = (data.path.clone(), Rc::clone(&data.data));
+        if let Some(&i) = self.indices.get(&key) {
+            return i;
+        }
+
+        let file = match data.data.is_empty() {
+            true => std::fs::read(&data.path).ok(),
+            false => Some(data.data.to_vec()),
+        };
+        let image = file.and_then(|file| {
+            read_png(&file)
+                .or_else(|| decode_png(&file))
+                .or_else(|| read_jpeg(file))
+        });
+        let i = image.map(|image| {
+            self.images.push(image);
+            self.images.len() - 1
+        });
+        self.indices.insert(key, i);
+
+        i
+    }
+}
+
+// 8-bit grayscale or RGB PNGs without interlacing, which PDF's Flate filter can read directly
+fn read_png(data: &[u8]) -> Option<PdfImage> {
+    if !data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return None;
+    }
+    let u32_at = |i: usize| Some(u32::from_be_bytes(data.get(i..i + 4)?.try_into().ok()?));
+
+    let mut header = None;
+    let mut idat = Vec::new();
+    let mut i = 8;
+    while i + 8 <= data.len() {
+        let length = u32_at(i)? as usize;
+        let chunk = data.get(i + 8..i + 8 + length)?;
+        match &data[i + 4..i + 8] {
+            b"IHDR" if length >= 13 => header = Some(chunk),
+            b"IDAT" => idat.extend_from_slice(chunk),
+            b"IEND" => break,
+            _ => (),
+        }
+        i += 12 + length;
+    }
+
+    let header = header?;
+    let width = u32::from_be_bytes(header[0..4].try_into().ok()?);
+    let height = u32::from_be_bytes(header[4..8].try_into().ok()?);
+    let (bit_depth, color_type, interlace) = (header[8], header[9], header[12]);
+    let (colors, space) = match color_type {
+        0 => (1, "DeviceGray"),
+        2 => (3, "DeviceRGB"),
+        _ => return None,
+    };
+    if bit_depth != 8 || interlace != 0 || idat.is_empty() {
+        return None;
+    }
+
+    Some(PdfImage {
+        dictionary: format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /{} \
+             /BitsPerComponent 8 /Filter /FlateDecode \
+             /DecodeParms << /Predictor 15 /Colors {} /BitsPerComponent 8 /Columns {} >> ",
+            width, height, space, colors, width
+        ),
+        data: idat,
+        pixels: (width, height),
+        alpha: None,
+    })
+}
+
+// Any other PNG, such as one with transparency, a palette or 16-bit colors, decoded by SDL_image
+// Its colors and its alpha channel are compressed again as separate images, with the alpha as
+// the colors' soft mask
+fn decode_png(data: &[u8]) -> Option<PdfImage> {
+    if !data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return None;
+    }
+    let surface = RWops::from_bytes(data)
+        .and_then(|rwops| rwops.load())
+        .ok()?;
+    let surface = surface.convert_format(PixelFormatEnum::RGBA32).ok()?;
+    let (width, height) = surface.size();
+    let pitch = surface.pitch() as usize;
+
+    let mut colors = Vec::with_capacity((width * height * 3) as usize);
+    let mut alpha = Vec::with_capacity((width * height) as usize);
+    surface.with_lock(|pixels| {
+        for row in pixels.chunks(pitch).take(height as usize) {
+            for pixel in row[..width as usize * 4].chunks(4) {
+                colors.extend_from_slice(&pixel[..3]);
+                alpha.push(pixel[3]);
+            }
+        }
+    });
+    let opaque = alpha.iter().all(|a| *a == 255);
+
+    Some(PdfImage {
+        dictionary: format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB \
+             /BitsPerComponent 8 /Filter /FlateDecode ",
+            width, height
+        ),
+        data: zlib::compress(&colors),
+        pixels: (width, height),
+        alpha: (!opaque).then(|| zlib::compress(&alpha)),
+    })
+}
+
+// Grayscale or RGB JPEGs, which are embedded whole
+fn read_jpeg(data: Vec<u8>) -> Option<PdfImage> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+
+    // Skip through the segments to the frame header, which holds the image's size
+    let mut i = 2;
+    while i + 4 <= data.len() {
+        if data[i] != 0xFF {
+            return None;
+        }
+        let marker = data[i + 1];
+        let length = u16::from_be_bytes([data[i + 2], data[i + 3]]) as usize;
+        let is_frame = matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+        if is_frame {
+            let frame = data.get(i + 4..i + 10)?;
+            let height = u16::from_be_bytes([frame[1], frame[2]]) as u32;
+            let width = u16::from_be_bytes([frame[3], frame[4]]) as u32;
+            let space = match frame[5] {
+                1 => "DeviceGray",
+                3 => "DeviceRGB",
+                _ => return None,
+            };
+
+            return Some(PdfImage {
+                dictionary: format!(
+                    "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /{} \
+                     /BitsPerComponent 8 /Filter /DCTDecode ",
+                    width, height, space
+                ),
+                alpha: None,
+                data,
+                pixels: (width, height),
+            });
+        }
+        i += 2 + length;
+    }
+
+    None
+}
+
+// Draws the marks of a page as PDF content, in pixels from the page's top-left corner
+struct Content<'a, 'd> {
+    out: String,
+    fonts: &'a mut Fonts<'d>,
+    images: &'a mut Images,
+    height: f32, // Of the page, since PDF measures y up from the bottom
+}
+
+impl Content<'_, '_> {
+    fn color(color: Color) -> String {
+        format!(
+            "{:.3} {:.3} {:.3}",
+            color.r as f32 / 255.0,
+            color.g as f32 / 255.0,
+            color.b as f32 / 255.0
+        )
+    }
+
+    fn fill_rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        writeln!(
+            self.out,
+            "{} rg {} {} {} {} re f",
+            Content::color(color),
+            x,
+            self.height - y - h,
+            w,
+            h
+        )
+        .unwrap();
+    }
+
+    fn line(&mut self, from: (f32, f32), to: (f32, f32), thickness: f32, color: Color) {
+        writeln!(
+            self.out,
+            "{} RG {} w {} {} m {} {} l S",
+            Content::color(color),
+            thickness,
+            from.0,
+            self.height - from.1,
+            to.0,
+            self.height - to.1
+        )
+        .unwrap();
+    }
+
+    // Writes text with its baseline starting at (x, y)
+    fn text(&mut self, font: usize, text: &str, x: f32, y: f32, point: f32, color: Color) {
+        if text.is_empty() {
+            return;
+        }
+        let hex = self.fonts.encode(font, text);
+        writeln!(
+            self.out,
+            "BT /F{} {} Tf {} rg {} {} Td <{}> Tj ET",
+            font,
+            point,
+            Content::color(color),
+            x,
+            self.height - y,
+            hex
+        )
+        .unwrap();
+    }
+
+    // Top-left corner of a square, in pixels
+    fn square(square: (u32, u32)) -> (f32, f32) {
+        (
+            (square.0 * SQUARE_SIZE) as f32,
+            (square.1 * SQUARE_SIZE) as f32,
+        )
+    }
+
+    // Text is drawn with its baseline one pixel above the bottom of its line, which takes up more
+    // than one row at larger point sizes
+    fn baseline(row_top: f32, point: u16) -> f32 {
+        row_top + (line_rows(point) * SQUARE_SIZE - 1) as f32
+    }
+
+    // Writes a line of a text box or bullet item with its baseline starting at (x, y), one run of
+    // the same style after another
+    fn styled_line(
+        &mut self,
+        (font_name, point): (&str, f32),
+        default: TextStyle,
+        line: &str,
+        spans: &[StyleSpan],
+        (mut x, baseline): (f32, f32),
+    ) -> Result<(), String> {
+        for (start, end, style) in textbox::line_runs(line.len(), spans, default) {
+            let font = self.fonts.get(font_name, style.font_style)?;
+            let text = &line[start..end];
+            self.text(font, text, x, baseline, point, style.color);
+            x += self.fonts.width(font, text, point);
+        }
+
+        Ok(())
+    }
+
+    fn text_box(&mut self, data: &TextData) -> Result<(), String> {
+        let (x, y) = Content::square(data.square);
+        let default = TextStyle {
+            font_style: data.font_style,
+            color: data.color,
+        };
+
+        for (i, line) in data.lines.iter().enumerate() {
+            let row = y + (i as u32 * line_rows(data.point) * SQUARE_SIZE) as f32;
+            self.styled_line(
+                (&data.font_name, data.point as f32),
+                default,
+                line,
+                data.spans.get(i).map_or(&[], Vec::as_slice),
+                (x, Content::baseline(row, data.point)),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn bullets(&mut self, data: &BulletData) -> Result<(), String> {
+        let font = self.fonts.get(&data.font_name, data.font_style)?;
+        let (x, y) = Content::square(data.square);
+        let markers = data.kind.markers(data.items.iter().map(|item| item.level));
+        let default = TextStyle {
+            font_style: data.font_style,
+            color: data.color,
+        };
+
+        let line_height = line_rows(data.point) * SQUARE_SIZE;
+        let mut row = 0;
+        for (item, marker) in data.items.iter().zip(markers) {
+            let marker_x = x + (item.level * SQUARE_SIZE) as f32;
+            let top = y + (row * line_height) as f32;
+            self.text(
+                font,
+                &marker,
+                marker_x,
+                Content::baseline(top, data.point),
+                data.point as f32,
+                data.color,
+            );
+
+            for (i, line) in item.lines.iter().enumerate() {
+                let top = top + (i as u32 * line_height) as f32;
+                self.styled_line(
+                    (&data.font_name, data.point as f32),
+                    default,
+                    line,
+                    item.spans.get(i).map_or(&[], Vec::as_slice),
+                    (
+                        marker_x + SQUARE_SIZE as f32,
+                        Content::baseline(top, data.point),
+                    ),
+                )?;
+            }
+            row += item.lines.len().max(1) as u32;
+        }
+
+        Ok(())
+    }
+
+    // Lays the formula out the same way as MathBox, measuring with the font files themselves
+    fn math(&mut self, data: &MathData) -> Result<(), String> {
+        if data.source.is_empty() {
+            return Ok(());
+        }
+        let upright = self.fonts.get(&data.font_name, FontStyle::NORMAL)?;
+        let italic = self.fonts.get(&data.font_name, FontStyle::ITALIC)?;
+        let point = data.point as f32;
+
+        let parsed = parser::parse(&data.source);
+        let color = if parsed.is_ok() {
+            data.color
+        } else {
+            Color::GRAY
+        };
+        let node =
+            parsed.unwrap_or_else(|_| Node::Symbol(data.source.clone(), SymbolKind::Upright));
+
+        let math_layout = {
+            let fonts = &*self.fonts;
+            let font = &fonts.fonts[upright].font;
+            let scale = point / font.units_per_em as f32;
+            let advance = |text: &str, is_italic: bool| {
+                fonts.width(if is_italic { italic } else { upright }, text, point)
+            };
+
+            layout::layout(
+                &node,
+                &Metrics {
+                    em: point,
+                    ascent: (font.ascent as f32 * scale).ceil(),
+                    descent: (-font.descent as f32 * scale).ceil(),
+                    advance: &advance,
+                },
+            )
+        };
+
+        let (x, y) = Content::square(data.square);
+        let rows_above = ((math_layout.ascent / SQUARE_SIZE as f32).ceil() as u32).max(1);
+        let baseline = (x, y + (rows_above * SQUARE_SIZE) as f32 - 1.0);
+
+        for item in &math_layout.items {
+            match item {
+                Item::Glyph {
+                    text,
+                    italic: is_italic,
+                    x,
+                    y,
+                    scale,
+                } => self.text(
+                    if *is_italic { italic } else { upright },
+                    text,
+                    baseline.0 + x,
+                    baseline.1 + y,
+                    point * scale,
+                    color,
+                ),
+                Item::Line {
+                    from,
+                    to,
+                    thickness,
+                } => self.line(
+                    (baseline.0 + from.0, baseline.1 + from.1),
+                    (baseline.0 + to.0, baseline.1 + to.1),
+                    *thickness,
+                    color,
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    // One character per square, each centered in its square like CodeBlock does
+    fn code(&mut self, data: &CodeData) -> Result<(), String> {
+        let font = self.fonts.get(&data.font_name, FontStyle::NORMAL)?;
+        let language = highlight::language(&data.language)
+            .ok_or_else(|| format!("Unknown language '{}'.", data.language))?;
+        let columns = (data.max_width / SQUARE_SIZE).max(1);
+        let (x, y) = Content::square(data.square);
+
+        // The background is see-through, so the grid still shows under it
+        let rows = data.lines.len().max(1) as u32;
+        self.out.push_str("q /Tint gs\n");
+        self.fill_rect(
+            x,
+            y,
+            (columns * SQUARE_SIZE - 1) as f32,
+            (rows * SQUARE_SIZE - 1) as f32,
+            codeblock::BACKGROUND,
+        );
+        self.out.push_str("Q\n");
+
+        let lines = highlight::cells(language, &data.lines, codeblock::TAB_WIDTH);
+        for (i, cells) in lines.iter().enumerate() {
+            let baseline = Content::baseline(y + (i as u32 * SQUARE_SIZE) as f32, data.point);
+            for (column, (c, kind)) in cells.iter().take(columns as usize).enumerate() {
+                if c.is_whitespace() {
+                    continue;
+                }
+                let text = c.to_string();
+                let width = self.fonts.width(font, &text, data.point as f32);
+                let cell = x + (column as u32 * SQUARE_SIZE) as f32;
+                self.text(
+                    font,
+                    &text,
+                    cell + ((SQUARE_SIZE as f32 - width) / 2.0).floor(),
+                    baseline,
+                    data.point as f32,
+                    CodeBlock::color(*kind),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    // Images are scaled to fit inside their squares, as they are on screen
+    // Images that can't be embedded are drawn as a crossed-out box instead
+    fn image(&mut self, data: &ImageData) {
+        let (x, y) = Content::square(data.square);
+        let width = (data.size.0 * SQUARE_SIZE - 1) as f32;
+        let height = (data.size.1 * SQUARE_SIZE - 1) as f32;
+
+        let i = match self.images.get(data) {
+            Some(i) => i,
+            None => {
+                let gray = Color::RGB(160, 160, 160);
+                let (right, bottom) = (x + width, y + height);
+                self.line((x, y), (right, y), 1.0, gray);
+                self.line((right, y), (right, bottom), 1.0, gray);
+                self.line((right, bottom), (x, bottom), 1.0, gray);
+                self.line((x, bottom), (x, y), 1.0, gray);
+                self.line((x, y), (right, bottom), 1.0, gray);
+                self.line((right, y), (x, bottom), 1.0, gray);
+                return;
+            }
+        };
+
+        let (w, h) = self.images.images[i].pixels;
+        let scale = (width / w as f32).min(height / h as f32);
+        let (w, h) = (w as f32 * scale, h as f32 * scale);
+        writeln!(
+            self.out,
+            "q {} 0 0 {} {} {} cm /Im{} Do Q",
+            w,
+            h,
+            x,
+            self.height - y - h,
+            i
+        )
+        .unwrap();
+    }
+
+    // Corners sit on the grid lines, which are the last pixel of each square
+    fn stroke(&mut self, data: &LineData) {
+        let corner = |c: (u32, u32)| {
+            (
+                (c.0 * SQUARE_SIZE) as f32 - 0.5,
+                (c.1 * SQUARE_SIZE) as f32 - 0.5,
+            )
+        };
+        self.line(
+            corner(data.start),
+            corner(data.end),
+            data.thickness as f32,
+            data.color,
+        );
+    }
+}
+
+// Writes a PDF with one page for each page of the document
+// Text is written as text in subsets of the fonts it uses, so it can be selected and searched
+pub fn export(document: &Document, path: &Path) -> Result<(), String> {
+    let width = (document.page_squares.0 * SQUARE_SIZE - 1) as f32;
+    let height = (document.page_squares.1 * SQUARE_SIZE - 1) as f32;
+    // Points per pixel
+    let scale = SQUARE_MM / 25.4 * 72.0 / SQUARE_SIZE as f32;
+
+    let mut fonts = Fonts {
+        embedded: &document.fonts,
+        ..Fonts::default()
+    };
+    let mut images = Images::default();
+    let mut contents = Vec::new();
+    for page in 0..document.pages {
+        let mut content = Content {
+            out: format!("{} 0 0 {} 0 0 cm\n", scale, scale),
+            fonts: &mut fonts,
+            images: &mut images,
+            height,
+        };
+
+        let style = document.style;
+        content.fill_rect(0.0, 0.0, width, height, style.background());
+        if let Some(grid) = style.grid() {
+            for x in 1..document.page_squares.0 {
+                content.fill_rect((x * SQUARE_SIZE - 1) as f32, 0.0, 1.0, height, grid);
+            }
+            for y in 1..document.page_squares.1 {
+                content.fill_rect(0.0, (y * SQUARE_SIZE - 1) as f32, width, 1.0, grid);
+            }
+        }
+
+        for mark in document.marks.iter().filter(|m| m.page() == page) {
+            match mark {
+                MarkData::Text(data) => content.text_box(data)?,
+                MarkData::Line(data) => content.stroke(data),
+                MarkData::Bullets(data) => content.bullets(data)?,
+                MarkData::Math(data) => content.math(data)?,
+                MarkData::Code(data) => content.code(data)?,
+                MarkData::Image(data) => content.image(data),
+            }
+        }
+
+        contents.push(content.out);
+    }
+
+    let mut pdf = PdfWriter::default();
+    // Objects 1 and 2 are the catalog and page tree, which refer to objects written after them
+    pdf.reserve(2);
+    pdf.add(b"<< /Producer (SquarePad) >>".to_vec());
+
+    let mut font_resources = String::new();
+    for (i, font) in fonts.fonts.iter().enumerate() {
+        let id = pdf.add_font(font, i)?;
+        write!(font_resources, "/F{} {} 0 R ", i, id).unwrap();
+    }
+    let mut image_resources = String::new();
+    for (i, image) in images.images.iter().enumerate() {
+        let mut dictionary = image.dictionary.clone();
+        if let Some(alpha) = &image.alpha {
+            let mask = pdf.add_stream(
+                &format!(
+                    "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceGray \
+                     /BitsPerComponent 8 /Filter /FlateDecode ",
+                    image.pixels.0, image.pixels.1
+                ),
+                alpha,
+            );
+            write!(dictionary, "/SMask {} 0 R ", mask).unwrap();
+        }
+        let id = pdf.add_stream(&dictionary, &image.data);
+        write!(image_resources, "/Im{} {} 0 R ", i, id).unwrap();
+    }
+    let resources = format!(
+        "<< /Font << {}>> /XObject << {}>> /ExtGState << /Tint << /ca {:.3} >> >> >>",
+        font_resources,
+        image_resources,
+        codeblock::BACKGROUND.a as f32 / 255.0
+    );
+
+    let mut kids = String::new();
+    for content in contents {
+        let content = pdf.add_stream("", content.as_bytes());
+        let page = pdf.add(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] /Resources {} /Contents {} 0 R >>",
+                width * scale,
+                height * scale,
+                resources,
+                content
+            )
+            .into_bytes(),
+        );
+        write!(kids, "{} 0 R ", page).unwrap();
+    }
+
+    pdf.set(1, b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+    pdf.set(
+        2,
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids, document.pages
+        )
+        .into_bytes(),
+    );
+
+    std::fs::write(path, pdf.finish(3))
+        .map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+// Numbers objects and keeps track of where they are for the cross-reference table
+#[derive(Default)]
+struct PdfWriter {
+    objects: Vec<Vec<u8>>,
+}
+
+impl PdfWriter {
+    fn reserve(&mut self, count: usize) {
+        self.objects.resize(self.objects.len() + count, Vec::new());
+    }
+
+    fn set(&mut self, id: usize, object: Vec<u8>) {
+        self.objects[id - 1] = object;
+    }
+
+    // Returns the new object's number
+    fn add(&mut self, object: Vec<u8>) -> usize {
+        self.objects.push(object);
+        self.objects.len()
+    }
+
+    fn add_stream(&mut self, dictionary: &str, data: &[u8]) -> usize {
+        let mut object =
+            format!("<< /Length {} {}>>\nstream\n", data.len(), dictionary).into_bytes();
+        object.extend_from_slice(data);
+        object.extend_from_slice(b"\nendstream");
+        self.add(object)
+    }
+
+    // Embeds a font as a CID font, addressing glyphs directly by their ids
+    fn add_font(&mut self, font: &PdfFont, index: usize) -> Result<usize, String> {
+        let ttf = &font.font;
+        let units = |v: i32| v * 1000 / ttf.units_per_em as i32;
+
+        // Subsets are named with six capital letters in front of the font's own name
+        let mut tag = String::from("SQP");
+        let mut n = index;
+        for _ in 0..3 {
+            tag.push((b'A' + (n % 26) as u8) as char);
+            n /= 26;
+        }
+        let name = format!("{}+{}", tag, font.name);
+
+        let glyphs: BTreeSet<u16> = font.glyphs.keys().copied().collect();
+        let subset = ttf.subset(&glyphs)?;
+        let file = self.add_stream(&format!("/Length1 {} ", subset.len()), &subset);
+
+        let italic = ttf.italic_angle != 0.0;
+        let descriptor = self.add(
+            format!(
+                "<< /Type /FontDescriptor /FontName /{} /Flags {} /FontBBox [{} {} {} {}] \
+                 /ItalicAngle {} /Ascent {} /Descent {} /CapHeight {} /StemV 80 /FontFile2 {} 0 R >>",
+                name,
+                if italic { 4 + 64 } else { 4 },
+                units(ttf.bbox[0] as i32),
+                units(ttf.bbox[1] as i32),
+                units(ttf.bbox[2] as i32),
+                units(ttf.bbox[3] as i32),
+                ttf.italic_angle,
+                units(ttf.ascent as i32),
+                units(ttf.descent as i32),
+                units(ttf.cap_height as i32),
+                file
+            )
+            .into_bytes(),
+        );
+
+        let mut widths = String::new();
+        for glyph in &glyphs {
+            write!(widths, "{} [{}] ", glyph, units(ttf.advance(*glyph) as i32)).unwrap();
+        }
+        let cid_font = self.add(
+            format!(
+                "<< /Type /Font /Subtype /CIDFontType2 /BaseFont /{} \
+                 /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> \
+                 /FontDescriptor {} 0 R /W [{}] /CIDToGIDMap /Identity >>",
+                name, descriptor, widths
+            )
+            .into_bytes(),
+        );
+
+        let to_unicode = self.add_stream("", to_unicode(&font.glyphs).as_bytes());
+
+        Ok(self.add(
+            format!(
+                "<< /Type /Font /Subtype /Type0 /BaseFont /{} /Encoding /Identity-H \
+                 /DescendantFonts [{} 0 R] /ToUnicode {} 0 R >>",
+                name, cid_font, to_unicode
+            )
+            .into_bytes(),
+        ))
+    }
+
+    fn finish(self, info: usize) -> Vec<u8> {
+        let mut out = b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n".to_vec();
+
+        let mut offsets = Vec::new();
+        for (i, object) in self.objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+            out.extend_from_slice(object);
+            out.extend_from_slice(b"\nendobj\n");
+        }
+
+        let xref = out.len();
+        out.extend_from_slice(
+            format!("xref\n0 {}\n0000000000 65535 f \n", self.objects.len() + 1).as_bytes(),
+        );
+        for offset in offsets {
+            out.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        out.extend_from_slice(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+                self.objects.len() + 1,
+                info,
+                xref
+            )
+            .as_bytes(),
+        );
+
+        out
+    }
+}
+
+// Maps glyphs back to the characters they were used for, which is what makes text copyable
+fn to_unicode(glyphs: &BTreeMap<u16, char>) -> String {
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+    );
+
+    let glyphs: Vec<(&u16, &char)> = glyphs.iter().collect();
+    // Each block can hold at most 100 entries
+    for chunk in glyphs.chunks(100) {
+        writeln!(cmap, "{} beginbfchar", chunk.len()).unwrap();
+        for (glyph, c) in chunk {
+            let mut utf16 = [0u16; 2];
+            let hex: String = c
+                .encode_utf16(&mut utf16)
+                .iter()
+                .map(|unit| format!("{:04X}", unit))
+                .collect();
+            writeln!(cmap, "<{:04X}> <{}>", glyph, hex).unwrap();
+        }
+        cmap.push_str("endbfchar\n");
+    }
+
+    cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+    cmap
+}
@@ -0,0 +1,75 @@
+use crate::app::pages::Pages;
+use crate::config::Config;
+use crate::document::Document;
+use crate::drawable::Drawable;
+use crate::editor::Editor;
+use crate::fonts::FontManager;
+use crate::renderer::Renderer;
+
+use std::path::{Path, PathBuf};
+
+use sdl2::rect::Rect;
+use sdl2::ttf::Sdl2TtfContext;
+use sdl2::VideoSubsystem;
+
+// Renders every page of a document at its native resolution and writes each one to
+// <out_dir>/<name>-<page number>.png, returning the paths written
+// Pages are drawn to a hidden window with a software renderer, so this also works with SDL's
+// dummy video driver when there is no display
+pub fn export(
+    video: &VideoSubsystem,
+    ttf: &Sdl2TtfContext,
+    document: &Document,
+    out_dir: &Path,
+    name: &str,
+) -> Result<Vec<PathBuf>, String> {
+    let square_size = crate::app::pages::SQUARE_SIZE;
+    let (width, height) = (
+        document.page_squares.0 * square_size - 1,
+        document.page_squares.1 * square_size - 1,
+    );
+
+    let window = video
+        .window("SquarePad export", width, height)
+        .hidden()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut canvas = window
+        .into_canvas()
+        .software()
+        .build()
+        .map_err(|e| e.to_string())?;
+    canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
+    let tex_creator = canvas.texture_creator();
+
+    let mut renderer = Renderer::new(
+        &mut canvas,
+        &tex_creator,
+        FontManager::new(ttf, &Config::load()?)?,
+    );
+
+    // The marks are rebuilt by an editor of their own, the same way as when opening a file
+    let pages = Pages::new(document.page_squares, &mut renderer)?;
+    let mut editor = Editor::new(pages, video.text_input(), video.clipboard());
+    editor.load_document(document, &mut renderer)?;
+
+    std::fs::create_dir_all(out_dir)
+        .map_err(|e| format!("Could not create {}: {}", out_dir.display(), e))?;
+
+    let mut paths = Vec::new();
+    for page in 0..document.pages {
+        // Point the camera at exactly one page
+        let position = editor.get_pages().get_page_position(page);
+        renderer.set_camera(Rect::new(position.x(), position.y(), width, height));
+
+        renderer.clear();
+        editor.get_pages().draw(&mut renderer)?;
+        editor.draw_marks(&mut renderer)?;
+
+        let path = out_dir.join(format!("{}-{}.png", name, page + 1));
+        renderer.save_png(&path)?;
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
@@ -0,0 +1,454 @@
+// Just enough of the TrueType format to measure text and embed subsets of fonts in PDFs
+// Only the tables that PDF viewers need are kept in a subset, and unused glyphs are emptied
+// rather than removed so that glyph ids stay the same
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+pub struct TrueType {
+    data: Vec<u8>,
+    tables: HashMap<[u8; 4], (usize, usize)>, // Offset and length of each table
+    cmap: Option<(usize, u16)>,               // Offset and format of the Unicode character map
+    loca: Vec<usize>,                         // Offsets of each glyph into glyf, plus its end
+    advances: Vec<u16>,
+    pub units_per_em: u16,
+    pub ascent: i16,
+    pub descent: i16,
+    pub cap_height: i16,
+    pub bbox: [i16; 4],
+    pub italic_angle: f32,
+    pub family: String, // Faces of a family are told apart by being bold or italic
+    pub bold: bool,
+    pub italic: bool,
+}
+
+impl TrueType {
+    pub fn parse(data: Vec<u8>) -> Result<TrueType, String> {
+        let mut font = TrueType {
+            data,
+            tables: HashMap::new(),
+            cmap: None,
+            loca: Vec::new(),
+            advances: Vec::new(),
+            units_per_em: 1000,
+            ascent: 0,
+            descent: 0,
+            cap_height: 0,
+            bbox: [0; 4],
+            italic_angle: 0.0,
+            family: String::new(),
+            bold: false,
+            italic: false,
+        };
+
+        let table_count = font.u16(4)? as usize;
+        for i in 0..table_count {
+            let record = 12 + i * 16;
+            let tag = font.bytes(record, 4)?;
+            let tag = [tag[0], tag[1], tag[2], tag[3]];
+            let offset = font.u32(record + 8)? as usize;
+            let length = font.u32(record + 12)? as usize;
+            font.bytes(offset, length)?;
+            font.tables.insert(tag, (offset, length));
+        }
+
+        let head = font.table(b"head")?;
+        font.units_per_em = font.u16(head + 18)?;
+        for i in 0..4 {
+            font.bbox[i] = font.u16(head + 36 + 2 * i)? as i16;
+        }
+        let long_offsets = font.u16(head + 50)? == 1;
+
+        let hhea = font.table(b"hhea")?;
+        font.ascent = font.u16(hhea + 4)? as i16;
+        font.descent = font.u16(hhea + 6)? as i16;
+        font.cap_height = font.ascent;
+        let metric_count = font.u16(hhea + 34)? as usize;
+
+        let glyph_count = font.u16(font.table(b"maxp")? + 4)? as usize;
+
+        if let Ok(post) = font.table(b"post") {
+            font.italic_angle = font.u32(post + 4)? as i32 as f32 / 65536.0;
+        }
+        let family = font.table_bytes(b"name").and_then(family_name);
+        font.family = family.unwrap_or_default();
+        let (bold, italic) = style(font.table_bytes(b"head")?, font.table_bytes(b"OS/2").ok())?;
+        font.bold = bold;
+        font.italic = italic;
+        if let Ok(os2) = font.table(b"OS/2") {
+            if font.u16(os2)? >= 2 {
+                font.cap_height = font.u16(os2 + 88)? as i16;
+            }
+        }
+
+        let hmtx = font.table(b"hmtx")?;
+        for i in 0..glyph_count {
+            // Glyphs past the last metric share its advance
+            let advance = font.u16(hmtx + 4 * i.min(metric_count.max(1) - 1))?;
+            font.advances.push(advance);
+        }
+
+        let loca = font.table(b"loca")?;
+        for i in 0..=glyph_count {
+            let offset = if long_offsets {
+                font.u32(loca + 4 * i)? as usize
+            } else {
+                font.u16(loca + 2 * i)? as usize * 2
+            };
+            font.loca.push(offset);
+        }
+
+        // Prefer the full Unicode map, then the Basic Multilingual Plane one
+        let cmap = font.table(b"cmap")?;
+        let mut best: Option<(usize, u16)> = None;
+        for i in 0..font.u16(cmap + 2)? as usize {
+            let record = cmap + 4 + i * 8;
+            let platform = font.u16(record)?;
+            let encoding = font.u16(record + 2)?;
+            let offset = cmap + font.u32(record + 4)? as usize;
+            let format = font.u16(offset)?;
+
+            let unicode = platform == 0 || (platform == 3 && (encoding == 1 || encoding == 10));
+            if unicode && (format == 12 || (format == 4 && best.is_none())) {
+                best = Some((offset, format));
+            }
+        }
+        font.cmap = best;
+
+        Ok(font)
+    }
+
+    fn bytes(&self, offset: usize, length: usize) -> Result<&[u8], String> {
+        self.data
+            .get(offset..offset + length)
+            .ok_or_else(|| "Font file is truncated.".to_string())
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, String> {
+        let b = self.bytes(offset, 2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, String> {
+        let b = self.bytes(offset, 4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn table(&self, tag: &[u8; 4]) -> Result<usize, String> {
+        self.tables.get(tag).map(|t| t.0).ok_or_else(|| {
+            format!(
+                "Font is missing its {} table.",
+                String::from_utf8_lossy(tag).trim()
+            )
+        })
+    }
+
+    fn table_bytes(&self, tag: &[u8; 4]) -> Result<&[u8], String> {
+        let offset = self.table(tag)?;
+        self.bytes(offset, self.tables[tag].1)
+    }
+
+    // The glyph for a character, or 0 (the missing glyph) if the font doesn't have one
+    pub fn glyph(&self, c: char) -> u16 {
+        self.lookup(c as u32).unwrap_or(0)
+    }
+
+    fn lookup(&self, c: u32) -> Result<u16, String> {
+        let (offset, format) = match self.cmap {
+            Some(cmap) => cmap,
+            None => return Ok(0),
+        };
+
+        if format == 12 {
+            for i in 0..self.u32(offset + 12)? as usize {
+                let group = offset + 16 + i * 12;
+                let start = self.u32(group)?;
+                if c >= start && c <= self.u32(group + 4)? {
+                    return Ok((self.u32(group + 8)? + c - start) as u16);
+                }
+            }
+            return Ok(0);
+        }
+
+        if c > 0xFFFF {
+            return Ok(0);
+        }
+        let segments = self.u16(offset + 6)? as usize / 2;
+        let ends = offset + 14;
+        let starts = ends + segments * 2 + 2;
+        let deltas = starts + segments * 2;
+        let range_offsets = deltas + segments * 2;
+
+        for i in 0..segments {
+            if c > self.u16(ends + i * 2)? as u32 {
+                continue;
+            }
+            let start = self.u16(starts + i * 2)? as u32;
+            if c < start {
+                return Ok(0);
+            }
+
+            let delta = self.u16(deltas + i * 2)?;
+            let range_offset = self.u16(range_offsets + i * 2)? as usize;
+            if range_offset == 0 {
+                return Ok((c as u16).wrapping_add(delta));
+            }
+
+            let glyph =
+                self.u16(range_offsets + i * 2 + range_offset + 2 * (c - start) as usize)?;
+            return Ok(if glyph == 0 {
+                0
+            } else {
+                glyph.wrapping_add(delta)
+            });
+        }
+
+        Ok(0)
+    }
+
+    // In font units
+    pub fn advance(&self, glyph: u16) -> u16 {
+        self.advances.get(glyph as usize).copied().unwrap_or(0)
+    }
+
+    // The width of some text in font units, without kerning
+    pub fn text_width(&self, text: &str) -> u32 {
+        text.chars()
+            .map(|c| self.advance(self.glyph(c)) as u32)
+            .sum()
+    }
+
+    fn glyph_data(&self, glyph: u16) -> Result<&[u8], String> {
+        let glyf = self.table(b"glyf")?;
+        let start = self.loca[glyph as usize];
+        let end = self.loca[glyph as usize + 1].max(start);
+
+        self.bytes(glyf + start, end - start)
+    }
+
+    // Glyphs that a composite glyph is built out of
+    fn components(&self, glyph: u16) -> Result<Vec<u16>, String> {
+        let data = self.glyph_data(glyph)?;
+        let mut components = Vec::new();
+        if data.len() < 10 || (i16::from_be_bytes([data[0], data[1]]) >= 0) {
+            return Ok(components);
+        }
+
+        let mut i = 10;
+        while let Some(b) = data.get(i..i + 4) {
+            let flags = u16::from_be_bytes([b[0], b[1]]);
+            components.push(u16::from_be_bytes([b[2], b[3]]));
+
+            i += 4 + if flags & 0x1 != 0 { 4 } else { 2 };
+            if flags & 0x8 != 0 {
+                i += 2;
+            } else if flags & 0x40 != 0 {
+                i += 4;
+            } else if flags & 0x80 != 0 {
+                i += 8;
+            }
+            if flags & 0x20 == 0 {
+                break;
+            }
+        }
+
+        Ok(components)
+    }
+
+    // A copy of the font where every glyph that isn't listed (or used by a listed one) is empty
+    pub fn subset(&self, glyphs: &BTreeSet<u16>) -> Result<Vec<u8>, String> {
+        let glyph_count = self.advances.len();
+
+        let mut kept: BTreeSet<u16> = glyphs.clone();
+        kept.insert(0);
+        let mut pending: Vec<u16> = kept.iter().copied().collect();
+        while let Some(glyph) = pending.pop() {
+            if glyph as usize >= glyph_count {
+                continue;
+            }
+            for component in self.components(glyph)? {
+                if kept.insert(component) {
+                    pending.push(component);
+                }
+            }
+        }
+
+        let mut glyf = Vec::new();
+        let mut loca = Vec::new();
+        for glyph in 0..glyph_count {
+            loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+            if kept.contains(&(glyph as u16)) {
+                glyf.extend_from_slice(self.glyph_data(glyph as u16)?);
+                while glyf.len() % 4 != 0 {
+                    glyf.push(0);
+                }
+            }
+        }
+        loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+
+        // Offsets into loca are always written as 32 bits
+        let (head, head_length) = self.tables[b"head"];
+        let mut head = self.data[head..head + head_length].to_vec();
+        head[8..12].copy_from_slice(&[0; 4]);
+        head[50..52].copy_from_slice(&1u16.to_be_bytes());
+
+        let mut tables: Vec<([u8; 4], Vec<u8>)> =
+            vec![(*b"glyf", glyf), (*b"head", head), (*b"loca", loca)];
+        for tag in [b"cvt ", b"fpgm", b"hhea", b"hmtx", b"maxp", b"prep"] {
+            if let Some(&(offset, length)) = self.tables.get(tag) {
+                tables.push((*tag, self.data[offset..offset + length].to_vec()));
+            }
+        }
+        tables.sort_by_key(|t| t.0);
+
+        Ok(write_font(&tables))
+    }
+}
+
+// The family of a font file and whether it is bold and italic, reading only the tables that say
+// so rather than the whole file, which keeps looking through every installed font quick
+pub fn read_face(path: &Path) -> Result<(String, bool, bool), String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let mut read = |offset: usize, length: usize| -> Result<Vec<u8>, String> {
+        let mut data = vec![0; length];
+        file.seek(SeekFrom::Start(offset as u64))
+            .and_then(|_| file.read_exact(&mut data))
+            .map_err(|_| "Font file is truncated.".to_string())?;
+        Ok(data)
+    };
+
+    let header = read(0, 12)?;
+    if &header[..4] == b"ttcf" {
+        return Err("Font collections are not supported.".to_string());
+    }
+    let table_count = be16(&header, 4)? as usize;
+    let directory = read(12, table_count * 16)?;
+
+    let mut tables = HashMap::new();
+    for record in directory.chunks_exact(16) {
+        let tag = [record[0], record[1], record[2], record[3]];
+        if [b"head", b"name", b"OS/2"].contains(&&tag) {
+            let offset = u32::from_be_bytes([record[8], record[9], record[10], record[11]]);
+            let length = u32::from_be_bytes([record[12], record[13], record[14], record[15]]);
+            tables.insert(tag, read(offset as usize, length as usize)?);
+        }
+    }
+
+    let head = tables
+        .get(b"head")
+        .ok_or_else(|| "Font is missing its head table.".to_string())?;
+    let (bold, italic) = style(head, tables.get(b"OS/2").map(Vec::as_slice))?;
+    let family = match tables.get(b"name") {
+        Some(name) => family_name(name).unwrap_or_default(),
+        None => String::new(),
+    };
+
+    Ok((family, bold, italic))
+}
+
+fn be16(data: &[u8], offset: usize) -> Result<u16, String> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| "Font file is truncated.".to_string())
+}
+
+// The family name from the name table, preferring the typographic family, which groups more
+// than the four regular, bold and italic styles together
+fn family_name(name: &[u8]) -> Result<String, String> {
+    let count = be16(name, 2)? as usize;
+    let strings = be16(name, 4)? as usize;
+
+    for id in [16, 1] {
+        for i in 0..count {
+            let record = 6 + i * 12;
+            if be16(name, record + 6)? != id {
+                continue;
+            }
+            let length = be16(name, record + 8)? as usize;
+            let start = strings + be16(name, record + 10)? as usize;
+            let bytes = name
+                .get(start..start + length)
+                .ok_or_else(|| "Font file is truncated.".to_string())?;
+
+            // Unicode and Windows names are UTF-16, and Macintosh ones are Roman
+            match (be16(name, record)?, be16(name, record + 2)?) {
+                (0, _) | (3, _) => {
+                    let units: Vec<u16> = bytes
+                        .chunks_exact(2)
+                        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                        .collect();
+                    return Ok(String::from_utf16_lossy(&units));
+                }
+                (1, 0) => return Ok(bytes.iter().map(|&b| b as char).collect()),
+                _ => (),
+            }
+        }
+    }
+
+    Err("Font has no family name.".to_string())
+}
+
+// Whether a font is bold and italic, going by its OS/2 table when it has one, since the older
+// head table flags are sometimes left unset
+fn style(head: &[u8], os2: Option<&[u8]>) -> Result<(bool, bool), String> {
+    match os2 {
+        Some(os2) => {
+            let selection = be16(os2, 62)?;
+            Ok((selection & 0x20 != 0, selection & 1 != 0))
+        }
+        None => {
+            let mac_style = be16(head, 44)?;
+            Ok((mac_style & 1 != 0, mac_style & 2 != 0))
+        }
+    }
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+// Assembles a font file out of its tables, which must be sorted by tag
+fn write_font(tables: &[([u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let count = tables.len() as u16;
+    let entry_selector = 15 - count.leading_zeros() as u16;
+    let search_range = (1u16 << entry_selector) * 16;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&0x00010000u32.to_be_bytes());
+    out.extend_from_slice(&count.to_be_bytes());
+    out.extend_from_slice(&search_range.to_be_bytes());
+    out.extend_from_slice(&entry_selector.to_be_bytes());
+    out.extend_from_slice(&(count * 16 - search_range).to_be_bytes());
+
+    let mut offset = 12 + 16 * tables.len();
+    let mut head_offset = None;
+    for (tag, data) in tables {
+        if tag == b"head" {
+            head_offset = Some(offset);
+        }
+        out.extend_from_slice(tag);
+        out.extend_from_slice(&checksum(data).to_be_bytes());
+        out.extend_from_slice(&(offset as u32).to_be_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        offset += data.len().div_ceil(4) * 4;
+    }
+    for (_, data) in tables {
+        out.extend_from_slice(data);
+        while out.len() % 4 != 0 {
+            out.push(0);
+        }
+    }
+
+    if let Some(head) = head_offset {
+        let adjustment = 0xB1B0AFBAu32.wrapping_sub(checksum(&out));
+        out[head + 8..head + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+
+    out
+}
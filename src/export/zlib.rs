@@ -0,0 +1,187 @@
+// Compresses data in the zlib format read by the Flate filter of PDF streams
+// Repeats are found by remembering where each run of three bytes was last seen, and everything is
+// written in one block with deflate's fixed Huffman codes, which is simple and still small enough
+
+const WINDOW: usize = 32768; // Furthest back a repeat can be found
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Bits {
+        bytes: vec![0x78, 0x01],
+        current: 0,
+        count: 0,
+    };
+    // The only block, which is the last, using the fixed codes
+    out.write(1, 1);
+    out.write(1, 2);
+
+    let hash = |i: usize| {
+        let key = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+        (key.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+    };
+    let mut last_seen = vec![usize::MAX; 1 << HASH_BITS];
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut length = 0;
+        let mut distance = 0;
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            let candidate = last_seen[h];
+            last_seen[h] = i;
+            if candidate != usize::MAX && i - candidate <= WINDOW {
+                let limit = (data.len() - i).min(MAX_MATCH);
+                while length < limit && data[candidate + length] == data[i + length] {
+                    length += 1;
+                }
+                distance = i - candidate;
+            }
+        }
+
+        if length >= MIN_MATCH {
+            out.length(length);
+            out.distance(distance);
+            // The bytes inside the repeat can start later repeats too
+            for j in i + 1..(i + length).min(data.len().saturating_sub(MIN_MATCH - 1)) {
+                last_seen[hash(j)] = j;
+            }
+            i += length;
+        } else {
+            out.symbol(data[i] as u16);
+            i += 1;
+        }
+    }
+    out.symbol(256);
+
+    let mut bytes = out.finish();
+    bytes.extend_from_slice(&adler32(data).to_be_bytes());
+    bytes
+}
+
+// Deflate packs bits starting from the lowest bit of each byte
+struct Bits {
+    bytes: Vec<u8>,
+    current: u32,
+    count: u32,
+}
+
+impl Bits {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.current |= value << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.bytes.push(self.current as u8);
+            self.current >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes are written starting from their highest bit
+    fn code(&mut self, code: u32, bits: u32) {
+        let reversed = code.reverse_bits() >> (32 - bits);
+        self.write(reversed, bits)
+    }
+
+    // A literal byte, the end of the block, or the start of a length
+    fn symbol(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn length(&mut self, length: usize) {
+        let i = LENGTH_BASES
+            .iter()
+            .rposition(|base| *base as usize <= length)
+            .unwrap();
+        self.symbol(257 + i as u16);
+        self.write(
+            (length - LENGTH_BASES[i] as usize) as u32,
+            LENGTH_EXTRA[i] as u32,
+        );
+    }
+
+    fn distance(&mut self, distance: usize) {
+        let i = DISTANCE_BASES
+            .iter()
+            .rposition(|base| *base as usize <= distance)
+            .unwrap();
+        self.code(i as u32, 5);
+        self.write(
+            (distance - DISTANCE_BASES[i] as usize) as u32,
+            DISTANCE_EXTRA[i] as u32,
+        );
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.current as u8);
+        }
+        self.bytes
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_match_the_zlib_format() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn empty_data_is_a_block_with_only_its_end() {
+        assert_eq!(
+            compress(b""),
+            [0x78, 0x01, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01]
+        );
+    }
+
+    #[test]
+    fn repeats_are_written_as_references_back() {
+        let rows: Vec<u8> = (0..100).flat_map(|_| [255, 255, 255, 0, 0, 0]).collect();
+        let compressed = compress(&rows);
+        assert!(compressed.len() < 30, "{} bytes", compressed.len());
+        assert_eq!(
+            compressed[compressed.len() - 4..],
+            adler32(&rows).to_be_bytes()
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use crate::assets;
+use crate::config::{self, Config};
+use crate::document::{Document, FontData, MarkData};
+use crate::export::ttf;
+
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use sdl2::ttf::{Font, FontStyle, Sdl2TtfContext};
+
+// Used for families that aren't installed, and for characters a font has no glyph for
+pub const DEFAULT_FAMILY: &str = "NotoSerif";
+pub const FALLBACK_FAMILY: &str = "DejaVuSansMono";
+
+const CACHE_SIZE: usize = 32; // Most fonts kept open at once, each at one style and point size
+
+// A font file, along with the family and style read from its metadata
+pub struct Face {
+    pub family: String,
+    pub style: FontStyle,
+    pub path: PathBuf,
+}
+
+// Every font file that can be used, looked up by family and style rather than by file name
+pub struct FontLibrary {
+    faces: Vec<Face>,
+}
+
+// The folders fonts are looked for in, in order of preference: the fonts that come with
+// SquarePad, the folders added in the config, then the user's and the system's font folders
+pub fn font_dirs(config: &Config) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = assets::font_dir().into_iter().collect();
+    dirs.extend(config.font_dirs.iter().cloned());
+    if let Some(data_home) = config::data_home() {
+        dirs.push(data_home.join("fonts"));
+    }
+    if let Some(home) = config::home_dir() {
+        dirs.push(home.join(".fonts"));
+    }
+    dirs.extend(config::data_dirs().iter().map(|dir| dir.join("fonts")));
+
+    dirs
+}
+
+impl FontLibrary {
+    // Reads the metadata of every TrueType font in the font folders that exist
+    // Faces are kept in the order of their folders, so that fonts found earlier are preferred
+    pub fn discover(config: &Config) -> Result<FontLibrary, String> {
+        let mut library = FontLibrary { faces: Vec::new() };
+        for dir in font_dirs(config) {
+            if dir.is_dir() {
+                library.add_dir(&dir);
+            }
+        }
+
+        if library.faces.is_empty() {
+            return Err(match assets::font_dir() {
+                Ok(dir) => format!(
+                    "No fonts were found in {} or in any font folder.",
+                    dir.display()
+                ),
+                Err(e) => format!("No fonts were found in any font folder. {}", e),
+            });
+        }
+
+        Ok(library)
+    }
+
+    // Adds the fonts in a folder and its subfolders
+    // Files and folders that can't be read are skipped, so that one bad file doesn't lose the rest
+    fn add_dir(&mut self, dir: &Path) {
+        let mut paths: Vec<PathBuf> = match dir.read_dir() {
+            Ok(entries) => entries.filter_map(|e| e.ok().map(|e| e.path())).collect(),
+            Err(_) => return,
+        };
+        // Listing order depends on the file system, so paths are sorted to be found the same way
+        paths.sort();
+
+        for path in paths {
+            if path.is_dir() {
+                self.add_dir(&path);
+                continue;
+            }
+            let is_ttf = path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("ttf"));
+            if !is_ttf {
+                continue;
+            }
+
+            let (family, bold, italic) = match ttf::read_face(&path) {
+                Ok(face) => face,
+                Err(_) => continue,
+            };
+            // Fonts without a family name are known by their file name instead
+            let family = if family.is_empty() {
+                path.file_stem().unwrap().to_string_lossy().into_owned()
+            } else {
+                family
+            };
+            let mut style = FontStyle::NORMAL;
+            style.set(FontStyle::BOLD, bold);
+            style.set(FontStyle::ITALIC, italic);
+
+            self.faces.push(Face {
+                family,
+                style,
+                path,
+            });
+        }
+    }
+
+    // Adds fonts embedded in a document, ahead of installed ones so that the document looks the
+    // way it was saved. SDL can only open fonts from files, so they are written to the cache first
+    pub fn add_embedded(&mut self, fonts: &[FontData]) -> Result<(), String> {
+        let dir = config::cache_home()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("squarepad")
+            .join("fonts");
+
+        for (i, font) in fonts.iter().enumerate() {
+            // Files are named after their contents, so that the same font is only written once
+            let mut hasher = DefaultHasher::new();
+            font.data.hash(&mut hasher);
+            let path = dir.join(format!("{:016x}.ttf", hasher.finish()));
+
+            if !path.exists() {
+                std::fs::create_dir_all(&dir)
+                    .and_then(|_| std::fs::write(&path, &font.data))
+                    .map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+            }
+
+            self.faces.insert(
+                i,
+                Face {
+                    family: font.family.clone(),
+                    style: font.style,
+                    path,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    // The files of every face of each family a document's text is drawn in, to be saved along
+    // with it. Families that aren't installed are left out, as they are drawn in the default one
+    pub fn embed(&self, document: &Document) -> Result<Vec<FontData>, String> {
+        let mut families: Vec<&str> = Vec::new();
+        for family in document.marks.iter().filter_map(MarkData::font_name) {
+            if !families.iter().any(|f| same_family(f, family)) {
+                families.push(family);
+            }
+        }
+
+        let mut fonts: Vec<FontData> = Vec::new();
+        for family in families {
+            for face in self.faces.iter().filter(|f| same_family(&f.family, family)) {
+                // The same face may be installed in more than one folder
+                if fonts
+                    .iter()
+                    .any(|f| same_family(&f.family, family) && f.style == face.style)
+                {
+                    continue;
+                }
+
+                let data = std::fs::read(&face.path)
+                    .map_err(|e| format!("Could not read {}: {}", face.path.display(), e))?;
+                fonts.push(FontData {
+                    family: family.to_string(),
+                    style: face.style,
+                    data,
+                });
+            }
+        }
+
+        Ok(fonts)
+    }
+
+    // The name of each family, in alphabetical order
+    pub fn families(&self) -> Vec<String> {
+        let mut families: Vec<String> = self.faces.iter().map(|f| f.family.clone()).collect();
+        families.sort();
+        families.dedup();
+
+        families
+    }
+
+    // The face of a family closest to a style, which is the face with all of the style if there
+    // is one, and otherwise the one with as much of the style as possible and nothing more
+    // Of equally close faces, the one found first is used
+    pub fn find(&self, family: &str, style: FontStyle) -> Option<&Face> {
+        let faces = self.faces.iter().filter(|f| same_family(&f.family, family));
+
+        faces
+            .filter(|f| style.contains(f.style))
+            .rev()
+            .max_by_key(|f| f.style.bits().count_ones())
+            .or_else(|| self.faces.iter().find(|f| same_family(&f.family, family)))
+    }
+}
+
+// Family names are compared ignoring case, spaces and dashes, so that "NotoSerif" as saved in
+// older documents is the same family as "Noto Serif"
+pub fn same_family(a: &str, b: &str) -> bool {
+    let letters = |name: &str| {
+        name.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect::<String>()
+    };
+
+    letters(a) == letters(b)
+}
+
+// Open fonts by their file, style and point size, along with when each was last used
+type FontCache<'ttf> = HashMap<(PathBuf, i32, u16), (Rc<Font<'ttf, 'static>>, u64)>;
+
+// Opens fonts at whatever point size they are asked for, the first time they are asked for
+// The least recently used ones are closed once too many are open
+pub struct FontManager<'ttf> {
+    ttf: &'ttf Sdl2TtfContext,
+    library: FontLibrary,
+    cache: RefCell<FontCache<'ttf>>,
+    clock: Cell<u64>, // Counts lookups, to know which font was used least recently
+}
+
+impl<'ttf> FontManager<'ttf> {
+    pub fn new(ttf: &'ttf Sdl2TtfContext, config: &Config) -> Result<FontManager<'ttf>, String> {
+        Ok(FontManager {
+            ttf,
+            library: FontLibrary::discover(config)?,
+            cache: RefCell::new(HashMap::new()),
+            clock: Cell::new(0),
+        })
+    }
+
+    pub fn library(&self) -> &FontLibrary {
+        &self.library
+    }
+
+    // Makes a document's embedded fonts available, closing open fonts that they may replace
+    pub fn add_embedded(&mut self, fonts: &[FontData]) -> Result<(), String> {
+        if fonts.is_empty() {
+            return Ok(());
+        }
+        self.library.add_embedded(fonts)?;
+        self.cache.borrow_mut().clear();
+
+        Ok(())
+    }
+
+    // A font at a point size, loading it if it isn't open
+    // Families that aren't installed are drawn in the default family instead, and styles that a
+    // family has no face for are made up by slanting or thickening the closest one
+    pub fn get(
+        &self,
+        family: &str,
+        style: FontStyle,
+        point: u16,
+    ) -> Result<Rc<Font<'ttf, 'static>>, String> {
+        let face = self
+            .library
+            .find(family, style)
+            .or_else(|| self.library.find(DEFAULT_FAMILY, style))
+            .ok_or_else(|| format!("The font {} is not installed.", family))?;
+        let key = (face.path.clone(), style.bits(), point);
+
+        let time = self.clock.get() + 1;
+        self.clock.set(time);
+
+        let mut cache = self.cache.borrow_mut();
+        if let Some((font, used)) = cache.get_mut(&key) {
+            *used = time;
+            return Ok(Rc::clone(font));
+        }
+
+        let mut font = self
+            .ttf
+            .load_font(&face.path, point)
+            .map_err(|e| format!("Could not load font {}: {}", face.path.display(), e))?;
+        font.set_style(style - face.style);
+        let font = Rc::new(font);
+
+        if cache.len() >= CACHE_SIZE {
+            let oldest = cache
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                cache.remove(&oldest);
+            }
+        }
+        cache.insert(key, (Rc::clone(&font), time));
+
+        Ok(font)
+    }
+
+    // The font that characters missing from a family are drawn in, if it is installed
+    pub fn fallback(
+        &self,
+        style: FontStyle,
+        point: u16,
+    ) -> Result<Option<Rc<Font<'ttf, 'static>>>, String> {
+        match self.library.find(FALLBACK_FAMILY, style) {
+            Some(_) => self.get(FALLBACK_FAMILY, style, point).map(Some),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,420 @@
+// Syntax highlighting for code blocks
+// Languages are tokenizers, so new ones can be added by implementing Tokenizer and listing them
+// in LANGUAGES. Most languages only need a Syntax describing their keywords, comments and strings.
+
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenKind {
+    Plain,
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+// What a line starts inside of, carried over from the end of the previous line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineState {
+    Normal,
+    BlockComment,
+    String(&'static str), // A string that can span lines, with its closing delimiter
+}
+
+pub trait Tokenizer {
+    // Name used when saving a document
+    fn name(&self) -> &'static str;
+
+    // Splits a line into byte ranges of each kind of token
+    // Ranges are in order and cover the whole line
+    fn tokenize(&self, line: &str, state: &mut LineState) -> Vec<(TokenKind, Range<usize>)>;
+}
+
+// Describes a language well enough to tokenize it, one line at a time
+pub struct Syntax {
+    pub name: &'static str,
+    pub keywords: &'static [&'static str],
+    pub line_comment: Option<&'static str>,
+    pub block_comment: Option<(&'static str, &'static str)>,
+    pub strings: &'static [&'static str], // Delimiters, with longer ones first
+    pub multiline_strings: &'static [&'static str], // Delimiters of strings that may span lines
+    pub char_literals: bool, // Whether 'x' is a character rather than a string delimiter
+}
+
+pub const PLAIN: Syntax = Syntax {
+    name: "plain",
+    keywords: &[],
+    line_comment: None,
+    block_comment: None,
+    strings: &[],
+    multiline_strings: &[],
+    char_literals: false,
+};
+
+pub const RUST: Syntax = Syntax {
+    name: "rust",
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+        "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait",
+        "true", "type", "unsafe", "use", "where", "while",
+    ],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    strings: &["\""],
+    multiline_strings: &["\""],
+    char_literals: true,
+};
+
+pub const PYTHON: Syntax = Syntax {
+    name: "python",
+    keywords: &[
+        "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class",
+        "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global",
+        "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return",
+        "try", "while", "with", "yield",
+    ],
+    line_comment: Some("#"),
+    block_comment: None,
+    strings: &["\"\"\"", "'''", "\"", "'"],
+    multiline_strings: &["\"\"\"", "'''"],
+    char_literals: false,
+};
+
+pub const C: Syntax = Syntax {
+    name: "c",
+    keywords: &[
+        "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+        "enum", "extern", "float", "for", "goto", "if", "inline", "int", "long", "register",
+        "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch", "typedef",
+        "union", "unsigned", "void", "volatile", "while", "#include", "#define", "#ifdef",
+        "#ifndef", "#endif", "#if", "#else",
+    ],
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    strings: &["\""],
+    multiline_strings: &[],
+    char_literals: true,
+};
+
+// Every language that code blocks can be highlighted as, in the order they are cycled through
+pub const LANGUAGES: &[&dyn Tokenizer] = &[&PLAIN, &RUST, &PYTHON, &C];
+
+pub fn language(name: &str) -> Option<&'static dyn Tokenizer> {
+    LANGUAGES.iter().copied().find(|l| l.name() == name)
+}
+
+// The language after the given one, wrapping around
+pub fn next_language(name: &str) -> &'static dyn Tokenizer {
+    let i = LANGUAGES.iter().position(|l| l.name() == name).unwrap_or(0);
+    LANGUAGES[(i + 1) % LANGUAGES.len()]
+}
+
+// Every line split into one character per column, with tabs expanded to spaces, along with the
+// kind of token each character belongs to
+pub fn cells(
+    language: &dyn Tokenizer,
+    lines: &[String],
+    tab_width: usize,
+) -> Vec<Vec<(char, TokenKind)>> {
+    let mut state = LineState::Normal;
+
+    lines
+        .iter()
+        .map(|line| {
+            let mut cells = Vec::new();
+            for (kind, range) in language.tokenize(line, &mut state) {
+                for c in line[range].chars() {
+                    match c {
+                        '\t' => {
+                            let width = (cells.len() / tab_width + 1) * tab_width;
+                            cells.resize(width, (' ', kind));
+                        }
+                        _ => cells.push((c, kind)),
+                    }
+                }
+            }
+            cells
+        })
+        .collect()
+}
+
+impl Syntax {
+    // Length of a character literal starting at the beginning of text, if there is one
+    fn char_literal(text: &str) -> Option<usize> {
+        let mut chars = text.char_indices();
+        chars.next().filter(|(_, c)| *c == '\'')?;
+        let (_, c) = chars.next()?;
+        if c == '\\' {
+            chars.next()?;
+        }
+        match chars.next() {
+            Some((i, '\'')) => Some(i + 1),
+            _ => None,
+        }
+    }
+
+    fn is_word_char(c: char) -> bool {
+        c.is_alphanumeric() || c == '_'
+    }
+
+    // Whether a word can start with #, as C's preprocessor directives do at the start of a line
+    fn has_directives(&self) -> bool {
+        self.keywords.iter().any(|keyword| keyword.starts_with('#'))
+    }
+}
+
+impl Tokenizer for Syntax {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn tokenize(&self, line: &str, state: &mut LineState) -> Vec<(TokenKind, Range<usize>)> {
+        let mut tokens: Vec<(TokenKind, Range<usize>)> = Vec::new();
+        let mut push = |kind: TokenKind, range: Range<usize>| {
+            if range.is_empty() {
+                return;
+            }
+            // Merge with the previous token of the same kind
+            match tokens.last_mut() {
+                Some((last_kind, last)) if *last_kind == kind && last.end == range.start => {
+                    last.end = range.end
+                }
+                _ => tokens.push((kind, range)),
+            }
+        };
+
+        let mut i = 0;
+        while i < line.len() {
+            let rest = &line[i..];
+
+            // Continue whatever the previous line left open
+            match *state {
+                LineState::BlockComment => {
+                    let close = self.block_comment.map_or("", |(_, close)| close);
+                    match rest.find(close) {
+                        Some(end) => {
+                            push(TokenKind::Comment, i..i + end + close.len());
+                            i += end + close.len();
+                            *state = LineState::Normal;
+                        }
+                        None => {
+                            push(TokenKind::Comment, i..line.len());
+                            i = line.len();
+                        }
+                    }
+                    continue;
+                }
+                LineState::String(delimiter) => {
+                    match find_string_end(rest, delimiter) {
+                        Some(end) => {
+                            push(TokenKind::String, i..i + end);
+                            i += end;
+                            *state = LineState::Normal;
+                        }
+                        None => {
+                            push(TokenKind::String, i..line.len());
+                            i = line.len();
+                        }
+                    }
+                    continue;
+                }
+                LineState::Normal => (),
+            }
+
+            if let Some(comment) = self.line_comment {
+                if rest.starts_with(comment) {
+                    push(TokenKind::Comment, i..line.len());
+                    break;
+                }
+            }
+            if let Some((open, _)) = self.block_comment {
+                if rest.starts_with(open) {
+                    push(TokenKind::Comment, i..i + open.len());
+                    i += open.len();
+                    *state = LineState::BlockComment;
+                    continue;
+                }
+            }
+            if self.char_literals {
+                if let Some(length) = Syntax::char_literal(rest) {
+                    push(TokenKind::String, i..i + length);
+                    i += length;
+                    continue;
+                }
+            }
+            if let Some(delimiter) = self.strings.iter().find(|d| rest.starts_with(**d)) {
+                let body = &rest[delimiter.len()..];
+                match find_string_end(body, delimiter) {
+                    Some(end) => {
+                        let length = delimiter.len() + end;
+                        push(TokenKind::String, i..i + length);
+                        i += length;
+                    }
+                    None => {
+                        push(TokenKind::String, i..line.len());
+                        i = line.len();
+                        if self.multiline_strings.contains(delimiter) {
+                            *state = LineState::String(delimiter);
+                        }
+                    }
+                }
+                continue;
+            }
+
+            let c = rest.chars().next().unwrap();
+            let directive = c == '#' && self.has_directives() && line[..i].trim().is_empty();
+            if Syntax::is_word_char(c) || directive {
+                let length = c.len_utf8()
+                    + rest[c.len_utf8()..]
+                        .find(|c: char| !Syntax::is_word_char(c))
+                        .unwrap_or(rest.len() - c.len_utf8());
+                let word = &rest[..length];
+
+                let kind = if c.is_ascii_digit() {
+                    TokenKind::Number
+                } else if self.keywords.contains(&word) {
+                    TokenKind::Keyword
+                } else {
+                    TokenKind::Plain
+                };
+                push(kind, i..i + length);
+                i += length;
+                continue;
+            }
+
+            push(TokenKind::Plain, i..i + c.len_utf8());
+            i += c.len_utf8();
+        }
+
+        tokens
+    }
+}
+
+// Byte index just past the delimiter closing a string, skipping escaped characters
+fn find_string_end(text: &str, delimiter: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if text[i..].starts_with(delimiter) {
+            return Some(i + delimiter.len());
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The text and kind of each token of a line, leaving out whitespace between them
+    fn tokens(language: &Syntax, line: &str) -> Vec<(String, TokenKind)> {
+        language
+            .tokenize(line, &mut LineState::Normal)
+            .into_iter()
+            .map(|(kind, range)| (line[range].trim().to_string(), kind))
+            .filter(|(text, _)| !text.is_empty())
+            .collect()
+    }
+
+    // The kind of the token where some text first appears in a line
+    fn kind_of(language: &Syntax, line: &str, text: &str) -> Option<TokenKind> {
+        let at = line.find(text)?;
+        language
+            .tokenize(line, &mut LineState::Normal)
+            .into_iter()
+            .find(|(_, range)| range.contains(&at))
+            .map(|(kind, _)| kind)
+    }
+
+    #[test]
+    fn tokens_cover_the_whole_line_in_order() {
+        let line = "let s = \"a\"; // done";
+        let ranges = RUST.tokenize(line, &mut LineState::Normal);
+        assert_eq!(ranges.first().unwrap().1.start, 0);
+        assert_eq!(ranges.last().unwrap().1.end, line.len());
+        for pair in ranges.windows(2) {
+            assert_eq!(pair[0].1.end, pair[1].1.start);
+        }
+    }
+
+    #[test]
+    fn comments_can_follow_words_and_numbers_straight_away() {
+        assert_eq!(
+            kind_of(&PYTHON, "x#note", "#note"),
+            Some(TokenKind::Comment)
+        );
+        assert_eq!(kind_of(&PYTHON, "x#note", "x"), Some(TokenKind::Plain));
+        assert_eq!(kind_of(&PYTHON, "1#note", "1"), Some(TokenKind::Number));
+        assert_eq!(
+            kind_of(&PYTHON, "1#note", "#note"),
+            Some(TokenKind::Comment)
+        );
+    }
+
+    #[test]
+    fn preprocessor_directives_are_keywords_only_at_the_start_of_a_line() {
+        assert_eq!(
+            tokens(&C, "#include <stdio.h>")[0],
+            ("#include".to_string(), TokenKind::Keyword)
+        );
+        assert_eq!(
+            kind_of(&C, "  #define X 1", "#define"),
+            Some(TokenKind::Keyword)
+        );
+        assert_eq!(kind_of(&C, "a #define", "#define"), Some(TokenKind::Plain));
+        assert_eq!(
+            kind_of(&C, "#define str(x) #x", "#x"),
+            Some(TokenKind::Plain)
+        );
+        assert_eq!(kind_of(&RUST, "#define", "define"), Some(TokenKind::Plain));
+    }
+
+    #[test]
+    fn keywords_strings_and_numbers() {
+        let line = "fn main() { let c = 'x'; return 42; }";
+        assert_eq!(kind_of(&RUST, line, "fn"), Some(TokenKind::Keyword));
+        assert_eq!(kind_of(&RUST, line, "main"), Some(TokenKind::Plain));
+        assert_eq!(kind_of(&RUST, line, "'x'"), Some(TokenKind::String));
+        assert_eq!(kind_of(&RUST, line, "42"), Some(TokenKind::Number));
+        // Words containing keywords aren't keywords
+        assert_eq!(kind_of(&RUST, "format", "format"), Some(TokenKind::Plain));
+    }
+
+    #[test]
+    fn block_comments_and_strings_carry_over_to_the_next_line() {
+        let mut state = LineState::Normal;
+        RUST.tokenize("a /* open", &mut state);
+        assert_eq!(state, LineState::BlockComment);
+        let next = RUST.tokenize("still */ b", &mut state);
+        assert_eq!(next[0], (TokenKind::Comment, 0..8));
+        assert_eq!(state, LineState::Normal);
+
+        PYTHON.tokenize("s = \"\"\"doc", &mut state);
+        assert_eq!(state, LineState::String("\"\"\""));
+        let next = PYTHON.tokenize("end\"\"\" # done", &mut state);
+        assert_eq!(next[0], (TokenKind::String, 0..6));
+        assert_eq!(next.last().unwrap().0, TokenKind::Comment);
+    }
+
+    #[test]
+    fn escaped_delimiters_dont_end_strings() {
+        let line = r#"x = "a \" b" + y"#;
+        assert_eq!(
+            kind_of(&PYTHON, line, r#""a \" b""#),
+            Some(TokenKind::String)
+        );
+        assert_eq!(kind_of(&PYTHON, line, "y"), Some(TokenKind::Plain));
+    }
+
+    #[test]
+    fn tabs_are_expanded_to_the_next_stop() {
+        let cells = cells(&PLAIN, &["a\tb".to_string()], 4);
+        let text: String = cells[0].iter().map(|(c, _)| c).collect();
+        assert_eq!(text, "a   b");
+    }
+}
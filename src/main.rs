@@ -1,14 +1,28 @@
 extern crate sdl2;
 
 pub mod app;
+pub mod assets;
+pub mod cli;
+pub mod config;
 pub mod cursor;
+pub mod document;
 pub mod drawable;
 pub mod editor;
+pub mod export;
+pub mod fonts;
+pub mod highlight;
 pub mod mark;
+pub mod math;
 pub mod position;
 pub mod renderer;
+#[cfg(test)]
+mod testing;
 
 use app::App;
+use cli::Command;
+
+use std::path::PathBuf;
+use std::process::ExitCode;
 
 pub struct SdlContext {
     pub sdl: sdl2::Sdl,
@@ -31,11 +45,45 @@ impl SdlContext {
     }
 }
 
-fn main() -> Result<(), String> {
+// Runs the editor, with an optional document to open, which Ctrl+S will also save to
+fn open(file_path: Option<PathBuf>) -> Result<(), String> {
     let sdl_context = SdlContext::init()?;
-    let app = App::init(&sdl_context)?;
+    let app = App::init(&sdl_context, file_path)?;
+
+    app.run()
+}
 
-    app.run()?;
+// Exits with 2 when the arguments can't be understood, and 1 when a command fails
+fn main() -> ExitCode {
+    let command = match Command::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("squarepad: {}\n\n{}", e, cli::USAGE);
+            return ExitCode::from(2);
+        }
+    };
 
-    Ok(())
+    let result = match command {
+        Command::Open(file_path) => open(file_path),
+        Command::Export {
+            path,
+            format,
+            out_dir,
+        } => cli::export(&path, format, &out_dir),
+        Command::Info(path) => cli::info(&path),
+        Command::EmbedFonts(path) => cli::embed_fonts(&path),
+        Command::PageSize { path, page_squares } => cli::page_size(&path, page_squares),
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            Ok(())
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("squarepad: {}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,689 @@
+use super::textbox::TextBox;
+use super::{Area, Mark, TextInput};
+use crate::app::pages::{line_rows, Pages, SQUARE_SIZE};
+use crate::document::{BulletData, BulletItemData, MarkData};
+use crate::drawable::DrawOptions;
+use crate::position::{PageSquare, Position};
+use crate::renderer::Renderer;
+
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::pixels::Color;
+use sdl2::ttf::FontStyle;
+
+const MAX_LEVEL: u32 = 5; // Deepest level of nesting
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BulletKind {
+    Dot,
+    Numbered,
+    Lettered,
+}
+
+impl BulletKind {
+    // Name used when saving a document
+    pub fn name(&self) -> &'static str {
+        match *self {
+            BulletKind::Dot => "dot",
+            BulletKind::Numbered => "numbered",
+            BulletKind::Lettered => "lettered",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<BulletKind> {
+        match name {
+            "dot" => Some(BulletKind::Dot),
+            "numbered" => Some(BulletKind::Numbered),
+            "lettered" => Some(BulletKind::Lettered),
+            _ => None,
+        }
+    }
+
+    // The text drawn in front of the number'th item (starting at 1) of a level
+    pub fn marker(&self, level: u32, number: u32) -> String {
+        match *self {
+            BulletKind::Dot => ["•", "◦", "▪"][level as usize % 3].to_string(),
+            BulletKind::Numbered => format!("{}.", number),
+            BulletKind::Lettered => {
+                // a, b, ..., z, aa, ab, ...
+                let mut letters = Vec::new();
+                let mut n = number;
+                while n > 0 {
+                    letters.push((b'a' + ((n - 1) % 26) as u8) as char);
+                    n = (n - 1) / 26;
+                }
+                letters.iter().rev().collect::<String>() + "."
+            }
+        }
+    }
+
+    // The markers of a whole list, given the level of each item
+    // Numbering restarts under each parent item
+    pub fn markers(&self, levels: impl Iterator<Item = u32>) -> Vec<String> {
+        // counts[l] is the number of items seen so far at level l under the current parent
+        let mut counts: Vec<u32> = vec![0; MAX_LEVEL as usize + 1];
+        levels
+            .map(|level| {
+                let l = level.min(MAX_LEVEL) as usize;
+                counts[l] += 1;
+                for count in counts.iter_mut().skip(l + 1) {
+                    *count = 0;
+                }
+
+                self.marker(level, counts[l])
+            })
+            .collect()
+    }
+}
+
+struct BulletItem {
+    level: u32,
+    text: TextBox,
+}
+
+// A list where each item is a text box indented one square past its bullet
+// Items are stacked below each other, each taking as many rows as its text has lines
+pub struct BulletList {
+    id: uuid::Uuid, // Marker textures are stored under this id, one per item
+    page_square: PageSquare,
+    kind: BulletKind,
+    items: Vec<BulletItem>,
+    marker_sizes: Vec<(u32, u32)>,
+    current: usize, // The item being typed into
+    font_name: String,
+    font_style: FontStyle,
+    point: u16,
+    color: Color,
+    max_width: u32,
+}
+
+impl BulletList {
+    pub fn new(
+        page_square: PageSquare,
+        kind: BulletKind,
+        font_name: String,
+        font_style: FontStyle,
+        point: u16,
+        color: Color,
+        max_width: u32,
+    ) -> BulletList {
+        let mut list = BulletList {
+            id: uuid::Uuid::new_v4(),
+            page_square,
+            kind,
+            items: vec![],
+            marker_sizes: vec![],
+            current: 0,
+            font_name,
+            font_style,
+            point,
+            color,
+            max_width,
+        };
+        list.items.push(list.new_item(0));
+
+        list
+    }
+
+    // Rebuilds a saved list, rendering all of its items
+    pub fn from_data(
+        data: &BulletData,
+        pages: &Pages,
+        renderer: &mut Renderer,
+    ) -> Result<BulletList, String> {
+        let page_square = PageSquare::new(data.page, data.square, pages)?;
+
+        let mut list = BulletList::new(
+            page_square,
+            data.kind,
+            data.font_name.clone(),
+            data.font_style,
+            data.point,
+            data.color,
+            data.max_width,
+        );
+        list.set_items(&data.items, renderer)?;
+
+        Ok(list)
+    }
+
+    fn new_item(&self, level: u32) -> BulletItem {
+        BulletItem {
+            level,
+            text: TextBox::new(
+                self.page_square,
+                self.font_name.clone(),
+                self.font_style,
+                self.point,
+                self.color,
+                self.item_width(level),
+            ),
+        }
+    }
+
+    // The width left for an item's text once it is indented past its bullet
+    fn item_width(&self, level: u32) -> u32 {
+        self.max_width.saturating_sub((level + 1) * SQUARE_SIZE)
+    }
+
+    fn set_items(
+        &mut self,
+        items: &[BulletItemData],
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        for item in &self.items {
+            item.text.free_textures(renderer);
+        }
+        self.items.clear();
+
+        for data in items {
+            let mut item = self.new_item(data.level.min(MAX_LEVEL));
+            item.text.set_lines(
+                data.lines.clone(),
+                data.breaks.clone(),
+                &data.spans,
+                renderer,
+            )?;
+            self.items.push(item);
+        }
+        // A list always has at least one item to type into
+        if self.items.is_empty() {
+            self.items.push(self.new_item(0));
+        }
+        self.current = self.current.min(self.items.len() - 1);
+
+        self.update_markers(renderer)
+    }
+
+    // The height of each line of the items' text, in pixels
+    fn line_height(&self) -> u32 {
+        line_rows(self.point) * SQUARE_SIZE
+    }
+
+    // Where an item's text starts, in pixels from the top-left of the list
+    fn item_offset(&self, i: usize) -> (i32, i32) {
+        let row: usize = self.items[..i]
+            .iter()
+            .map(|item| item.text.line_count())
+            .sum();
+
+        (
+            ((self.items[i].level + 1) * SQUARE_SIZE) as i32,
+            (row as u32 * self.line_height()) as i32,
+        )
+    }
+
+    // Changes the nesting level of the current item
+    // An item can be at most one level deeper than the item above it
+    fn set_level(&mut self, level: u32, renderer: &mut Renderer) -> Result<(), String> {
+        let max_level = match self.current {
+            0 => 0,
+            i => (self.items[i - 1].level + 1).min(MAX_LEVEL),
+        };
+        let level = level.min(max_level);
+
+        let width = self.item_width(level);
+        let item = &mut self.items[self.current];
+        if item.level == level {
+            return Ok(());
+        }
+        item.level = level;
+        item.text.set_max_width(width, renderer)?;
+
+        self.update_markers(renderer)
+    }
+
+    // Renders the bullets again, since numbering depends on every item above
+    pub fn update_markers(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        self.marker_sizes.clear();
+
+        let markers = self.kind.markers(self.items.iter().map(|item| item.level));
+        for (i, marker) in markers.iter().enumerate() {
+            let size = renderer.create_text(
+                self.id,
+                Some(i),
+                marker,
+                &self.font_name,
+                self.font_style,
+                self.point,
+                self.color,
+            )?;
+            self.marker_sizes.push(size);
+        }
+
+        Ok(())
+    }
+}
+
+impl Mark for BulletList {
+    fn draw(&self, renderer: &mut Renderer) -> Result<(), String> {
+        let mut row = 0;
+
+        for (i, item) in self.items.iter().enumerate() {
+            let position = Position::add(
+                self.page_square.position,
+                (item.level * SQUARE_SIZE) as i32,
+                row * self.line_height() as i32,
+            );
+
+            let options = DrawOptions {
+                src: None,
+                position,
+                size: self.marker_sizes[i],
+                rotation: None,
+                flip_h: false,
+                flip_v: false,
+            };
+            renderer.draw_texture(self.id, i, options)?;
+
+            item.text
+                .draw_at(Position::add(position, SQUARE_SIZE as i32, 0), renderer)?;
+
+            row += item.text.line_count() as i32;
+        }
+
+        Ok(())
+    }
+
+    fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    fn page_square(&self) -> PageSquare {
+        self.page_square
+    }
+
+    fn size(&self) -> (u32, u32) {
+        // Each item's text starts one square past its bullet
+        let width = self
+            .items
+            .iter()
+            .map(|item| (item.level + 1) * SQUARE_SIZE + item.text.width())
+            .max()
+            .unwrap_or(SQUARE_SIZE);
+        let rows = self
+            .items
+            .iter()
+            .map(|item| item.text.line_count())
+            .sum::<usize>();
+
+        (
+            width.div_ceil(SQUARE_SIZE),
+            rows.max(1) as u32 * line_rows(self.point),
+        )
+    }
+
+    fn free_textures(&self, renderer: &mut Renderer) {
+        renderer.remove_textures(self.id);
+        for item in &self.items {
+            item.text.free_textures(renderer);
+        }
+    }
+
+    fn to_data(&self) -> MarkData {
+        MarkData::Bullets(BulletData {
+            page: self.page_square.page,
+            square: self.page_square.square,
+            kind: self.kind,
+            font_name: self.font_name.clone(),
+            font_style: self.font_style,
+            point: self.point,
+            color: self.color,
+            max_width: self.max_width,
+            items: self
+                .items
+                .iter()
+                .map(|item| BulletItemData {
+                    level: item.level,
+                    lines: item.text.lines().clone(),
+                    breaks: item.text.breaks().clone(),
+                    spans: item.text.spans(),
+                })
+                .collect(),
+        })
+    }
+
+    fn restore(&mut self, data: &MarkData, renderer: &mut Renderer) -> Result<(), String> {
+        match data {
+            MarkData::Bullets(data) => {
+                self.kind = data.kind;
+                self.font_name = data.font_name.clone();
+                self.font_style = data.font_style;
+                self.point = data.point;
+                self.color = data.color;
+                self.max_width = data.max_width;
+
+                self.set_items(&data.items, renderer)
+            }
+            _ => Err("Cannot restore a bullet list from another kind of mark.".to_string()),
+        }
+    }
+}
+
+impl TextInput for BulletList {
+    fn push_str(&mut self, string: &str, renderer: &mut Renderer) -> Result<(), String> {
+        self.items[self.current].text.push_str(string, renderer)
+    }
+
+    // Deleting from an empty item removes it and continues in the item above
+    fn pop(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        if !self.items[self.current].text.is_empty() {
+            return self.items[self.current].text.pop(renderer);
+        }
+        if self.items.len() == 1 {
+            return Ok(());
+        }
+
+        let item = self.items.remove(self.current);
+        item.text.free_textures(renderer);
+        self.current = self.current.saturating_sub(1);
+
+        self.update_markers(renderer)
+    }
+
+    // Splits the current item at the caret, with the text after it starting the next item on the
+    // same level
+    // Pressing enter on an empty item moves it up a level instead, like most word processors
+    fn new_line(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        let level = self.items[self.current].level;
+        if self.items[self.current].text.is_empty() && level > 0 {
+            return self.set_level(level - 1, renderer);
+        }
+
+        // The text box breaks its paragraph at the caret, leaving the caret on the first line of
+        // what is moved into the new item
+        let text = &mut self.items[self.current].text;
+        text.new_line(renderer)?;
+        let at = text.caret().0;
+        let mut lines = text.lines().clone();
+        let mut breaks = text.breaks().clone();
+        let mut spans = text.spans();
+        let rest = (
+            lines.split_off(at),
+            breaks.split_off(at),
+            spans.split_off(at),
+        );
+        text.set_lines(lines, breaks, &spans, renderer)?;
+
+        let mut item = self.new_item(level);
+        item.text.set_lines(rest.0, rest.1, &rest.2, renderer)?;
+        item.text.place_caret(0, 0, false, renderer)?;
+        self.current += 1;
+        self.items.insert(self.current, item);
+
+        self.update_markers(renderer)
+    }
+
+    fn delete(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        self.items[self.current].text.delete(renderer)
+    }
+
+    // Up and Down carry on into the items above and below, keeping the caret as close as they
+    // can to where it was across the list, but selections stay within one item
+    fn move_caret(
+        &mut self,
+        keycode: Keycode,
+        select: bool,
+        renderer: &Renderer,
+    ) -> Result<(), String> {
+        let text = &self.items[self.current].text;
+        let target = match keycode {
+            Keycode::Up if text.caret().0 == 0 => self.current.checked_sub(1),
+            Keycode::Down if text.caret().0 + 1 >= text.line_count() => {
+                Some(self.current + 1).filter(|i| *i < self.items.len())
+            }
+            _ => None,
+        };
+        let target = match target {
+            Some(target) if !select => target,
+            _ => {
+                return self.items[self.current]
+                    .text
+                    .move_caret(keycode, select, renderer)
+            }
+        };
+
+        let x = self.item_offset(self.current).0 + text.caret_offset(renderer)?.0;
+        let text = &self.items[target].text;
+        let line = match keycode {
+            Keycode::Up => text.line_count().saturating_sub(1),
+            _ => 0,
+        };
+        let y = (line as u32 * text.line_height()) as i32;
+        let dx = self.item_offset(target).0;
+        self.current = target;
+
+        self.items[target]
+            .text
+            .place_caret(x - dx, y, false, renderer)
+    }
+
+    // Clicking on another item starts typing into it, but selections stay within one item
+    fn place_caret(
+        &mut self,
+        x: i32,
+        y: i32,
+        select: bool,
+        renderer: &Renderer,
+    ) -> Result<(), String> {
+        if !select {
+            let row = y.max(0) as usize / self.line_height() as usize;
+            let mut rows = 0;
+            self.current = self.items.len() - 1;
+            for (i, item) in self.items.iter().enumerate() {
+                rows += item.text.line_count();
+                if row < rows {
+                    self.current = i;
+                    break;
+                }
+            }
+        }
+
+        let (dx, dy) = self.item_offset(self.current);
+        self.items[self.current]
+            .text
+            .place_caret(x - dx, y - dy, select, renderer)
+    }
+
+    fn caret_position(&self, renderer: &Renderer) -> Result<Option<Position>, String> {
+        let (dx, dy) = self.item_offset(self.current);
+        let (x, y) = self.items[self.current].text.caret_offset(renderer)?;
+
+        Ok(Some(Position::add(
+            self.page_square.position,
+            dx + x,
+            dy + y,
+        )))
+    }
+
+    fn selected_text(&self) -> Option<String> {
+        self.items[self.current].text.selected_text()
+    }
+
+    fn delete_selection(&mut self, renderer: &mut Renderer) -> Result<bool, String> {
+        self.items[self.current].text.delete_selection(renderer)
+    }
+
+    fn selection_rects(&self, renderer: &Renderer) -> Result<Vec<Area>, String> {
+        let (dx, dy) = self.item_offset(self.current);
+        let origin = Position::add(self.page_square.position, dx, dy);
+
+        Ok(self.items[self.current]
+            .text
+            .selection_offsets(renderer)?
+            .into_iter()
+            .map(|(x, y, width)| (Position::add(origin, x, y), (width, self.line_height())))
+            .collect())
+    }
+
+    fn line_height(&self) -> u32 {
+        BulletList::line_height(self)
+    }
+
+    // The bullets change along with the text
+    fn set_text_font(
+        &mut self,
+        font_name: &str,
+        point: u16,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        self.font_name = font_name.to_string();
+        self.point = point;
+        for item in &mut self.items {
+            item.text.set_font(font_name, point, renderer)?;
+        }
+
+        self.update_markers(renderer)
+    }
+
+    // Only the current item's selected text changes color, unless the whole list does
+    fn set_text_color(
+        &mut self,
+        color: Color,
+        all: bool,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        if !all {
+            return self.items[self.current].text.set_color(color, renderer);
+        }
+
+        self.color = color;
+        for item in &mut self.items {
+            item.text.set_all_color(color, renderer)?;
+        }
+
+        self.update_markers(renderer)
+    }
+
+    fn handle_key(
+        &mut self,
+        keycode: Keycode,
+        keymod: Mod,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        let level = self.items[self.current].level;
+
+        match keycode {
+            // Tab nests the current item, Shift+Tab brings it back out
+            Keycode::Tab => {
+                if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                    self.set_level(level.saturating_sub(1), renderer)
+                } else {
+                    self.set_level(level + 1, renderer)
+                }
+            }
+            // Ctrl+L cycles between bullets, numbers and letters
+            Keycode::L if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                self.kind = match self.kind {
+                    BulletKind::Dot => BulletKind::Numbered,
+                    BulletKind::Numbered => BulletKind::Lettered,
+                    BulletKind::Lettered => BulletKind::Dot,
+                };
+                self.update_markers(renderer)
+            }
+            // Anything else styles the item's text
+            _ => self.items[self.current]
+                .text
+                .handle_key(keycode, keymod, renderer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mark::textbox::{paragraphs, LineBreak, StyleSpan, TextStyle};
+    use crate::testing::with_renderer;
+
+    const BOLD: TextStyle = TextStyle {
+        font_style: FontStyle::BOLD,
+        color: Color::BLACK,
+    };
+
+    // A list with an item for each text, with the caret at the end of the first one
+    fn list(texts: &[&str], spans: &[StyleSpan], renderer: &mut Renderer) -> BulletList {
+        let pages = Pages::new((29, 42), renderer).unwrap();
+        let page_square = PageSquare::new(0, (2, 2), &pages).unwrap();
+        let mut list = BulletList::new(
+            page_square,
+            BulletKind::Dot,
+            "NotoSerif".to_string(),
+            FontStyle::NORMAL,
+            30,
+            Color::BLACK,
+            600,
+        );
+
+        let items: Vec<BulletItemData> = texts
+            .iter()
+            .map(|text| BulletItemData {
+                level: 0,
+                lines: vec![text.to_string()],
+                breaks: vec![LineBreak::Hard],
+                spans: vec![spans.to_vec()],
+            })
+            .collect();
+        list.set_items(&items, renderer).unwrap();
+        list
+    }
+
+    fn texts(list: &BulletList) -> Vec<String> {
+        list.items
+            .iter()
+            .map(|item| paragraphs(item.text.lines(), item.text.breaks()).join("\n"))
+            .collect()
+    }
+
+    #[test]
+    fn enter_splits_the_item_at_the_caret() {
+        with_renderer(|_, renderer| {
+            let world = StyleSpan {
+                start: 6,
+                end: 11,
+                style: BOLD,
+            };
+            let mut list = list(&["hello world"], &[world], renderer);
+            for _ in 0..5 {
+                list.move_caret(Keycode::Left, false, renderer).unwrap();
+            }
+
+            list.new_line(renderer).unwrap();
+            assert_eq!(texts(&list), vec!["hello ", "world"]);
+            assert_eq!(list.current, 1);
+            assert_eq!(list.items[1].text.caret(), (0, 0));
+
+            // The styled text moves along with it
+            assert_eq!(list.items[0].text.spans(), vec![vec![]]);
+            assert_eq!(
+                list.items[1].text.spans(),
+                vec![vec![StyleSpan {
+                    start: 0,
+                    end: 5,
+                    style: BOLD
+                }]]
+            );
+        });
+    }
+
+    #[test]
+    fn up_and_down_move_between_items() {
+        with_renderer(|_, renderer| {
+            let mut list = list(&["one", "two", "three"], &[], renderer);
+
+            let mut visited = vec![];
+            for keycode in [Keycode::Down, Keycode::Down, Keycode::Down] {
+                list.move_caret(keycode, false, renderer).unwrap();
+                visited.push(list.current);
+            }
+            for keycode in [Keycode::Up, Keycode::Up, Keycode::Up] {
+                list.move_caret(keycode, false, renderer).unwrap();
+                visited.push(list.current);
+            }
+            assert_eq!(visited, vec![1, 2, 2, 1, 0, 0]);
+            assert_eq!(list.items[0].text.caret().0, 0);
+
+            // Selecting stays within the item
+            list.move_caret(Keycode::Down, true, renderer).unwrap();
+            assert_eq!(list.current, 0);
+        });
+    }
+}
@@ -0,0 +1,296 @@
+use super::{Mark, TextInput};
+use crate::app::pages::{Pages, SQUARE_SIZE};
+use crate::document::{CodeData, MarkData};
+use crate::drawable::DrawOptions;
+use crate::highlight::{self, TokenKind, Tokenizer};
+use crate::position::{PageSquare, Position};
+use crate::renderer::{Renderer, TextSpan};
+
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::pixels::Color;
+use sdl2::ttf::FontStyle;
+
+pub const TAB_WIDTH: usize = 4;
+// New code blocks are written in the monospaced font that is always there, at this size
+pub const POINT: u16 = 30;
+pub const BACKGROUND: Color = Color::RGBA(90, 110, 160, 40);
+
+// Code written in a monospaced font, with one character in each square
+// Tabs are kept in the text and shown as spaces up to the next tab stop
+pub struct CodeBlock {
+    id: uuid::Uuid,
+    page_square: PageSquare, // Top-left of the block
+    lines: Vec<String>,
+    language: &'static dyn Tokenizer,
+    font_name: String,
+    point: u16,
+    max_width: u32,
+    line_cells: Vec<Vec<(char, TokenKind)>>, // What each line's texture was last rendered with
+    line_sizes: Vec<(u32, u32)>,
+}
+
+impl CodeBlock {
+    pub fn new(
+        page_square: PageSquare,
+        language: &'static dyn Tokenizer,
+        font_name: String,
+        point: u16,
+        max_width: u32,
+    ) -> CodeBlock {
+        CodeBlock {
+            id: uuid::Uuid::new_v4(),
+            page_square,
+            lines: vec![String::new()],
+            language,
+            font_name,
+            point,
+            max_width,
+            line_cells: vec![],
+            line_sizes: vec![],
+        }
+    }
+
+    pub fn from_data(
+        data: &CodeData,
+        pages: &Pages,
+        renderer: &mut Renderer,
+    ) -> Result<CodeBlock, String> {
+        let page_square = PageSquare::new(data.page, data.square, pages)?;
+        let language = highlight::language(&data.language)
+            .ok_or_else(|| format!("Unknown language '{}'.", data.language))?;
+
+        let mut code_block = CodeBlock::new(
+            page_square,
+            language,
+            data.font_name.clone(),
+            data.point,
+            data.max_width,
+        );
+        if !data.lines.is_empty() {
+            code_block.lines = data.lines.clone();
+        }
+        code_block.update(renderer)?;
+
+        Ok(code_block)
+    }
+
+    pub fn lines(&self) -> &Vec<String> {
+        &self.lines
+    }
+
+    pub fn language(&self) -> &'static str {
+        self.language.name()
+    }
+
+    // The number of characters that fit on a line
+    pub fn columns(&self) -> usize {
+        (self.max_width / SQUARE_SIZE).max(1) as usize
+    }
+
+    // The number of squares taken up by a line once its tabs are expanded
+    fn display_width(line: &str) -> usize {
+        line.chars().fold(0, |width, c| match c {
+            '\t' => (width / TAB_WIDTH + 1) * TAB_WIDTH,
+            _ => width + 1,
+        })
+    }
+
+    pub fn color(kind: TokenKind) -> Color {
+        match kind {
+            TokenKind::Plain => Color::BLACK,
+            TokenKind::Keyword => Color::RGB(0, 60, 170),
+            TokenKind::String => Color::RGB(20, 130, 40),
+            TokenKind::Comment => Color::RGB(128, 128, 128),
+            TokenKind::Number => Color::RGB(170, 80, 0),
+        }
+    }
+
+    // Highlights every line again, since an edit can change the state that later lines start in
+    // Only lines that come out differently are rendered again
+    pub fn update(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        self.line_cells.truncate(self.lines.len());
+        self.line_sizes.truncate(self.lines.len());
+
+        let lines = highlight::cells(self.language, &self.lines, TAB_WIDTH);
+        for (i, cells) in lines.into_iter().enumerate() {
+            if self.line_cells.get(i) == Some(&cells) {
+                continue;
+            }
+
+            // Neighbouring characters of the same kind share a span
+            let mut spans: Vec<TextSpan> = Vec::new();
+            let mut last_kind = None;
+            for (c, kind) in &cells {
+                match spans.last_mut() {
+                    Some(span) if last_kind == Some(*kind) => span.text.push(*c),
+                    _ => spans.push(TextSpan {
+                        text: c.to_string(),
+                        font_style: FontStyle::NORMAL,
+                        color: CodeBlock::color(*kind),
+                    }),
+                }
+                last_kind = Some(*kind);
+            }
+            let size = renderer.create_text_spans(
+                self.id,
+                Some(i),
+                &spans,
+                &self.font_name,
+                self.point,
+                Some(SQUARE_SIZE),
+            )?;
+
+            if i < self.line_cells.len() {
+                self.line_cells[i] = cells;
+                self.line_sizes[i] = size;
+            } else {
+                self.line_cells.push(cells);
+                self.line_sizes.push(size);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Mark for CodeBlock {
+    fn draw(&self, renderer: &mut Renderer) -> Result<(), String> {
+        renderer.draw_fill_rect(
+            self.page_square.position,
+            (
+                self.columns() as u32 * SQUARE_SIZE - 1,
+                self.lines.len() as u32 * SQUARE_SIZE - 1,
+            ),
+            BACKGROUND,
+        )?;
+
+        for (i, cells) in self.line_cells.iter().enumerate() {
+            if cells.is_empty() {
+                continue;
+            }
+
+            // Characters beyond the last column are only there if the block has been narrowed
+            let (width, height) = self.line_sizes[i];
+            let width = width.min(self.columns() as u32 * SQUARE_SIZE);
+            let options = DrawOptions {
+                src: Some(sdl2::rect::Rect::new(0, 0, width, height)),
+                position: Position::add(
+                    self.page_square.position,
+                    0,
+                    (i as u32 * SQUARE_SIZE) as i32,
+                ),
+                size: (width, height),
+                rotation: None,
+                flip_h: false,
+                flip_v: false,
+            };
+            renderer.draw_texture(self.id, i, options)?;
+        }
+
+        Ok(())
+    }
+
+    fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    fn page_square(&self) -> PageSquare {
+        self.page_square
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (self.columns() as u32, self.lines.len() as u32)
+    }
+
+    fn to_data(&self) -> MarkData {
+        MarkData::Code(CodeData {
+            page: self.page_square.page,
+            square: self.page_square.square,
+            language: self.language.name().to_string(),
+            font_name: self.font_name.clone(),
+            point: self.point,
+            max_width: self.max_width,
+            lines: self.lines.clone(),
+        })
+    }
+
+    fn restore(&mut self, data: &MarkData, renderer: &mut Renderer) -> Result<(), String> {
+        match data {
+            MarkData::Code(data) => {
+                self.language = highlight::language(&data.language)
+                    .ok_or_else(|| format!("Unknown language '{}'.", data.language))?;
+                self.font_name = data.font_name.clone();
+                self.point = data.point;
+                self.max_width = data.max_width;
+                self.lines = data.lines.clone();
+                if self.lines.is_empty() {
+                    self.lines.push(String::new());
+                }
+                // The font may have changed, so nothing rendered before can be kept
+                self.line_cells.clear();
+                self.line_sizes.clear();
+
+                self.update(renderer)
+            }
+            _ => Err("Cannot restore a code block from another kind of mark.".to_string()),
+        }
+    }
+}
+
+impl TextInput for CodeBlock {
+    // Characters that would run past the last column are dropped, code is never wrapped
+    fn push_str(&mut self, string: &str, renderer: &mut Renderer) -> Result<(), String> {
+        for c in string.chars() {
+            match c {
+                '\n' => self.lines.push(String::new()),
+                '\r' => (),
+                _ => {
+                    let columns = self.columns();
+                    let line = self.lines.last_mut().unwrap();
+                    line.push(c);
+                    if CodeBlock::display_width(line) > columns {
+                        line.pop();
+                    }
+                }
+            }
+        }
+
+        self.update(renderer)
+    }
+
+    // Removing from an empty line joins it to the previous one
+    fn pop(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        let line = self.lines.last_mut().unwrap();
+        if line.pop().is_none() && self.lines.len() > 1 {
+            self.lines.pop();
+        }
+
+        self.update(renderer)
+    }
+
+    // New lines start with the same indentation as the line before them
+    fn new_line(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        let line = self.lines.last().unwrap();
+        let indentation: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+        self.lines.push(indentation);
+
+        self.update(renderer)
+    }
+
+    // Tab indents, and Ctrl+L switches to the next language
+    fn handle_key(
+        &mut self,
+        keycode: Keycode,
+        keymod: Mod,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        match keycode {
+            Keycode::Tab => self.push_str("\t", renderer),
+            Keycode::L if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) => {
+                self.language = highlight::next_language(self.language.name());
+                self.update(renderer)
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use super::Mark;
+use crate::app::pages::{Pages, SQUARE_SIZE};
+use crate::document::{ImageData, MarkData};
+use crate::drawable::DrawOptions;
+use crate::position::{PageSquare, Position};
+use crate::renderer::Renderer;
+
+use std::path::Path;
+use std::rc::Rc;
+
+use sdl2::image::ImageRWops;
+use sdl2::pixels::Color;
+use sdl2::rwops::RWops;
+
+// Extensions of the image files that can be placed on a page
+pub const EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "bmp", "gif"];
+
+// An image file shown over a block of squares, scaled down to fit inside it
+// The file is kept in the document, and its path is only where it was pasted from
+pub struct ImageMark {
+    id: uuid::Uuid,
+    page_square: PageSquare, // Top-left of the block
+    path: String,
+    data: Rc<[u8]>,     // The image file
+    size: (u32, u32),   // In squares
+    pixels: (u32, u32), // Size of the image file, or (0, 0) if it couldn't be loaded
+}
+
+impl ImageMark {
+    // Places an image with its top-left at page_square, at its own size if it fits within
+    // max_size squares, and otherwise as large as fits while keeping its proportions
+    pub fn new(
+        page_square: PageSquare,
+        path: &Path,
+        max_size: (u32, u32),
+        renderer: &mut Renderer,
+    ) -> Result<ImageMark, String> {
+        let data =
+            std::fs::read(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        let mut image = ImageMark {
+            id: uuid::Uuid::new_v4(),
+            page_square,
+            path: path.to_string_lossy().into_owned(),
+            data: data.into(),
+            size: (1, 1),
+            pixels: (0, 0),
+        };
+        image.load(renderer)?;
+
+        let (w, h) = image.pixels;
+        let scale = (max_size.0 as f32 * SQUARE_SIZE as f32 / w as f32)
+            .min(max_size.1 as f32 * SQUARE_SIZE as f32 / h as f32)
+            .min(1.0);
+        image.size = (
+            ((w as f32 * scale / SQUARE_SIZE as f32).ceil() as u32).clamp(1, max_size.0.max(1)),
+            ((h as f32 * scale / SQUARE_SIZE as f32).ceil() as u32).clamp(1, max_size.1.max(1)),
+        );
+
+        Ok(image)
+    }
+
+    // Images that can't be loaded are drawn as a crossed-out box in their place, so that the
+    // rest of the document can still be opened
+    pub fn from_data(
+        data: &ImageData,
+        pages: &Pages,
+        renderer: &mut Renderer,
+    ) -> Result<ImageMark, String> {
+        let mut image = ImageMark {
+            id: uuid::Uuid::new_v4(),
+            page_square: PageSquare::new(data.page, data.square, pages)?,
+            path: data.path.clone(),
+            data: Rc::clone(&data.data),
+            size: data.size,
+            pixels: (0, 0),
+        };
+        // Documents from before images were kept in them only have the image's path
+        if image.data.is_empty() {
+            if let Ok(file) = std::fs::read(&image.path) {
+                image.data = file.into();
+            }
+        }
+        if let Err(e) = image.load(renderer) {
+            eprintln!("{}", e);
+        }
+
+        Ok(image)
+    }
+
+    // Whether a file looks like an image that can be placed, going by its extension
+    pub fn is_image(path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| EXTENSIONS.contains(&e.to_lowercase().as_str()))
+            .unwrap_or(false)
+    }
+
+    fn load(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        self.pixels = (0, 0);
+        let surface = RWops::from_bytes(&self.data)
+            .and_then(|rwops| rwops.load())
+            .map_err(|e| format!("Could not load image {}: {}", self.path, e))?;
+        self.pixels = surface.size();
+        renderer.create_texture(self.id, Some(0), surface)
+    }
+
+    // The size the image is drawn at, the largest that fits in its squares without stretching
+    fn draw_size(&self) -> (u32, u32) {
+        let (w, h) = self.pixels;
+        let width = self.size.0 * SQUARE_SIZE - 1;
+        let height = self.size.1 * SQUARE_SIZE - 1;
+        let scale = (width as f32 / w as f32).min(height as f32 / h as f32);
+
+        (
+            ((w as f32 * scale) as u32).max(1),
+            ((h as f32 * scale) as u32).max(1),
+        )
+    }
+}
+
+impl Mark for ImageMark {
+    fn draw(&self, renderer: &mut Renderer) -> Result<(), String> {
+        if self.pixels == (0, 0) {
+            let (w, h) = (
+                (self.size.0 * SQUARE_SIZE - 1) as i32,
+                (self.size.1 * SQUARE_SIZE - 1) as i32,
+            );
+            let gray = Color::RGB(160, 160, 160);
+            let corner = |x, y| Position::add(self.page_square.position, x, y);
+            renderer.draw_rect(corner(0, 0), 1, (w as u32, h as u32), gray)?;
+            renderer.draw_line(corner(0, 0), corner(w, h), 1, gray)?;
+            return renderer.draw_line(corner(w, 0), corner(0, h), 1, gray);
+        }
+
+        let options = DrawOptions {
+            src: None,
+            position: self.page_square.position,
+            size: self.draw_size(),
+            rotation: None,
+            flip_h: false,
+            flip_v: false,
+        };
+        renderer.draw_texture(self.id, 0, options)
+    }
+
+    fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    fn page_square(&self) -> PageSquare {
+        self.page_square
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    fn to_data(&self) -> MarkData {
+        MarkData::Image(ImageData {
+            page: self.page_square.page,
+            square: self.page_square.square,
+            size: self.size,
+            path: self.path.clone(),
+            data: Rc::clone(&self.data),
+        })
+    }
+
+    fn restore(&mut self, data: &MarkData, renderer: &mut Renderer) -> Result<(), String> {
+        match data {
+            MarkData::Image(data) => {
+                self.size = data.size;
+                if self.data != data.data {
+                    self.path = data.path.clone();
+                    self.data = Rc::clone(&data.data);
+                    renderer.remove_textures(self.id);
+                    if let Err(e) = self.load(renderer) {
+                        eprintln!("{}", e);
+                    }
+                }
+
+                Ok(())
+            }
+            _ => Err("Cannot restore an image from another kind of mark.".to_string()),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use super::Mark;
+use crate::app::pages::Pages;
+use crate::document::{LineData, MarkData};
+use crate::position::{PageSquare, Position};
+use crate::renderer::Renderer;
+
+use sdl2::pixels::Color;
+
+// A line's page and the two corners it joins, in the same order whichever way it was drawn
+// No two lines have the same ends, so they tell lines apart
+pub type LineEnds = (u32, (u32, u32), (u32, u32));
+
+pub fn ends(page: u32, start: (u32, u32), end: (u32, u32)) -> LineEnds {
+    (page, start.min(end), start.max(end))
+}
+
+// The squares that a line between two corners passes through
+// Lines along a grid line take up the row or column of squares below or right of them
+pub fn crossed_squares(start: (u32, u32), end: (u32, u32)) -> Vec<(u32, u32)> {
+    let (a, b) = (start.min(end), start.max(end));
+    if a.0 == b.0 {
+        let (top, bottom) = (a.1.min(b.1), a.1.max(b.1));
+        return (top..bottom).map(|y| (a.0, y)).collect();
+    }
+    if a.1 == b.1 {
+        return (a.0..b.0).map(|x| (x, a.1)).collect();
+    }
+
+    // Heights are kept multiplied by the line's width so that they stay whole numbers
+    let dx = (b.0 - a.0) as i64;
+    let dy = b.1 as i64 - a.1 as i64;
+    let mut squares = Vec::new();
+    for x in a.0..b.0 {
+        let left = a.1 as i64 * dx + dy * (x - a.0) as i64;
+        let (top, bottom) = (left.min(left + dy), left.max(left + dy));
+
+        // Squares the line crosses inside of, rather than only touching at a corner
+        let first = top.div_euclid(dx);
+        let last = (bottom + dx - 1).div_euclid(dx);
+        squares.extend((first..last).map(|y| (x, y as u32)));
+    }
+
+    squares
+}
+
+// A straight line between two square corners on the same page
+// Corners are indexed like squares, so corner (x, y) is the top-left corner of square (x, y)
+pub struct Line {
+    id: uuid::Uuid,
+    page_square: PageSquare, // The square at the top-left of the line's bounding box
+    origin: Position,        // FreeOnWorld position of the page the line is on
+    start: (u32, u32),
+    end: (u32, u32),
+    thickness: u32, // In pixels
+    color: Color,
+    square_size: u32,
+}
+
+impl Line {
+    pub fn new(
+        page: u32,
+        start: (u32, u32),
+        end: (u32, u32),
+        thickness: u32,
+        color: Color,
+        pages: &Pages,
+    ) -> Result<Line, String> {
+        let squares = pages.page_squares();
+        if start.0 > squares.0 || start.1 > squares.1 || end.0 > squares.0 || end.1 > squares.1 {
+            return Err("Line corner out of bounds.".to_string());
+        }
+        if start == end {
+            return Err("Line must have two different corners.".to_string());
+        }
+
+        // Lines running along the right or bottom edge of the page have no square to belong to
+        let page_square = PageSquare::new(page, Line::top_left(start, end), pages)
+            .map_err(|_| "Lines cannot be drawn along the page's edge.".to_string())?;
+
+        Ok(Line {
+            id: uuid::Uuid::new_v4(),
+            page_square,
+            origin: pages.get_page_position(page),
+            start,
+            end,
+            thickness: thickness.max(1),
+            color,
+            square_size: pages.square_size(),
+        })
+    }
+
+    pub fn from_data(data: &LineData, pages: &Pages) -> Result<Line, String> {
+        Line::new(
+            data.page,
+            data.start,
+            data.end,
+            data.thickness,
+            data.color,
+            pages,
+        )
+    }
+
+    // The square at the top-left of the bounding box of a line between two corners
+    pub fn top_left(start: (u32, u32), end: (u32, u32)) -> (u32, u32) {
+        (start.0.min(end.0), start.1.min(end.1))
+    }
+
+    pub fn ends(&self) -> LineEnds {
+        ends(self.page_square.page, self.start, self.end)
+    }
+
+    // The squares the line takes up, which other marks can't be placed on
+    pub fn squares(&self) -> Vec<(u32, u32)> {
+        crossed_squares(self.start, self.end)
+    }
+
+    // FreeOnWorld position of a corner, placed on the grid line so it matches the page image
+    fn corner_position(&self, corner: (u32, u32)) -> Position {
+        Position::add(
+            self.origin,
+            (corner.0 * self.square_size) as i32 - 1,
+            (corner.1 * self.square_size) as i32 - 1,
+        )
+    }
+}
+
+impl Mark for Line {
+    fn draw(&self, renderer: &mut Renderer) -> Result<(), String> {
+        renderer.draw_line(
+            self.corner_position(self.start),
+            self.corner_position(self.end),
+            self.thickness,
+            self.color,
+        )
+    }
+
+    fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    fn page_square(&self) -> PageSquare {
+        self.page_square
+    }
+
+    // The line's bounding box, which is outlined when it is selected
+    // It only takes up the squares it passes through, see squares
+    fn size(&self) -> (u32, u32) {
+        (
+            self.start.0.abs_diff(self.end.0).max(1),
+            self.start.1.abs_diff(self.end.1).max(1),
+        )
+    }
+
+    fn to_data(&self) -> MarkData {
+        MarkData::Line(LineData {
+            page: self.page_square.page,
+            start: self.start,
+            end: self.end,
+            thickness: self.thickness,
+            color: self.color,
+        })
+    }
+
+    fn restore(&mut self, data: &MarkData, _renderer: &mut Renderer) -> Result<(), String> {
+        match data {
+            MarkData::Line(data) => {
+                self.start = data.start;
+                self.end = data.end;
+                self.thickness = data.thickness;
+                self.color = data.color;
+
+                Ok(())
+            }
+            _ => Err("Cannot restore a line from another kind of mark.".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn straight_lines_take_up_the_squares_below_or_right_of_them() {
+        assert_eq!(
+            crossed_squares((1, 2), (4, 2)),
+            vec![(1, 2), (2, 2), (3, 2)]
+        );
+        assert_eq!(crossed_squares((3, 3), (3, 1)), vec![(3, 1), (3, 2)]);
+    }
+
+    #[test]
+    fn diagonal_lines_take_up_the_squares_they_cross() {
+        assert_eq!(crossed_squares((0, 0), (2, 2)), vec![(0, 0), (1, 1)]);
+        assert_eq!(crossed_squares((0, 2), (2, 0)), vec![(0, 1), (1, 0)]);
+        assert_eq!(crossed_squares((0, 0), (2, 1)), vec![(0, 0), (1, 0)]);
+        assert_eq!(
+            crossed_squares((4, 0), (0, 2)),
+            vec![(0, 1), (1, 1), (2, 0), (3, 0)]
+        );
+    }
+
+    #[test]
+    fn ends_are_the_same_either_way_round() {
+        assert_eq!(ends(1, (4, 0), (0, 2)), ends(1, (0, 2), (4, 0)));
+    }
+}
@@ -0,0 +1,457 @@
+use super::{Area, Mark, TextInput};
+use crate::app::pages::{self, Pages, SQUARE_SIZE};
+use crate::document::{MarkData, MathData};
+use crate::drawable::DrawOptions;
+use crate::math::layout::{self, Item, Layout, Metrics};
+use crate::math::parser::{self, Node, SymbolKind};
+use crate::position::{PageSquare, Position};
+use crate::renderer::Renderer;
+
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::ttf::FontStyle;
+
+// A formula typed in a subset of LaTeX
+// Until the source parses, it is shown as typed in grey
+pub struct MathBox {
+    id: uuid::Uuid,
+    page_square: PageSquare, // Top-left of the squares the formula takes up
+    source: String,
+    caret: usize, // Byte index into the source
+    font_name: String,
+    point: u16,
+    color: Color,
+    layout: Layout,
+    valid: bool, // Whether the source parsed, otherwise it is shown as typed
+    glyph_sizes: Vec<(u32, u32)>, // Sizes of the glyph textures, at full scale
+    rows_above: u32, // Number of rows between the top and the baseline
+    size: (u32, u32), // In squares
+    caret_x: f32, // Width of the formula typed before the caret
+}
+
+impl MathBox {
+    pub fn new(page_square: PageSquare, font_name: String, point: u16, color: Color) -> MathBox {
+        MathBox {
+            id: uuid::Uuid::new_v4(),
+            page_square,
+            source: String::new(),
+            caret: 0,
+            font_name,
+            point,
+            color,
+            layout: Layout {
+                items: vec![],
+                width: 0.0,
+                ascent: 0.0,
+                descent: 0.0,
+            },
+            valid: true,
+            glyph_sizes: vec![],
+            rows_above: 1,
+            size: (1, 1),
+            caret_x: 0.0,
+        }
+    }
+
+    pub fn from_data(
+        data: &MathData,
+        pages: &Pages,
+        renderer: &mut Renderer,
+    ) -> Result<MathBox, String> {
+        let page_square = PageSquare::new(data.page, data.square, pages)?;
+
+        let mut math_box =
+            MathBox::new(page_square, data.font_name.clone(), data.point, data.color);
+        math_box.source = data.source.clone();
+        math_box.caret = math_box.source.len();
+        math_box.update(renderer)?;
+
+        Ok(math_box)
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    // Glyph textures have their baseline this far down from their top, as for lines of text
+    fn glyph_baseline(&self) -> f32 {
+        (pages::line_rows(self.point) * SQUARE_SIZE - 1) as f32
+    }
+
+    // The baseline of the formula, on the grid line below its top rows
+    fn baseline(&self) -> Position {
+        Position::add(
+            self.page_square.position,
+            0,
+            (self.rows_above * SQUARE_SIZE) as i32 - 1,
+        )
+    }
+
+    // Where each glyph texture is drawn, in the order of the layout's glyphs
+    fn glyph_areas(&self) -> Vec<Area> {
+        let baseline = self.baseline();
+        let glyphs = self.layout.items.iter().filter_map(|item| match item {
+            Item::Glyph { x, y, scale, .. } => Some((*x, *y, *scale)),
+            Item::Line { .. } => None,
+        });
+
+        glyphs
+            .zip(&self.glyph_sizes)
+            .map(|((x, y, scale), (w, h))| {
+                let position = Position::add(
+                    baseline,
+                    x.round() as i32,
+                    (y - scale * self.glyph_baseline()).round() as i32,
+                );
+                let size = (
+                    (*w as f32 * scale).round() as u32,
+                    (*h as f32 * scale).round() as u32,
+                );
+                (position, size)
+            })
+            .collect()
+    }
+
+    fn font_style(italic: bool) -> FontStyle {
+        if italic {
+            FontStyle::ITALIC
+        } else {
+            FontStyle::NORMAL
+        }
+    }
+
+    // Lays out some source, or shows it as typed if it doesn't parse, returning whether it parsed
+    fn lay_out(&self, source: &str, renderer: &Renderer) -> Result<(Layout, bool), String> {
+        let parsed = parser::parse(source);
+        let valid = parsed.is_ok();
+        let node = parsed.unwrap_or_else(|_| Node::Symbol(source.to_string(), SymbolKind::Upright));
+
+        let (ascent, descent) =
+            renderer.font_extents(&self.font_name, FontStyle::NORMAL, self.point)?;
+        let advance = |text: &str, italic: bool| {
+            renderer
+                .text_size(
+                    text,
+                    &self.font_name,
+                    MathBox::font_style(italic),
+                    self.point,
+                )
+                .map(|size| size.0 as f32)
+                .unwrap_or(0.0)
+        };
+        let layout = layout::layout(
+            &node,
+            &Metrics {
+                em: self.point as f32,
+                ascent: ascent as f32,
+                descent: descent as f32,
+                advance: &advance,
+            },
+        );
+
+        Ok((layout, valid))
+    }
+
+    // How far along the formula the caret would be if it were at an index of the source
+    fn offset_at(&self, index: usize, renderer: &Renderer) -> Result<f32, String> {
+        let before = &self.source[..index];
+        if before.is_empty() {
+            Ok(0.0)
+        } else if self.valid {
+            Ok(self.lay_out(before, renderer)?.0.width)
+        } else {
+            let size =
+                renderer.text_size(before, &self.font_name, FontStyle::NORMAL, self.point)?;
+            Ok(size.0 as f32)
+        }
+    }
+
+    // Lays out the source again and renders each of its glyphs
+    pub fn update(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        let (layout, valid) = self.lay_out(&self.source, renderer)?;
+        self.layout = layout;
+        self.valid = valid;
+        self.caret_x = self.offset_at(self.caret, renderer)?;
+
+        let color = if valid { self.color } else { Color::GRAY };
+        self.glyph_sizes.clear();
+        for item in &self.layout.items {
+            if let Item::Glyph { text, italic, .. } = item {
+                // Empty text can't be rendered, but still needs a texture to keep indices aligned
+                let text = if text.is_empty() {
+                    " ".to_string()
+                } else {
+                    text.clone()
+                };
+                let size = renderer.create_text(
+                    self.id,
+                    Some(self.glyph_sizes.len()),
+                    &text,
+                    &self.font_name,
+                    MathBox::font_style(*italic),
+                    self.point,
+                    color,
+                )?;
+                self.glyph_sizes.push(size);
+            }
+        }
+
+        // The baseline sits on a grid line, with whole rows above and below it
+        let square = SQUARE_SIZE as f32;
+        self.rows_above = ((self.layout.ascent / square).ceil() as u32).max(1);
+        self.size = (
+            ((self.layout.width / square).ceil() as u32).max(1),
+            self.rows_above + (self.layout.descent / square).ceil() as u32,
+        );
+
+        Ok(())
+    }
+}
+
+impl Mark for MathBox {
+    fn draw(&self, renderer: &mut Renderer) -> Result<(), String> {
+        // Show where the formula will go before anything has been typed
+        if self.source.is_empty() {
+            return renderer.draw_rect(
+                self.page_square.position,
+                1,
+                (SQUARE_SIZE - 1, SQUARE_SIZE - 1),
+                Color::GRAY,
+            );
+        }
+
+        for (glyph, (position, size)) in self.glyph_areas().into_iter().enumerate() {
+            let options = DrawOptions {
+                src: None,
+                position,
+                size,
+                rotation: None,
+                flip_h: false,
+                flip_v: false,
+            };
+            renderer.draw_texture(self.id, glyph, options)?;
+        }
+
+        let baseline = self.baseline();
+        let at = |x: f32, y: f32| Position::add(baseline, x.round() as i32, y.round() as i32);
+        for item in &self.layout.items {
+            if let Item::Line {
+                from,
+                to,
+                thickness,
+            } = item
+            {
+                renderer.draw_line(
+                    at(from.0, from.1),
+                    at(to.0, to.1),
+                    thickness.round() as u32,
+                    self.color,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    fn page_square(&self) -> PageSquare {
+        self.page_square
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    fn to_data(&self) -> MarkData {
+        MarkData::Math(MathData {
+            page: self.page_square.page,
+            square: self.page_square.square,
+            font_name: self.font_name.clone(),
+            point: self.point,
+            color: self.color,
+            source: self.source.clone(),
+        })
+    }
+
+    fn restore(&mut self, data: &MarkData, renderer: &mut Renderer) -> Result<(), String> {
+        match data {
+            MarkData::Math(data) => {
+                self.font_name = data.font_name.clone();
+                self.point = data.point;
+                self.color = data.color;
+                self.source = data.source.clone();
+                self.caret = self.source.len();
+
+                self.update(renderer)
+            }
+            _ => Err("Cannot restore a math box from another kind of mark.".to_string()),
+        }
+    }
+}
+
+impl TextInput for MathBox {
+    fn push_str(&mut self, string: &str, renderer: &mut Renderer) -> Result<(), String> {
+        self.source.insert_str(self.caret, string);
+        self.caret += string.len();
+        self.update(renderer)
+    }
+
+    fn pop(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        if let Some((index, _)) = self.source[..self.caret].char_indices().last() {
+            self.source.remove(index);
+            self.caret = index;
+        }
+        self.update(renderer)
+    }
+
+    // Formulas are a single line of source, rows of matrices are separated with \\
+    fn new_line(&mut self, _renderer: &mut Renderer) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn delete(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        if self.caret < self.source.len() {
+            self.source.remove(self.caret);
+        }
+        self.update(renderer)
+    }
+
+    // The caret moves through the source, and is shown after the part of the formula before it
+    fn move_caret(
+        &mut self,
+        keycode: Keycode,
+        _select: bool,
+        renderer: &Renderer,
+    ) -> Result<(), String> {
+        self.caret = match keycode {
+            Keycode::Left => self.source[..self.caret]
+                .char_indices()
+                .last()
+                .map_or(0, |(i, _)| i),
+            Keycode::Right => self.source[self.caret..]
+                .chars()
+                .next()
+                .map_or(self.caret, |c| self.caret + c.len_utf8()),
+            Keycode::Home => 0,
+            Keycode::End => self.source.len(),
+            _ => return Ok(()),
+        };
+        self.caret_x = self.offset_at(self.caret, renderer)?;
+
+        Ok(())
+    }
+
+    // Clicking puts the caret where the part of the formula before it ends closest to the click
+    fn place_caret(
+        &mut self,
+        x: i32,
+        _y: i32,
+        _select: bool,
+        renderer: &Renderer,
+    ) -> Result<(), String> {
+        let mut closest = (0, 0.0);
+        for index in self.source.char_indices().map(|(i, _)| i).skip(1) {
+            let offset = self.offset_at(index, renderer)?;
+            if (offset - x as f32).abs() < (closest.1 - x as f32).abs() {
+                closest = (index, offset);
+            }
+        }
+        if (self.layout.width - x as f32).abs() < (closest.1 - x as f32).abs() {
+            closest = (self.source.len(), self.layout.width);
+        }
+        self.caret = closest.0;
+        self.caret_x = closest.1;
+
+        Ok(())
+    }
+
+    fn caret_position(&self, _renderer: &Renderer) -> Result<Option<Position>, String> {
+        Ok(Some(Position::add(
+            self.page_square.position,
+            self.caret_x.round() as i32,
+            0,
+        )))
+    }
+
+    // The caret runs the full height of the formula
+    fn line_height(&self) -> u32 {
+        self.size.1 * SQUARE_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::with_renderer;
+
+    fn math_box(point: u16, source: &str, renderer: &mut Renderer) -> MathBox {
+        let pages = Pages::new((29, 42), renderer).unwrap();
+        let page_square = PageSquare::new(0, (2, 2), &pages).unwrap();
+        let mut math_box = MathBox::new(page_square, "NotoSerif".to_string(), point, Color::BLACK);
+        math_box.push_str(source, renderer).unwrap();
+        math_box
+    }
+
+    #[test]
+    fn glyphs_spanning_two_rows_sit_on_the_baseline() {
+        with_renderer(|_, renderer| {
+            let math_box = math_box(60, "x", renderer);
+            assert_eq!(pages::line_rows(60), 2);
+
+            let (position, size) = math_box.glyph_areas()[0];
+            let top = math_box.page_square.position.y();
+            let baseline = top + (math_box.rows_above * SQUARE_SIZE) as i32 - 1;
+            assert_eq!(position.y() + (2 * SQUARE_SIZE) as i32 - 1, baseline);
+            // The glyph stays within the squares the formula takes up
+            assert!(position.y() >= top);
+            assert!(position.y() + size.1 as i32 <= top + (math_box.size.1 * SQUARE_SIZE) as i32);
+        });
+    }
+
+    #[test]
+    fn typing_and_deleting_happen_at_the_caret() {
+        with_renderer(|_, renderer| {
+            let mut math_box = math_box(30, "a+c", renderer);
+            let end = math_box.caret_position(renderer).unwrap().unwrap();
+
+            math_box.move_caret(Keycode::Left, false, renderer).unwrap();
+            let before_c = math_box.caret_position(renderer).unwrap().unwrap();
+            assert!(before_c.x() < end.x());
+
+            math_box.push_str("b", renderer).unwrap();
+            assert_eq!(math_box.source(), "a+bc");
+            math_box.pop(renderer).unwrap();
+            math_box.pop(renderer).unwrap();
+            assert_eq!(math_box.source(), "ac");
+
+            math_box.move_caret(Keycode::Home, false, renderer).unwrap();
+            math_box.delete(renderer).unwrap();
+            assert_eq!(math_box.source(), "c");
+            let start = math_box.caret_position(renderer).unwrap().unwrap();
+            assert_eq!(start.x(), math_box.page_square.position.x());
+
+            math_box.move_caret(Keycode::End, false, renderer).unwrap();
+            math_box.push_str("^2", renderer).unwrap();
+            assert_eq!(math_box.source(), "c^2");
+        });
+    }
+
+    #[test]
+    fn clicking_puts_the_caret_between_glyphs() {
+        with_renderer(|_, renderer| {
+            let mut math_box = math_box(30, "abc", renderer);
+            let width = math_box.layout.width;
+
+            math_box.place_caret(-5, 0, false, renderer).unwrap();
+            math_box.push_str("x", renderer).unwrap();
+            math_box
+                .place_caret(width as i32 + 50, 0, false, renderer)
+                .unwrap();
+            math_box.push_str("y", renderer).unwrap();
+            assert_eq!(math_box.source(), "xabcy");
+        });
+    }
+}
@@ -1,10 +1,136 @@
+pub mod bullet_list;
+pub mod codeblock;
+pub mod image;
+pub mod line;
+pub mod mathbox;
 pub mod textbox;
 
+use crate::app::pages::SQUARE_SIZE;
+use crate::document::MarkData;
 use crate::drawable::Drawable;
+use crate::position::{PageSquare, Position};
 use crate::renderer::Renderer;
 
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::pixels::Color;
+
+use uuid::Uuid;
+
+// An area of a page, as its top-left position and its size in pixels
+pub type Area = (Position, (u32, u32));
+
 pub trait Mark {
     fn draw(&self, renderer: &mut Renderer) -> Result<(), String>;
+
+    // The id that the mark's textures are stored under
+    fn id(&self) -> Uuid;
+
+    // The square that the mark is stored under
+    fn page_square(&self) -> PageSquare;
+
+    // The number of squares the mark covers, across and down from its page square
+    fn size(&self) -> (u32, u32);
+
+    // Removes all of the mark's textures from the renderer
+    fn free_textures(&self, renderer: &mut Renderer) {
+        renderer.remove_textures(self.id())
+    }
+
+    // The saveable state of the mark, used to write documents
+    fn to_data(&self) -> MarkData;
+
+    // Returns the mark to a previously saved state of the same kind, without moving it
+    fn restore(&mut self, data: &MarkData, renderer: &mut Renderer) -> Result<(), String>;
+}
+
+// Marks that can be typed into with the text tool
+pub trait TextInput: Mark {
+    fn push_str(&mut self, string: &str, renderer: &mut Renderer) -> Result<(), String>;
+
+    fn pop(&mut self, renderer: &mut Renderer) -> Result<(), String>;
+
+    fn new_line(&mut self, renderer: &mut Renderer) -> Result<(), String>;
+
+    // Removes what is after the caret, for marks that have one
+    fn delete(&mut self, _renderer: &mut Renderer) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Moves the caret with the arrow keys, Home or End, for marks that have one
+    // When selecting, the selection is extended to where the caret moves to
+    fn move_caret(
+        &mut self,
+        _keycode: Keycode,
+        _select: bool,
+        _renderer: &Renderer,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Moves the caret to a point in pixels from the mark's top-left, as when clicking on it
+    fn place_caret(
+        &mut self,
+        _x: i32,
+        _y: i32,
+        _select: bool,
+        _renderer: &Renderer,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Where the caret is drawn, at the top of the line it's on
+    fn caret_position(&self, _renderer: &Renderer) -> Result<Option<Position>, String> {
+        Ok(None)
+    }
+
+    // The height of the line the caret is on, which is taller for larger text
+    fn line_height(&self) -> u32 {
+        SQUARE_SIZE
+    }
+
+    fn selected_text(&self) -> Option<String> {
+        None
+    }
+
+    // Removes the selected text, returning whether anything was selected
+    fn delete_selection(&mut self, _renderer: &mut Renderer) -> Result<bool, String> {
+        Ok(false)
+    }
+
+    // The areas to highlight to show what is selected
+    fn selection_rects(&self, _renderer: &Renderer) -> Result<Vec<Area>, String> {
+        Ok(vec![])
+    }
+
+    // Changes the font and point size of all of the mark's text, for marks that can change them
+    fn set_text_font(
+        &mut self,
+        _font_name: &str,
+        _point: u16,
+        _renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Changes the color of the selected text, or of all of the mark's text when all is set
+    fn set_text_color(
+        &mut self,
+        _color: Color,
+        _all: bool,
+        _renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Any other key press, for marks with their own key bindings
+    fn handle_key(
+        &mut self,
+        _keycode: Keycode,
+        _keymod: Mod,
+        _renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        Ok(())
+    }
 }
 
 impl Drawable for dyn Mark {
@@ -1,16 +1,186 @@
-use super::Mark;
+use super::{Area, Mark, TextInput};
+use crate::app::pages::{line_rows, Pages, SQUARE_SIZE};
+use crate::document::{MarkData, TextData};
 use crate::drawable::DrawOptions;
 use crate::position::{PageSquare, Position};
-use crate::renderer::Renderer;
+use crate::renderer::{Renderer, TextSpan};
 
+use sdl2::keyboard::{Keycode, Mod};
 use sdl2::pixels::Color;
 use sdl2::ttf::FontStyle;
 
+// How a line of text ends
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineBreak {
+    Hard,   // Enter was pressed, or it's the last line
+    Soft,   // The paragraph was wrapped onto the next line between words
+    Hyphen, // A word too long for a line was split, with a hyphen added to the end of the line
+}
+
+impl LineBreak {
+    // Name of the record a line with this break is saved as
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineBreak::Hard => "line",
+            LineBreak::Soft => "wrap",
+            LineBreak::Hyphen => "hyphen",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<LineBreak> {
+        match name {
+            "line" => Some(LineBreak::Hard),
+            "wrap" => Some(LineBreak::Soft),
+            "hyphen" => Some(LineBreak::Hyphen),
+            _ => None,
+        }
+    }
+}
+
+// Joins wrapped lines back into the paragraphs they were wrapped from
+pub fn paragraphs(lines: &[String], breaks: &[LineBreak]) -> Vec<String> {
+    let mut paragraphs = vec![String::new()];
+
+    for (i, line) in lines.iter().enumerate() {
+        let paragraph = paragraphs.last_mut().unwrap();
+        match breaks.get(i).copied().unwrap_or(LineBreak::Hard) {
+            LineBreak::Hard => {
+                paragraph.push_str(line);
+                if i + 1 < lines.len() {
+                    paragraphs.push(String::new());
+                }
+            }
+            LineBreak::Soft => paragraph.push_str(line),
+            LineBreak::Hyphen => paragraph.push_str(line.strip_suffix('-').unwrap_or(line)),
+        }
+    }
+
+    paragraphs
+}
+
+// How a piece of text is drawn, in its text box's font and point size
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextStyle {
+    pub font_style: FontStyle,
+    pub color: Color,
+}
+
+// A run of a line drawn in another style than its text box's own, between two byte indices
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StyleSpan {
+    pub start: usize,
+    pub end: usize,
+    pub style: TextStyle,
+}
+
+// The colors that Ctrl+K cycles text through
+pub const TEXT_COLORS: [Color; 5] = [
+    Color::BLACK,
+    Color::RGB(200, 30, 30),
+    Color::RGB(30, 90, 200),
+    Color::RGB(20, 130, 40),
+    Color::RGB(210, 110, 0),
+];
+
+// Runs of bytes in the same style, as their start, end and style
+fn runs(styles: &[TextStyle]) -> Vec<(usize, usize, TextStyle)> {
+    let mut runs: Vec<(usize, usize, TextStyle)> = Vec::new();
+    for (i, style) in styles.iter().enumerate() {
+        match runs.last_mut() {
+            Some(run) if run.2 == *style => run.1 = i + 1,
+            _ => runs.push((i, i + 1, *style)),
+        }
+    }
+
+    runs
+}
+
+// The runs of a line that aren't in the default style
+fn spans(styles: &[TextStyle], default: TextStyle) -> Vec<StyleSpan> {
+    runs(styles)
+        .into_iter()
+        .filter(|run| run.2 != default)
+        .map(|(start, end, style)| StyleSpan { start, end, style })
+        .collect()
+}
+
+// The style of each byte of a line, which is the default one outside of its spans
+fn from_spans(length: usize, spans: &[StyleSpan], default: TextStyle) -> Vec<TextStyle> {
+    let mut styles = vec![default; length];
+    for span in spans {
+        let end = span.end.min(length);
+        styles[span.start.min(end)..end].fill(span.style);
+    }
+
+    styles
+}
+
+// A line's runs of text in the same style, as their start, end and style, for exporters
+pub fn line_runs(
+    length: usize,
+    spans: &[StyleSpan],
+    default: TextStyle,
+) -> Vec<(usize, usize, TextStyle)> {
+    runs(&from_spans(length, spans, default))
+}
+
+// Joins the spans of wrapped lines into spans over the paragraphs they were wrapped from
+// Spans that carry on from one line to the next become one
+pub fn paragraph_spans(
+    lines: &[String],
+    breaks: &[LineBreak],
+    spans: &[Vec<StyleSpan>],
+) -> Vec<Vec<StyleSpan>> {
+    let mut paragraphs: Vec<Vec<StyleSpan>> = vec![Vec::new()];
+    let mut offset = 0;
+
+    for (i, line) in lines.iter().enumerate() {
+        let line_break = breaks.get(i).copied().unwrap_or(LineBreak::Hard);
+        let length = match line_break {
+            LineBreak::Hyphen => line.strip_suffix('-').unwrap_or(line).len(),
+            _ => line.len(),
+        };
+
+        let paragraph = paragraphs.last_mut().unwrap();
+        for span in spans.get(i).map_or(&[][..], Vec::as_slice) {
+            let (start, end) = (
+                offset + span.start.min(length),
+                offset + span.end.min(length),
+            );
+            match paragraph.last_mut() {
+                Some(last) if last.end == start && last.style == span.style => last.end = end,
+                _ if start < end => paragraph.push(StyleSpan {
+                    start,
+                    end,
+                    style: span.style,
+                }),
+                _ => (),
+            }
+        }
+
+        offset += length;
+        if line_break == LineBreak::Hard && i + 1 < lines.len() {
+            paragraphs.push(Vec::new());
+            offset = 0;
+        }
+    }
+
+    paragraphs
+}
+
+// Text wrapped to the text box's width, in lines that end in line breaks
+// Lines are only ever broken by wrapping, each paragraph keeps its own lines
+// Runs of text can be bold, italic or colored, which is kept as the style of every byte
 pub struct TextBox {
     id: uuid::Uuid,
     page_square: PageSquare, // Position on page
     lines: Vec<String>,
     line_sizes: Vec<(u32, u32)>,
+    breaks: Vec<LineBreak>,         // How each line ends
+    styles: Vec<Vec<TextStyle>>,    // The style of each byte of each line
+    style: TextStyle,               // The style that typed text is given
+    caret: (usize, usize),          // Line, and byte index into it, that text is inserted at
+    anchor: Option<(usize, usize)>, // Where the selection started, the caret being its other end
     font_name: String,
     font_style: FontStyle,
     point: u16,
@@ -32,6 +202,11 @@ impl TextBox {
             page_square,
             line_sizes: vec![],
             lines: vec![],
+            breaks: vec![],
+            styles: vec![],
+            style: TextStyle { font_style, color },
+            caret: (0, 0),
+            anchor: None,
             font_name,
             font_style,
             point,
@@ -40,75 +215,194 @@ impl TextBox {
         }
     }
 
-    pub fn push_str(&mut self, string: &str, renderer: &mut Renderer) -> Result<(), String> {
-        if self.lines.is_empty() {
-            self.lines.push("".to_string());
-            self.line_sizes.push((0,0));
-        }
-
-        let last_line = self.lines.len() - 1;
-        let mut line = self.lines[last_line].clone();
-        line.push_str(string);
-
-        if renderer.text_overflow(
-            &line,
-            &self.font_name,
-            self.font_style,
-            self.point,
-            self.max_width,
-        )? {
-            self.lines.push(string.to_string());
-            self.line_sizes.push((0, 0));
-        } else {
-            self.lines[last_line] = line;
-        }
+    // Rebuilds a saved text box, rendering all of its lines
+    // Lines are kept as they were saved rather than wrapped again, so that they match exports
+    pub fn from_data(
+        data: &TextData,
+        pages: &Pages,
+        renderer: &mut Renderer,
+    ) -> Result<TextBox, String> {
+        let page_square = PageSquare::new(data.page, data.square, pages)?;
+
+        let mut text_box = TextBox::new(
+            page_square,
+            data.font_name.clone(),
+            data.font_style,
+            data.point,
+            data.color,
+            data.max_width,
+        );
+        text_box.set_lines(
+            data.lines.clone(),
+            data.breaks.clone(),
+            &data.spans,
+            renderer,
+        )?;
 
-        self.update(renderer)
+        Ok(text_box)
     }
 
-    pub fn pop(&mut self, renderer: &mut Renderer) -> Result<(), String> {
-        if self.lines.is_empty() {
+    pub fn max_width(&self) -> u32 {
+        self.max_width
+    }
+
+    // Wraps every paragraph again to fit the new width
+    pub fn set_max_width(&mut self, max_width: u32, renderer: &mut Renderer) -> Result<(), String> {
+        if max_width == self.max_width {
             return Ok(());
         }
+        self.max_width = max_width;
 
-        let last_line = self.lines.len() - 1;
-        self.lines[last_line].pop();
+        self.rewrap(renderer)
+    }
 
-        if self.lines[last_line].is_empty() {
-            self.lines.pop();
-            self.line_sizes.pop();
+    // Changes the font and point size of all of the text, wrapping it again to fit
+    pub fn set_font(
+        &mut self,
+        font_name: &str,
+        point: u16,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        if font_name == self.font_name && point == self.point {
+            return Ok(());
         }
+        self.font_name = font_name.to_string();
+        self.point = point;
 
-        self.update(renderer)
+        self.rewrap(renderer)
     }
 
-    pub fn new_line(&mut self) {
-        self.lines.push("".to_string());
-        self.line_sizes.push((0, 0));
-    }
-
-    pub fn update(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+    // Wraps every paragraph again, keeping the caret and selection where they were in the text
+    fn rewrap(&mut self, renderer: &mut Renderer) -> Result<(), String> {
         if self.lines.is_empty() {
             return Ok(());
         }
-        let last_line = self.line_sizes.len() - 1;
 
-        self.line_sizes[last_line] = renderer.create_text(
-            self.id,
-            Some(last_line),
-            &self.lines[last_line],
-            &self.font_name,
-            self.font_style,
-            self.point,
-            self.color,
+        let caret = self.caret_in_paragraphs();
+        let anchor = self.anchor.map(|anchor| self.in_paragraphs(anchor));
+        let paragraphs = paragraphs(&self.lines, &self.breaks);
+        self.replace_paragraphs(
+            0,
+            self.lines.len() - 1,
+            &paragraphs,
+            &self.paragraph_styles(),
+            caret,
+            renderer,
         )?;
+        self.caret = self.in_lines(caret);
+        self.anchor = anchor.map(|anchor| self.in_lines(anchor));
 
         Ok(())
     }
-}
 
-impl Mark for TextBox {
-    fn draw(&self, renderer: &mut Renderer) -> Result<(), String> {
+    // The height of each line in pixels, a whole number of squares
+    pub fn line_height(&self) -> u32 {
+        line_rows(self.point) * SQUARE_SIZE
+    }
+
+    // Number of lines taken up on the page, an empty text box still takes up one
+    pub fn line_count(&self) -> usize {
+        self.lines.len().max(1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.iter().all(|line| line.is_empty())
+    }
+
+    // The width of the widest line, in pixels
+    pub fn width(&self) -> u32 {
+        self.line_sizes.iter().map(|size| size.0).max().unwrap_or(0)
+    }
+
+    pub fn lines(&self) -> &Vec<String> {
+        &self.lines
+    }
+
+    pub fn breaks(&self) -> &Vec<LineBreak> {
+        &self.breaks
+    }
+
+    // The runs of each line in other styles than the text box's own
+    pub fn spans(&self) -> Vec<Vec<StyleSpan>> {
+        let default = self.default_style();
+        self.styles
+            .iter()
+            .map(|styles| spans(styles, default))
+            .collect()
+    }
+
+    fn default_style(&self) -> TextStyle {
+        TextStyle {
+            font_style: self.font_style,
+            color: self.color,
+        }
+    }
+
+    // Replaces all of the text, rendering every line again
+    // Lines without a break are taken to end in hard breaks, and the caret is left at the end
+    pub fn set_lines(
+        &mut self,
+        lines: Vec<String>,
+        mut breaks: Vec<LineBreak>,
+        spans: &[Vec<StyleSpan>],
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        breaks.resize(lines.len(), LineBreak::Hard);
+        if let Some(last) = breaks.last_mut() {
+            *last = LineBreak::Hard;
+        }
+        for (line, line_break) in lines.iter().zip(breaks.iter_mut()) {
+            if *line_break == LineBreak::Hyphen && !line.ends_with('-') {
+                *line_break = LineBreak::Soft;
+            }
+        }
+
+        let default = self.default_style();
+        self.styles = lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                from_spans(line.len(), spans.get(i).map_or(&[], Vec::as_slice), default)
+            })
+            .collect();
+        self.line_sizes = vec![(0, 0); lines.len()];
+        self.breaks = breaks;
+        self.lines = lines;
+        self.caret = match self.lines.len() {
+            0 => (0, 0),
+            n => (n - 1, self.line_end(n - 1)),
+        };
+        self.anchor = None;
+        self.style = default;
+        self.pick_up_style();
+
+        self.update_all(renderer)
+    }
+
+    pub fn caret(&self) -> (usize, usize) {
+        self.caret
+    }
+
+    // The start and end of the selected text, in order, if any is selected
+    pub fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        match self.anchor {
+            Some(anchor) if anchor < self.caret => Some((anchor, self.caret)),
+            Some(anchor) if anchor > self.caret => Some((self.caret, anchor)),
+            _ => None,
+        }
+    }
+
+    // Starts or stops selecting, with the caret where it is now as the selection's other end
+    fn set_selecting(&mut self, select: bool) {
+        if !select {
+            self.anchor = None;
+        } else if self.anchor.is_none() {
+            self.anchor = Some(self.caret);
+        }
+    }
+
+    // Draws the text with its first line at position, rather than at its own page square
+    pub fn draw_at(&self, position: Position, renderer: &mut Renderer) -> Result<(), String> {
         for (i, size) in self.line_sizes.iter().enumerate() {
             if *size == (0, 0) {
                 continue;
@@ -116,11 +410,7 @@ impl Mark for TextBox {
 
             let options = DrawOptions {
                 src: None,
-                position: Position::add(
-                    self.page_square.position,
-                    0,
-                    i as i32 * crate::app::pages::SQUARE_SIZE as i32,
-                ),
+                position: Position::add(position, 0, (i as u32 * self.line_height()) as i32),
                 size: *size,
                 rotation: None,
                 flip_h: false,
@@ -132,4 +422,1135 @@ impl Mark for TextBox {
 
         Ok(())
     }
+
+    // Recreates the textures of every line
+    pub fn update_all(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        self.update_lines(0, self.lines.len(), renderer)
+    }
+
+    // Recreates the textures of the lines in first..end
+    fn update_lines(
+        &mut self,
+        first: usize,
+        end: usize,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        self.line_sizes.resize(self.lines.len(), (0, 0));
+
+        for i in first..end.min(self.lines.len()) {
+            // Spaces that a line was wrapped after aren't drawn, so they don't add to its width
+            // Empty lines are rendered as a space so that texture indices stay aligned with lines
+            let text = self.lines[i].trim_end();
+            let spans: Vec<TextSpan> = match text {
+                "" => vec![TextSpan {
+                    text: " ".to_string(),
+                    font_style: self.font_style,
+                    color: self.color,
+                }],
+                text => runs(&self.styles[i][..text.len()])
+                    .into_iter()
+                    .map(|(start, end, style)| TextSpan {
+                        text: text[start..end].to_string(),
+                        font_style: style.font_style,
+                        color: style.color,
+                    })
+                    .collect(),
+            };
+
+            self.line_sizes[i] = renderer.create_text_spans(
+                self.id,
+                Some(i),
+                &spans,
+                &self.font_name,
+                self.point,
+                None,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // Byte index of the character before index in a line, or 0
+    fn prev_boundary(line: &str, index: usize) -> usize {
+        line[..index].char_indices().last().map_or(0, |(i, _)| i)
+    }
+
+    // Byte index of the character after index in a line, or the line's length
+    fn next_boundary(line: &str, index: usize) -> usize {
+        line[index..]
+            .chars()
+            .next()
+            .map_or(index, |c| index + c.len_utf8())
+    }
+
+    // The furthest the caret can go along a line, which is before any hyphen added by wrapping
+    fn line_end(&self, line: usize) -> usize {
+        match self.breaks[line] {
+            LineBreak::Hyphen => self.lines[line].len() - 1,
+            _ => self.lines[line].len(),
+        }
+    }
+
+    // The first and last lines of the paragraph that a line is part of
+    fn paragraph_bounds(&self, line: usize) -> (usize, usize) {
+        let mut first = line;
+        while first > 0 && self.breaks[first - 1] != LineBreak::Hard {
+            first -= 1;
+        }
+        let mut last = line;
+        while self.breaks[last] != LineBreak::Hard {
+            last += 1;
+        }
+
+        (first, last)
+    }
+
+    // The style of each byte of each paragraph, like paragraphs() for the text
+    fn paragraph_styles(&self) -> Vec<Vec<TextStyle>> {
+        let mut paragraphs = vec![Vec::new()];
+
+        for (i, styles) in self.styles.iter().enumerate() {
+            paragraphs
+                .last_mut()
+                .unwrap()
+                .extend_from_slice(&styles[..self.line_end(i)]);
+            if self.breaks[i] == LineBreak::Hard && i + 1 < self.lines.len() {
+                paragraphs.push(Vec::new());
+            }
+        }
+
+        paragraphs
+    }
+
+    // The line and byte index into it of a byte index into a paragraph
+    fn in_lines(&self, (paragraph, mut offset): (usize, usize)) -> (usize, usize) {
+        let mut current = 0;
+
+        for line in 0..self.lines.len() {
+            if current == paragraph {
+                let end = self.line_end(line);
+                if offset < end || self.breaks[line] == LineBreak::Hard {
+                    return (line, offset.min(end));
+                }
+                offset -= end;
+            }
+            if self.breaks[line] == LineBreak::Hard {
+                current += 1;
+            }
+        }
+
+        (self.lines.len().saturating_sub(1), 0)
+    }
+
+    // The index of the caret's paragraph and its byte index into the paragraph's text
+    fn caret_in_paragraphs(&self) -> (usize, usize) {
+        self.in_paragraphs(self.caret)
+    }
+
+    // The paragraph that a line and index into it are in, and the byte index into its text
+    fn in_paragraphs(&self, (line, index): (usize, usize)) -> (usize, usize) {
+        let mut paragraph = 0;
+        let mut offset = 0;
+
+        for i in 0..line {
+            match self.breaks[i] {
+                LineBreak::Hard => {
+                    paragraph += 1;
+                    offset = 0;
+                }
+                _ => offset += self.line_end(i),
+            }
+        }
+
+        (paragraph, offset + index)
+    }
+
+    // Whether text fits on one line, not counting the spaces it ends with
+    fn fits(&self, text: &str, styles: &[TextStyle], renderer: &Renderer) -> Result<bool, String> {
+        let text = text.trim_end();
+        Ok(self.styled_width(text, &styles[..text.len()], renderer)? <= self.max_width)
+    }
+
+    // Whether text fits on one line with a hyphen after it, in the style of its last character
+    fn fits_hyphenated(
+        &self,
+        text: &str,
+        styles: &[TextStyle],
+        renderer: &Renderer,
+    ) -> Result<bool, String> {
+        let hyphen = styles.last().copied().unwrap_or(self.style);
+        let width = self.styled_width(text, styles, renderer)?
+            + self.styled_width("-", &[hyphen], renderer)?;
+        Ok(width <= self.max_width)
+    }
+
+    // Splits a paragraph into lines that fit within max_width, breaking between words
+    // Words too long for a line of their own are split, with a hyphen at the end of each part
+    // Each line comes with its break and the number of bytes of the paragraph it holds
+    fn wrap(
+        &self,
+        paragraph: &str,
+        styles: &[TextStyle],
+        renderer: &Renderer,
+    ) -> Result<Vec<(String, LineBreak, usize)>, String> {
+        let mut lines = Vec::new();
+        // The line being filled is paragraph[start..end]
+        let mut start = 0;
+        let mut end = 0;
+
+        // The end of each word along with the spaces after it
+        let mut words = Vec::new();
+        for (i, c) in paragraph.char_indices() {
+            if i > 0 && !c.is_whitespace() && paragraph[..i].ends_with(char::is_whitespace) {
+                words.push(i);
+            }
+        }
+        words.push(paragraph.len());
+
+        for word_end in words {
+            if self.fits(
+                &paragraph[start..word_end],
+                &styles[start..word_end],
+                renderer,
+            )? {
+                end = word_end;
+                continue;
+            }
+            if end > start {
+                lines.push((
+                    paragraph[start..end].to_string(),
+                    LineBreak::Soft,
+                    end - start,
+                ));
+                start = end;
+            }
+
+            while !self.fits(
+                &paragraph[start..word_end],
+                &styles[start..word_end],
+                renderer,
+            )? {
+                // The most of the word that fits with a hyphen after it, but at least a character
+                let mut split = TextBox::next_boundary(paragraph, start);
+                let mut next = TextBox::next_boundary(paragraph, split);
+                while next < word_end
+                    && self.fits_hyphenated(
+                        &paragraph[start..next],
+                        &styles[start..next],
+                        renderer,
+                    )?
+                {
+                    split = next;
+                    next = TextBox::next_boundary(paragraph, next);
+                }
+                if split == word_end {
+                    break;
+                }
+                // Words are split after a hyphen they already have if one fits
+                if let Some(hyphen) = paragraph[start..split].rfind('-') {
+                    split = start + hyphen + 1;
+                }
+
+                // Words that already have a hyphen where they are split don't get another
+                let part = &paragraph[start..split];
+                if part.ends_with('-') {
+                    lines.push((part.to_string(), LineBreak::Soft, split - start));
+                } else {
+                    lines.push((format!("{}-", part), LineBreak::Hyphen, split - start));
+                }
+                start = split;
+            }
+            end = word_end;
+        }
+
+        lines.push((paragraph[start..].to_string(), LineBreak::Hard, end - start));
+
+        Ok(lines)
+    }
+
+    // Replaces the lines first..=last with paragraphs, wrapping each of them
+    // Each paragraph comes with the style of each of its bytes
+    // The caret is put in the given paragraph of the new ones, at a byte index into its text
+    fn replace_paragraphs(
+        &mut self,
+        first: usize,
+        last: usize,
+        paragraphs: &[String],
+        styles: &[Vec<TextStyle>],
+        caret: (usize, usize),
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        let mut lines = Vec::new();
+        let mut breaks = Vec::new();
+        let mut line_styles = Vec::new();
+        let mut caret_at = None;
+
+        for (i, paragraph) in paragraphs.iter().enumerate() {
+            let mut offset = 0;
+            for (line, line_break, length) in self.wrap(paragraph, &styles[i], renderer)? {
+                // The caret goes at the start of a line rather than the end of the one before
+                if i == caret.0 && caret_at.is_none() {
+                    let ends_here = line_break == LineBreak::Hard;
+                    if caret.1 < offset + length || ends_here {
+                        caret_at = Some((first + lines.len(), caret.1 - offset));
+                    }
+                }
+                // Added hyphens take the style of the character before them
+                let mut line_style = styles[i][offset..offset + length].to_vec();
+                if line_break == LineBreak::Hyphen {
+                    line_style.push(line_style.last().copied().unwrap_or(self.style));
+                }
+
+                offset += length;
+                lines.push(line);
+                breaks.push(line_break);
+                line_styles.push(line_style);
+            }
+        }
+
+        let old_count = last + 1 - first;
+        let new_count = lines.len();
+        self.lines.splice(first..=last, lines);
+        self.breaks.splice(first..=last, breaks);
+        self.styles.splice(first..=last, line_styles);
+        self.line_sizes
+            .splice(first..=last, vec![(0, 0); new_count]);
+        if let Some(caret) = caret_at {
+            self.caret = caret;
+        }
+        self.anchor = None;
+
+        // Lines after the paragraphs only need rendering again if they have moved
+        let end = if new_count == old_count {
+            first + new_count
+        } else {
+            self.lines.len()
+        };
+        self.update_lines(first, end, renderer)
+    }
+
+    // Inserts text at the caret, leaving the caret after it
+    // Every new line in the text starts a new paragraph, and selected text is replaced
+    fn insert(&mut self, string: &str, renderer: &mut Renderer) -> Result<(), String> {
+        if self.lines.is_empty() {
+            self.lines.push(String::new());
+            self.breaks.push(LineBreak::Hard);
+            self.styles.push(Vec::new());
+            self.line_sizes.push((0, 0));
+            self.caret = (0, 0);
+        }
+
+        self.delete_selection(renderer)?;
+
+        let (first, last) = self.paragraph_bounds(self.caret.0);
+        let (paragraph, offset) = self.caret_in_paragraphs();
+        let text = &paragraphs(&self.lines, &self.breaks)[paragraph];
+        let styles = &self.paragraph_styles()[paragraph];
+
+        let string = string.replace('\r', "");
+        let mut inserted: Vec<String> = string.split('\n').map(str::to_string).collect();
+        let mut inserted_styles: Vec<Vec<TextStyle>> = inserted
+            .iter()
+            .map(|text| vec![self.style; text.len()])
+            .collect();
+        let caret_offset = inserted.last().unwrap().len();
+        inserted[0].insert_str(0, &text[..offset]);
+        inserted_styles[0].splice(0..0, styles[..offset].iter().copied());
+        inserted.last_mut().unwrap().push_str(&text[offset..]);
+        inserted_styles
+            .last_mut()
+            .unwrap()
+            .extend_from_slice(&styles[offset..]);
+
+        let caret = (
+            inserted.len() - 1,
+            caret_offset + if inserted.len() == 1 { offset } else { 0 },
+        );
+        self.replace_paragraphs(first, last, &inserted, &inserted_styles, caret, renderer)
+    }
+
+    // The width of some text in the text box's font, with the style of each of its bytes
+    fn styled_width(
+        &self,
+        text: &str,
+        styles: &[TextStyle],
+        renderer: &Renderer,
+    ) -> Result<u32, String> {
+        let mut width = 0;
+        for (start, end, style) in runs(styles) {
+            width += renderer
+                .text_size(
+                    &text[start..end],
+                    &self.font_name,
+                    style.font_style,
+                    self.point,
+                )?
+                .0;
+        }
+
+        Ok(width)
+    }
+
+    // The width of the start of a line up to a byte index, in pixels
+    fn line_width(&self, line: usize, index: usize, renderer: &Renderer) -> Result<u32, String> {
+        self.styled_width(
+            &self.lines[line][..index],
+            &self.styles[line][..index],
+            renderer,
+        )
+    }
+
+    // Where the caret is from the top-left of the text, for marks that draw text boxes elsewhere
+    pub fn caret_offset(&self, renderer: &Renderer) -> Result<(i32, i32), String> {
+        let (line, index) = self.caret;
+        let width = match self.lines.get(line) {
+            Some(_) => self.line_width(line, index, renderer)?,
+            None => 0,
+        };
+
+        Ok((width as i32, (line as u32 * self.line_height()) as i32))
+    }
+
+    // The byte index in a line whose left edge is closest to x pixels from the start of the line
+    fn index_at(&self, line: usize, x: u32, renderer: &Renderer) -> Result<usize, String> {
+        let text = &self.lines[line][..self.line_end(line)];
+        let mut best = (0, x);
+
+        for (i, c) in text.char_indices() {
+            let end = i + c.len_utf8();
+            let width = self.line_width(line, end, renderer)?;
+            if width.abs_diff(x) < best.1 {
+                best = (end, width.abs_diff(x));
+            }
+            if width > x {
+                break;
+            }
+        }
+
+        Ok(best.0)
+    }
+
+    // Moves the caret to the point closest to x and y, in pixels from the top-left of the text
+    pub fn place_caret(
+        &mut self,
+        x: i32,
+        y: i32,
+        select: bool,
+        renderer: &Renderer,
+    ) -> Result<(), String> {
+        if self.lines.is_empty() {
+            return Ok(());
+        }
+        self.set_selecting(select);
+
+        let line = (y.max(0) as u32 / self.line_height()) as usize;
+        let line = line.min(self.lines.len() - 1);
+        self.caret = (line, self.index_at(line, x.max(0) as u32, renderer)?);
+        self.pick_up_style();
+
+        Ok(())
+    }
+
+    // The selected text, with the paragraphs it covers on separate lines
+    pub fn selected_text(&self) -> Option<String> {
+        let (start, end) = self.selection()?;
+        let (first, start) = self.in_paragraphs(start);
+        let (last, end) = self.in_paragraphs(end);
+        let texts = paragraphs(&self.lines, &self.breaks);
+
+        if first == last {
+            return Some(texts[first][start..end].to_string());
+        }
+        let mut selected = vec![&texts[first][start..]];
+        selected.extend(texts[first + 1..last].iter().map(String::as_str));
+        selected.push(&texts[last][..end]);
+
+        Some(selected.join("\n"))
+    }
+
+    // Removes the selected text, returning whether there was any
+    pub fn delete_selection(&mut self, renderer: &mut Renderer) -> Result<bool, String> {
+        let (start, end) = match self.selection() {
+            Some(selection) => selection,
+            None => {
+                self.anchor = None;
+                return Ok(false);
+            }
+        };
+        let (first, _) = self.paragraph_bounds(start.0);
+        let (_, last) = self.paragraph_bounds(end.0);
+        let (first_paragraph, start) = self.in_paragraphs(start);
+        let (last_paragraph, end) = self.in_paragraphs(end);
+        let texts = paragraphs(&self.lines, &self.breaks);
+        let styles = self.paragraph_styles();
+
+        let joined = texts[first_paragraph][..start].to_string() + &texts[last_paragraph][end..];
+        let joined_styles = [
+            &styles[first_paragraph][..start],
+            &styles[last_paragraph][end..],
+        ]
+        .concat();
+        self.replace_paragraphs(
+            first,
+            last,
+            &[joined],
+            &[joined_styles],
+            (0, start),
+            renderer,
+        )?;
+
+        Ok(true)
+    }
+
+    // The parts of each line that are selected, as their left edge, top and width in pixels from
+    // the top-left of the text
+    pub fn selection_offsets(&self, renderer: &Renderer) -> Result<Vec<(i32, i32, u32)>, String> {
+        let (start, end) = match self.selection() {
+            Some(selection) => selection,
+            None => return Ok(vec![]),
+        };
+
+        let mut offsets = Vec::new();
+        for line in start.0..=end.0 {
+            let from = if line == start.0 { start.1 } else { 0 };
+            let to = if line == end.0 {
+                end.1
+            } else {
+                self.line_end(line)
+            };
+
+            let left = self.line_width(line, from, renderer)?;
+            let mut width = self.line_width(line, to, renderer)? - left;
+            // Selected line breaks are shown as a sliver of selection
+            if line != end.0 && width == 0 {
+                width = SQUARE_SIZE / 4;
+            }
+            offsets.push((
+                left as i32,
+                (line as u32 * self.line_height()) as i32,
+                width,
+            ));
+        }
+
+        Ok(offsets)
+    }
+
+    // The styles of the selected bytes
+    fn selected_styles(&self) -> Vec<TextStyle> {
+        let (start, end) = match self.selection() {
+            Some(selection) => selection,
+            None => return vec![],
+        };
+
+        let mut styles = Vec::new();
+        for line in start.0..=end.0 {
+            let from = if line == start.0 { start.1 } else { 0 };
+            let to = if line == end.0 {
+                end.1
+            } else {
+                self.line_end(line)
+            };
+            styles.extend_from_slice(&self.styles[line][from..to]);
+        }
+
+        styles
+    }
+
+    // Text typed next takes the style of the character before the caret
+    fn pick_up_style(&mut self) {
+        let (line, index) = self.caret;
+        let before = match index {
+            0 if line > 0 && self.breaks[line - 1] != LineBreak::Hard => {
+                self.styles[line - 1][..self.line_end(line - 1)].last()
+            }
+            0 => self.styles.get(line).and_then(|styles| styles.first()),
+            _ => self.styles[line].get(index - 1),
+        };
+        if let Some(style) = before {
+            self.style = *style;
+        }
+    }
+
+    // Changes the style of the selected text, and of the text typed next
+    // The selection is kept, though its paragraphs are wrapped again since their widths change
+    fn restyle(
+        &mut self,
+        change: impl Fn(&mut TextStyle),
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        change(&mut self.style);
+        let (start, end, anchor) = match (self.selection(), self.anchor) {
+            (Some((start, end)), Some(anchor)) => (start, end, anchor),
+            _ => return Ok(()),
+        };
+
+        let (first, _) = self.paragraph_bounds(start.0);
+        let (_, last) = self.paragraph_bounds(end.0);
+        let (first_paragraph, from) = self.in_paragraphs(start);
+        let (last_paragraph, to) = self.in_paragraphs(end);
+        let anchor = self.in_paragraphs(anchor);
+        let caret = self.caret_in_paragraphs();
+        let texts = paragraphs(&self.lines, &self.breaks);
+        let mut styles = self.paragraph_styles();
+
+        for (paragraph, styles) in styles
+            .iter_mut()
+            .enumerate()
+            .take(last_paragraph + 1)
+            .skip(first_paragraph)
+        {
+            let from = if paragraph == first_paragraph {
+                from
+            } else {
+                0
+            };
+            let to = if paragraph == last_paragraph {
+                to
+            } else {
+                styles.len()
+            };
+            styles[from..to].iter_mut().for_each(&change);
+        }
+
+        self.replace_paragraphs(
+            first,
+            last,
+            &texts[first_paragraph..=last_paragraph],
+            &styles[first_paragraph..=last_paragraph],
+            (caret.0 - first_paragraph, caret.1),
+            renderer,
+        )?;
+        self.caret = self.in_lines(caret);
+        self.anchor = Some(self.in_lines(anchor));
+
+        Ok(())
+    }
+
+    // Makes the selected text bold or italic, or plain again if all of it already is
+    pub fn toggle_font_style(
+        &mut self,
+        font_style: FontStyle,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        let add = match self.selection() {
+            Some(_) => !self
+                .selected_styles()
+                .iter()
+                .all(|style| style.font_style.contains(font_style)),
+            None => !self.style.font_style.contains(font_style),
+        };
+
+        self.restyle(
+            |style| {
+                if add {
+                    style.font_style.insert(font_style)
+                } else {
+                    style.font_style.remove(font_style)
+                }
+            },
+            renderer,
+        )
+    }
+
+    pub fn set_color(&mut self, color: Color, renderer: &mut Renderer) -> Result<(), String> {
+        self.restyle(|style| style.color = color, renderer)
+    }
+
+    // Changes the color of all of the text, including the text box's own
+    pub fn set_all_color(&mut self, color: Color, renderer: &mut Renderer) -> Result<(), String> {
+        self.color = color;
+        self.style.color = color;
+        for style in self.styles.iter_mut().flatten() {
+            style.color = color;
+        }
+
+        self.update_all(renderer)
+    }
+
+    // The color after the one text is being typed in, from TEXT_COLORS
+    fn next_color(&self) -> Color {
+        let i = TEXT_COLORS.iter().position(|c| *c == self.style.color);
+        TEXT_COLORS[i.map_or(0, |i| (i + 1) % TEXT_COLORS.len())]
+    }
+}
+
+impl Mark for TextBox {
+    fn draw(&self, renderer: &mut Renderer) -> Result<(), String> {
+        self.draw_at(self.page_square.position, renderer)
+    }
+
+    fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    fn page_square(&self) -> PageSquare {
+        self.page_square
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (
+            self.width().div_ceil(SQUARE_SIZE).max(1),
+            self.line_count() as u32 * line_rows(self.point),
+        )
+    }
+
+    fn to_data(&self) -> MarkData {
+        MarkData::Text(TextData {
+            page: self.page_square.page,
+            square: self.page_square.square,
+            font_name: self.font_name.clone(),
+            font_style: self.font_style,
+            point: self.point,
+            color: self.color,
+            max_width: self.max_width,
+            lines: self.lines.clone(),
+            breaks: self.breaks.clone(),
+            spans: self.spans(),
+        })
+    }
+
+    fn restore(&mut self, data: &MarkData, renderer: &mut Renderer) -> Result<(), String> {
+        match data {
+            MarkData::Text(data) => {
+                self.font_name = data.font_name.clone();
+                self.font_style = data.font_style;
+                self.point = data.point;
+                self.color = data.color;
+                self.max_width = data.max_width;
+
+                self.set_lines(
+                    data.lines.clone(),
+                    data.breaks.clone(),
+                    &data.spans,
+                    renderer,
+                )
+            }
+            _ => Err("Cannot restore a text box from another kind of mark.".to_string()),
+        }
+    }
+}
+
+impl TextInput for TextBox {
+    fn push_str(&mut self, string: &str, renderer: &mut Renderer) -> Result<(), String> {
+        self.insert(string, renderer)
+    }
+
+    // Removes the selected text, or else the character before the caret, or joins the caret's
+    // paragraph onto the one before
+    fn pop(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        if self.lines.is_empty() || self.delete_selection(renderer)? {
+            return Ok(());
+        }
+
+        let (first, last) = self.paragraph_bounds(self.caret.0);
+        let (paragraph, offset) = self.caret_in_paragraphs();
+        let mut texts = paragraphs(&self.lines, &self.breaks);
+        let mut styles = self.paragraph_styles();
+
+        if offset > 0 {
+            let start = TextBox::prev_boundary(&texts[paragraph], offset);
+            texts[paragraph].replace_range(start..offset, "");
+            styles[paragraph].drain(start..offset);
+            self.replace_paragraphs(
+                first,
+                last,
+                &texts[paragraph..=paragraph],
+                &styles[paragraph..=paragraph],
+                (0, start),
+                renderer,
+            )
+        } else if first > 0 {
+            let (previous, _) = self.paragraph_bounds(first - 1);
+            let joined = texts[paragraph - 1].clone() + &texts[paragraph];
+            let joined_styles = styles[paragraph - 1..=paragraph].concat();
+            let offset = texts[paragraph - 1].len();
+            self.replace_paragraphs(
+                previous,
+                last,
+                &[joined],
+                &[joined_styles],
+                (0, offset),
+                renderer,
+            )
+        } else {
+            // Nothing is left, so the text box goes back to having no lines
+            if self.is_empty() && self.lines.len() == 1 {
+                self.lines.clear();
+                self.breaks.clear();
+                self.styles.clear();
+                self.line_sizes.clear();
+            }
+            Ok(())
+        }
+    }
+
+    fn new_line(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        self.insert("\n", renderer)
+    }
+
+    // Removes the selected text, or else the character after the caret, or joins the next
+    // paragraph onto the caret's one
+    fn delete(&mut self, renderer: &mut Renderer) -> Result<(), String> {
+        if self.lines.is_empty() || self.delete_selection(renderer)? {
+            return Ok(());
+        }
+
+        let (first, last) = self.paragraph_bounds(self.caret.0);
+        let (paragraph, offset) = self.caret_in_paragraphs();
+        let mut texts = paragraphs(&self.lines, &self.breaks);
+        let mut styles = self.paragraph_styles();
+
+        if offset < texts[paragraph].len() {
+            let end = TextBox::next_boundary(&texts[paragraph], offset);
+            texts[paragraph].replace_range(offset..end, "");
+            styles[paragraph].drain(offset..end);
+            self.replace_paragraphs(
+                first,
+                last,
+                &texts[paragraph..=paragraph],
+                &styles[paragraph..=paragraph],
+                (0, offset),
+                renderer,
+            )
+        } else if last + 1 < self.lines.len() {
+            let (_, next) = self.paragraph_bounds(last + 1);
+            let joined = texts[paragraph].clone() + &texts[paragraph + 1];
+            let joined_styles = styles[paragraph..=paragraph + 1].concat();
+            self.replace_paragraphs(
+                first,
+                next,
+                &[joined],
+                &[joined_styles],
+                (0, offset),
+                renderer,
+            )
+        } else {
+            Ok(())
+        }
+    }
+
+    // Moves the caret with the arrow keys, Home and End, keeping it within the text
+    // Moving without selecting from a selection leaves the caret at the side it moved towards
+    fn move_caret(
+        &mut self,
+        keycode: Keycode,
+        select: bool,
+        renderer: &Renderer,
+    ) -> Result<(), String> {
+        if self.lines.is_empty() {
+            return Ok(());
+        }
+        if let (false, Some((start, end))) = (select, self.selection()) {
+            if matches!(keycode, Keycode::Left | Keycode::Right) {
+                self.caret = if keycode == Keycode::Left { start } else { end };
+                self.anchor = None;
+                return Ok(());
+            }
+        }
+        self.set_selecting(select);
+        let (line, index) = self.caret;
+        let text = &self.lines[line];
+        let end = self.line_end(line);
+
+        self.caret = match keycode {
+            Keycode::Left if index > 0 => (line, TextBox::prev_boundary(text, index)),
+            Keycode::Left if line > 0 => (line - 1, self.line_end(line - 1)),
+            Keycode::Right if index < end => (line, TextBox::next_boundary(text, index)),
+            Keycode::Right if line + 1 < self.lines.len() => (line + 1, 0),
+            // Up and Down keep the caret as close as they can to where it was across the line
+            Keycode::Up | Keycode::Down => {
+                let target = match keycode {
+                    Keycode::Up if line > 0 => line - 1,
+                    Keycode::Down if line + 1 < self.lines.len() => line + 1,
+                    _ => return Ok(()),
+                };
+                let x = self.line_width(line, index, renderer)?;
+                (target, self.index_at(target, x, renderer)?)
+            }
+            Keycode::Home => (line, 0),
+            Keycode::End => (line, end),
+            _ => self.caret,
+        };
+        self.pick_up_style();
+
+        Ok(())
+    }
+
+    fn place_caret(
+        &mut self,
+        x: i32,
+        y: i32,
+        select: bool,
+        renderer: &Renderer,
+    ) -> Result<(), String> {
+        TextBox::place_caret(self, x, y, select, renderer)
+    }
+
+    fn caret_position(&self, renderer: &Renderer) -> Result<Option<Position>, String> {
+        let (x, y) = self.caret_offset(renderer)?;
+        Ok(Some(Position::add(self.page_square.position, x, y)))
+    }
+
+    fn selected_text(&self) -> Option<String> {
+        TextBox::selected_text(self)
+    }
+
+    fn delete_selection(&mut self, renderer: &mut Renderer) -> Result<bool, String> {
+        TextBox::delete_selection(self, renderer)
+    }
+
+    fn selection_rects(&self, renderer: &Renderer) -> Result<Vec<Area>, String> {
+        Ok(self
+            .selection_offsets(renderer)?
+            .into_iter()
+            .map(|(x, y, width)| {
+                (
+                    Position::add(self.page_square.position, x, y),
+                    (width, self.line_height()),
+                )
+            })
+            .collect())
+    }
+
+    fn line_height(&self) -> u32 {
+        TextBox::line_height(self)
+    }
+
+    fn set_text_font(
+        &mut self,
+        font_name: &str,
+        point: u16,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        self.set_font(font_name, point, renderer)
+    }
+
+    fn set_text_color(
+        &mut self,
+        color: Color,
+        all: bool,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        if all {
+            self.set_all_color(color, renderer)
+        } else {
+            self.set_color(color, renderer)
+        }
+    }
+
+    // Ctrl+B and Ctrl+I make text bold and italic, and Ctrl+K changes its color
+    fn handle_key(
+        &mut self,
+        keycode: Keycode,
+        keymod: Mod,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        if !keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+            return Ok(());
+        }
+
+        match keycode {
+            Keycode::B => self.toggle_font_style(FontStyle::BOLD, renderer),
+            Keycode::I => self.toggle_font_style(FontStyle::ITALIC, renderer),
+            Keycode::K => self.set_color(self.next_color(), renderer),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::with_renderer;
+
+    const FONT: &str = "NotoSerif";
+
+    fn text_box(max_width: u32, renderer: &mut Renderer) -> TextBox {
+        let pages = Pages::new((29, 42), renderer).unwrap();
+        let page_square = PageSquare::new(0, (2, 2), &pages).unwrap();
+        TextBox::new(
+            page_square,
+            FONT.to_string(),
+            FontStyle::NORMAL,
+            30,
+            Color::BLACK,
+            max_width,
+        )
+    }
+
+    fn width(text: &str, renderer: &Renderer) -> u32 {
+        renderer
+            .text_size(text, FONT, FontStyle::NORMAL, 30)
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn typing_and_deleting_carry_on_across_a_soft_wrap() {
+        with_renderer(|_, renderer| {
+            let mut text_box = text_box(width("alpha beta", renderer), renderer);
+            text_box.push_str("alpha beta gamma", renderer).unwrap();
+            assert_eq!(text_box.lines(), &["alpha beta ", "gamma"]);
+            assert_eq!(text_box.breaks(), &[LineBreak::Soft, LineBreak::Hard]);
+
+            // Backspace at the start of the wrapped line takes the space before it
+            text_box.move_caret(Keycode::Home, false, renderer).unwrap();
+            text_box.pop(renderer).unwrap();
+            assert_eq!(text_box.lines(), &["alpha ", "betagamma"]);
+            assert_eq!(text_box.caret(), (1, 4));
+
+            text_box.push_str(" ", renderer).unwrap();
+            assert_eq!(text_box.lines(), &["alpha beta ", "gamma"]);
+
+            // Delete at the end of the first line takes the first character of the next
+            text_box.move_caret(Keycode::Up, false, renderer).unwrap();
+            text_box.move_caret(Keycode::End, false, renderer).unwrap();
+            text_box.delete(renderer).unwrap();
+            assert_eq!(
+                paragraphs(text_box.lines(), text_box.breaks()),
+                ["alpha beta amma"]
+            );
+            assert_eq!(text_box.lines().len(), 2);
+        });
+    }
+
+    #[test]
+    fn up_and_down_keep_the_caret_across_lines_of_different_lengths() {
+        with_renderer(|_, renderer| {
+            let mut text_box = text_box(600, renderer);
+            text_box
+                .push_str("a long first line\nab\nanother long line", renderer)
+                .unwrap();
+            assert_eq!(text_box.caret(), (2, 17));
+
+            // The short line in between takes the caret to its end
+            text_box.move_caret(Keycode::Up, false, renderer).unwrap();
+            assert_eq!(text_box.caret(), (1, 2));
+
+            // From there, the caret goes to about the same distance along the longer lines
+            let x = width("ab", renderer);
+            let close = width("m", renderer);
+            let near = |text_box: &TextBox, line: usize| {
+                let (caret_line, index) = text_box.caret();
+                let along = text_box.line_width(caret_line, index, renderer).unwrap();
+                caret_line == line && along.abs_diff(x) <= close
+            };
+            text_box.move_caret(Keycode::Down, false, renderer).unwrap();
+            assert!(near(&text_box, 2));
+            text_box.move_caret(Keycode::Up, false, renderer).unwrap();
+            text_box.move_caret(Keycode::Up, false, renderer).unwrap();
+            assert!(near(&text_box, 0));
+
+            // Up from the first line and Down from the last go nowhere
+            text_box.move_caret(Keycode::Up, false, renderer).unwrap();
+            assert_eq!(text_box.caret().0, 0);
+            text_box.move_caret(Keycode::Down, false, renderer).unwrap();
+            text_box.move_caret(Keycode::Down, false, renderer).unwrap();
+            text_box.move_caret(Keycode::Down, false, renderer).unwrap();
+            assert_eq!(text_box.caret().0, 2);
+        });
+    }
+
+    const BOLD: TextStyle = TextStyle {
+        font_style: FontStyle::BOLD,
+        color: Color::BLACK,
+    };
+    const PLAIN: TextStyle = TextStyle {
+        font_style: FontStyle::NORMAL,
+        color: Color::BLACK,
+    };
+
+    fn strings(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn paragraphs_join_wrapped_lines_without_the_hyphens_added() {
+        let lines = strings(&["alpha ", "beta", "extraordi-", "nary", "well-", "known", ""]);
+        let breaks = [
+            LineBreak::Soft,
+            LineBreak::Hard,
+            LineBreak::Hyphen,
+            LineBreak::Hard,
+            LineBreak::Soft,
+            LineBreak::Hard,
+            LineBreak::Hard,
+        ];
+        assert_eq!(
+            paragraphs(&lines, &breaks),
+            ["alpha beta", "extraordinary", "well-known", ""]
+        );
+
+        // Lines without a break saved end their paragraph, and no lines are one empty paragraph
+        assert_eq!(paragraphs(&strings(&["a", "b"]), &[]), ["a", "b"]);
+        assert_eq!(paragraphs(&[], &[]), [""]);
+    }
+
+    #[test]
+    fn line_runs_fill_the_gaps_between_spans_with_the_default_style() {
+        let spans = [StyleSpan {
+            start: 2,
+            end: 5,
+            style: BOLD,
+        }];
+        assert_eq!(
+            line_runs(8, &spans, PLAIN),
+            [(0, 2, PLAIN), (2, 5, BOLD), (5, 8, PLAIN)]
+        );
+
+        // Spans running past the end of a line are cut short
+        assert_eq!(line_runs(4, &spans, PLAIN), [(0, 2, PLAIN), (2, 4, BOLD)]);
+        assert_eq!(line_runs(3, &[], PLAIN), [(0, 3, PLAIN)]);
+        assert!(line_runs(0, &spans, PLAIN).is_empty());
+    }
+
+    #[test]
+    fn words_too_long_for_a_line_are_hyphenated() {
+        with_renderer(|_, renderer| {
+            let max_width = width("extra", renderer);
+            let mut text_box = text_box(max_width, renderer);
+            text_box.push_str("extraordinarily", renderer).unwrap();
+
+            let (lines, breaks) = (text_box.lines(), text_box.breaks());
+            assert!(lines.len() > 2);
+            for (line, line_break) in lines.iter().zip(breaks).take(lines.len() - 1) {
+                assert!(line.ends_with('-'));
+                assert_eq!(*line_break, LineBreak::Hyphen);
+                assert!(width(line, renderer) <= max_width, "{} is too wide", line);
+            }
+            assert_eq!(breaks.last(), Some(&LineBreak::Hard));
+            assert_eq!(paragraphs(lines, breaks), ["extraordinarily"]);
+        });
+    }
+
+    #[test]
+    fn reflowing_to_another_width_keeps_the_paragraphs() {
+        with_renderer(|_, renderer| {
+            let text = "alpha beta gamma delta\nsecond paragraph here";
+            let wide = width("alpha beta gamma delta", renderer) + 10;
+            let mut text_box = text_box(wide, renderer);
+            text_box.push_str(text, renderer).unwrap();
+            let (lines, breaks) = (text_box.lines().clone(), text_box.breaks().clone());
+            assert_eq!(lines.len(), 2);
+
+            text_box
+                .set_max_width(width("alpha beta", renderer), renderer)
+                .unwrap();
+            assert!(text_box.lines().len() > 2);
+            assert!(text_box.breaks().contains(&LineBreak::Soft));
+            assert_eq!(
+                paragraphs(text_box.lines(), text_box.breaks()),
+                ["alpha beta gamma delta", "second paragraph here"]
+            );
+            // The caret stays at the end of the text
+            let (line, index) = text_box.caret();
+            assert_eq!(
+                (line, index),
+                (text_box.lines().len() - 1, text_box.lines()[line].len())
+            );
+
+            text_box.set_max_width(wide, renderer).unwrap();
+            assert_eq!((text_box.lines(), text_box.breaks()), (&lines, &breaks));
+        });
+    }
 }
@@ -0,0 +1,517 @@
+use super::parser::{Delimiters, Node, SymbolKind};
+
+// Measurements of the font used to draw the formula, in pixels at its full size
+pub struct Metrics<'a> {
+    pub em: f32,                                // The font's point size
+    pub ascent: f32,                            // Above the baseline
+    pub descent: f32,                           // Below the baseline, as a positive number
+    pub advance: &'a dyn Fn(&str, bool) -> f32, // Width of (text, italic)
+}
+
+// Something to draw, with coordinates relative to the left end of the formula's baseline
+// y increases downwards, so anything above the baseline has a negative y
+#[derive(Clone, Debug, PartialEq)]
+pub enum Item {
+    Glyph {
+        text: String,
+        italic: bool,
+        x: f32,
+        y: f32, // Baseline of the text
+        scale: f32,
+    },
+    Line {
+        from: (f32, f32),
+        to: (f32, f32),
+        thickness: f32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layout {
+    pub items: Vec<Item>,
+    pub width: f32,
+    pub ascent: f32,
+    pub descent: f32,
+}
+
+const SCRIPT_SCALE: f32 = 0.7;
+const FRACTION_SCALE: f32 = 0.85;
+const MIN_SCALE: f32 = 0.5;
+const BIG_OP_SCALE: f32 = 1.5;
+
+impl Layout {
+    fn empty() -> Layout {
+        Layout {
+            items: Vec::new(),
+            width: 0.0,
+            ascent: 0.0,
+            descent: 0.0,
+        }
+    }
+
+    // Copies the items of another layout into this one, offset by (dx, dy)
+    fn place(&mut self, other: Layout, dx: f32, dy: f32) {
+        for item in other.items {
+            self.items.push(match item {
+                Item::Glyph {
+                    text,
+                    italic,
+                    x,
+                    y,
+                    scale,
+                } => Item::Glyph {
+                    text,
+                    italic,
+                    x: x + dx,
+                    y: y + dy,
+                    scale,
+                },
+                Item::Line {
+                    from,
+                    to,
+                    thickness,
+                } => Item::Line {
+                    from: (from.0 + dx, from.1 + dy),
+                    to: (to.0 + dx, to.1 + dy),
+                    thickness,
+                },
+            });
+        }
+    }
+
+    fn line(&mut self, from: (f32, f32), to: (f32, f32), thickness: f32) {
+        self.items.push(Item::Line {
+            from,
+            to,
+            thickness,
+        })
+    }
+}
+
+pub fn layout(node: &Node, metrics: &Metrics) -> Layout {
+    layout_node(node, 1.0, metrics)
+}
+
+fn smaller(scale: f32, factor: f32) -> f32 {
+    (scale * factor).max(MIN_SCALE)
+}
+
+fn layout_node(node: &Node, scale: f32, metrics: &Metrics) -> Layout {
+    let em = metrics.em * scale;
+    // Fraction bars and the middle of matrices sit on the math axis, a little above the baseline
+    let axis = 0.25 * em;
+    let thickness = (0.05 * em).max(1.0);
+
+    match node {
+        Node::Symbol(text, kind) => {
+            let italic = *kind == SymbolKind::Variable;
+            let mut layout = Layout::empty();
+
+            // Operators get some room on either side
+            let padding = match kind {
+                SymbolKind::Binary => 0.22 * em,
+                SymbolKind::Relation => 0.28 * em,
+                _ => 0.0,
+            };
+            layout.items.push(Item::Glyph {
+                text: text.clone(),
+                italic,
+                x: padding,
+                y: 0.0,
+                scale,
+            });
+            layout.width = (metrics.advance)(text, italic) * scale + 2.0 * padding;
+            layout.ascent = metrics.ascent * scale;
+            layout.descent = metrics.descent * scale;
+
+            layout
+        }
+        Node::Space(ems) => {
+            let mut layout = Layout::empty();
+            layout.width = ems * em;
+            layout
+        }
+        Node::Row(nodes) => {
+            let mut layout = Layout::empty();
+            for node in nodes {
+                let child = layout_node(node, scale, metrics);
+                layout.ascent = layout.ascent.max(child.ascent);
+                layout.descent = layout.descent.max(child.descent);
+                let x = layout.width;
+                layout.width += child.width;
+                layout.place(child, x, 0.0);
+            }
+            layout
+        }
+        Node::Frac(numerator, denominator) => {
+            let child_scale = smaller(scale, FRACTION_SCALE);
+            let num = layout_node(numerator, child_scale, metrics);
+            let den = layout_node(denominator, child_scale, metrics);
+            let gap = 0.15 * em;
+            let padding = 0.1 * em;
+            let width = num.width.max(den.width) + 2.0 * padding;
+
+            let mut layout = Layout::empty();
+            layout.width = width;
+            layout.ascent = axis + gap + num.descent + num.ascent;
+            layout.descent = (gap + den.ascent + den.descent - axis).max(0.0);
+
+            let num_y = -axis - gap - num.descent;
+            let den_y = -axis + gap + den.ascent;
+            let num_x = (width - num.width) / 2.0;
+            let den_x = (width - den.width) / 2.0;
+            layout.place(num, num_x, num_y);
+            layout.place(den, den_x, den_y);
+            layout.line((0.0, -axis), (width, -axis), thickness);
+
+            layout
+        }
+        Node::Sqrt(radicand) => {
+            let inner = layout_node(radicand, scale, metrics);
+            let gap = 0.15 * em;
+            let sign_width = 0.6 * em;
+            let top = -(inner.ascent + gap);
+            let bottom = inner.descent;
+            let middle = (top + bottom) / 2.0;
+
+            let mut layout = Layout::empty();
+            layout.width = sign_width + inner.width + 0.1 * em;
+            layout.ascent = inner.ascent + gap + thickness;
+            layout.descent = inner.descent;
+
+            // The radical sign, then the bar over the radicand
+            layout.line(
+                (0.0, middle + 0.1 * em),
+                (0.2 * sign_width, middle),
+                thickness,
+            );
+            layout.line(
+                (0.2 * sign_width, middle),
+                (0.5 * sign_width, bottom),
+                thickness,
+            );
+            layout.line((0.5 * sign_width, bottom), (sign_width, top), thickness);
+            layout.line((sign_width, top), (layout.width, top), thickness);
+            layout.place(inner, sign_width, 0.0);
+
+            layout
+        }
+        Node::Scripts { base, sup, sub } => {
+            let base = layout_node(base, scale, metrics);
+            let mut layout = Layout::empty();
+            layout.ascent = base.ascent;
+            layout.descent = base.descent;
+            let x = base.width;
+            layout.place(base.clone(), 0.0, 0.0);
+
+            let mut width = x;
+            if let Some(sup) = sup {
+                let sup = layout_node(sup, smaller(scale, SCRIPT_SCALE), metrics);
+                // Raise the script at least as high as the top of tall bases, like fractions
+                let shift = (0.45 * em).max(base.ascent - 0.5 * sup.ascent);
+                layout.ascent = layout.ascent.max(shift + sup.ascent);
+                width = width.max(x + sup.width);
+                layout.place(sup, x, -shift);
+            }
+            if let Some(sub) = sub {
+                let sub = layout_node(sub, smaller(scale, SCRIPT_SCALE), metrics);
+                let shift = (0.2 * em).max(base.descent - 0.5 * sub.descent);
+                layout.descent = layout.descent.max(shift + sub.descent);
+                width = width.max(x + sub.width);
+                layout.place(sub, x, shift);
+            }
+            layout.width = width + 0.05 * em;
+
+            layout
+        }
+        Node::BigOp {
+            symbol,
+            limits,
+            sup,
+            sub,
+        } => {
+            let op_scale = scale * BIG_OP_SCALE;
+            let op_width = (metrics.advance)(symbol, false) * op_scale;
+            // Lower the larger symbol so that it stays centred on the axis
+            let op_y = (BIG_OP_SCALE - 1.0) * 0.35 * em;
+            let op_ascent = metrics.ascent * op_scale - op_y;
+            let op_descent = metrics.descent * op_scale + op_y;
+
+            let sup = sup
+                .as_ref()
+                .map(|n| layout_node(n, smaller(scale, SCRIPT_SCALE), metrics));
+            let sub = sub
+                .as_ref()
+                .map(|n| layout_node(n, smaller(scale, SCRIPT_SCALE), metrics));
+
+            let mut layout = Layout::empty();
+            layout.ascent = op_ascent;
+            layout.descent = op_descent;
+
+            if *limits {
+                // Limits are centred above and below the symbol
+                let width = op_width
+                    .max(sup.as_ref().map_or(0.0, |l| l.width))
+                    .max(sub.as_ref().map_or(0.0, |l| l.width));
+                layout.items.push(Item::Glyph {
+                    text: symbol.clone(),
+                    italic: false,
+                    x: (width - op_width) / 2.0,
+                    y: op_y,
+                    scale: op_scale,
+                });
+                if let Some(sup) = sup {
+                    let y = -op_ascent - sup.descent;
+                    layout.ascent = op_ascent + sup.descent + sup.ascent;
+                    let x = (width - sup.width) / 2.0;
+                    layout.place(sup, x, y);
+                }
+                if let Some(sub) = sub {
+                    let y = op_descent + sub.ascent;
+                    layout.descent = op_descent + sub.ascent + sub.descent;
+                    let x = (width - sub.width) / 2.0;
+                    layout.place(sub, x, y);
+                }
+                layout.width = width + 0.15 * em;
+            } else {
+                // Integrals have their limits beside the symbol, at the top and bottom
+                layout.items.push(Item::Glyph {
+                    text: symbol.clone(),
+                    italic: false,
+                    x: 0.0,
+                    y: op_y,
+                    scale: op_scale,
+                });
+                let mut width = op_width;
+                if let Some(sup) = sup {
+                    let y = -op_ascent + sup.ascent;
+                    layout.ascent = layout.ascent.max(-y + sup.ascent);
+                    width = width.max(op_width + sup.width);
+                    layout.place(sup, op_width, y);
+                }
+                if let Some(sub) = sub {
+                    let y = op_descent - sub.descent;
+                    layout.descent = layout.descent.max(y + sub.descent);
+                    width = width.max(op_width + sub.width);
+                    layout.place(sub, op_width, y);
+                }
+                layout.width = width + 0.15 * em;
+            }
+
+            layout
+        }
+        Node::Matrix { rows, delimiters } => layout_matrix(rows, *delimiters, scale, metrics),
+    }
+}
+
+fn layout_matrix(
+    rows: &[Vec<Node>],
+    delimiters: Delimiters,
+    scale: f32,
+    metrics: &Metrics,
+) -> Layout {
+    let em = metrics.em * scale;
+    let axis = 0.25 * em;
+    let thickness = (0.05 * em).max(1.0);
+    let column_gap = 0.8 * em;
+    let row_gap = 0.3 * em;
+
+    let cells: Vec<Vec<Layout>> = rows
+        .iter()
+        .map(|row| row.iter().map(|n| layout_node(n, scale, metrics)).collect())
+        .collect();
+
+    let columns = cells.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut column_widths = vec![0.0f32; columns];
+    for row in &cells {
+        for (i, cell) in row.iter().enumerate() {
+            column_widths[i] = column_widths[i].max(cell.width);
+        }
+    }
+    let row_extents: Vec<(f32, f32)> = cells
+        .iter()
+        .map(|row| {
+            row.iter().fold((0.0f32, 0.0f32), |(a, d), c| {
+                (a.max(c.ascent), d.max(c.descent))
+            })
+        })
+        .collect();
+
+    let content_width =
+        column_widths.iter().sum::<f32>() + column_gap * columns.saturating_sub(1) as f32;
+    let content_height = row_extents.iter().map(|(a, d)| a + d).sum::<f32>()
+        + row_gap * row_extents.len().saturating_sub(1) as f32;
+
+    let delimiter_width = match delimiters {
+        Delimiters::None => 0.0,
+        _ => 0.4 * em,
+    };
+
+    // The matrix is centred on the axis
+    let top = -axis - content_height / 2.0;
+    let bottom = top + content_height;
+
+    let mut layout = Layout::empty();
+    layout.width = content_width + 2.0 * delimiter_width;
+    layout.ascent = -top;
+    layout.descent = bottom.max(0.0);
+
+    let mut y = top;
+    for (row, (ascent, descent)) in cells.into_iter().zip(row_extents) {
+        let mut x = delimiter_width;
+        for (i, cell) in row.into_iter().enumerate() {
+            // Cells are centred in their column
+            let cell_x = x + (column_widths[i] - cell.width) / 2.0;
+            layout.place(cell, cell_x, y + ascent);
+            x += column_widths[i] + column_gap;
+        }
+        y += ascent + descent + row_gap;
+    }
+
+    // Delimiters are drawn as lines so that they can stretch to any height
+    let left = 0.15 * em;
+    let right = layout.width - 0.15 * em;
+    let inset = 0.2 * em;
+    match delimiters {
+        Delimiters::None => (),
+        Delimiters::Brackets => {
+            layout.line((left, top), (left, bottom), thickness);
+            layout.line((left, top), (left + inset, top), thickness);
+            layout.line((left, bottom), (left + inset, bottom), thickness);
+            layout.line((right, top), (right, bottom), thickness);
+            layout.line((right - inset, top), (right, top), thickness);
+            layout.line((right - inset, bottom), (right, bottom), thickness);
+        }
+        Delimiters::Bars => {
+            layout.line((left, top), (left, bottom), thickness);
+            layout.line((right, top), (right, bottom), thickness);
+        }
+        Delimiters::Parentheses => {
+            // Each parenthesis is approximated by three segments bowing outwards
+            let quarter = content_height / 4.0;
+            let outer_left = left - 0.1 * em;
+            let outer_right = right + 0.1 * em;
+            for (edge, outer) in [(left + inset, outer_left), (right - inset, outer_right)] {
+                layout.line((edge, top), (outer, top + quarter), thickness);
+                layout.line((outer, top + quarter), (outer, bottom - quarter), thickness);
+                layout.line((outer, bottom - quarter), (edge, bottom), thickness);
+            }
+        }
+    }
+
+    layout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::parser::parse;
+
+    // Every character is 10 pixels wide at full size, in a 30 point font
+    fn lay_out(source: &str) -> Layout {
+        let advance = |text: &str, _: bool| 10.0 * text.chars().count() as f32;
+        let metrics = Metrics {
+            em: 30.0,
+            ascent: 24.0,
+            descent: 6.0,
+            advance: &advance,
+        };
+        layout(&parse(source).unwrap(), &metrics)
+    }
+
+    // The glyphs of a layout as (text, x, y, scale)
+    fn glyphs(layout: &Layout) -> Vec<(&str, f32, f32, f32)> {
+        layout
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Glyph {
+                    text, x, y, scale, ..
+                } => Some((text.as_str(), *x, *y, *scale)),
+                Item::Line { .. } => None,
+            })
+            .collect()
+    }
+
+    fn lines(layout: &Layout) -> usize {
+        layout
+            .items
+            .iter()
+            .filter(|item| matches!(item, Item::Line { .. }))
+            .count()
+    }
+
+    #[test]
+    fn lays_rows_out_side_by_side() {
+        let layout = lay_out("ab");
+        assert_eq!(
+            glyphs(&layout),
+            vec![("a", 0.0, 0.0, 1.0), ("b", 10.0, 0.0, 1.0)]
+        );
+        assert_eq!(layout.width, 20.0);
+        assert_eq!((layout.ascent, layout.descent), (24.0, 6.0));
+    }
+
+    #[test]
+    fn puts_numerators_over_denominators() {
+        let layout = lay_out("\\frac{a}{bc}");
+        let glyphs = glyphs(&layout);
+        let (numerator, denominator) = (glyphs[0], glyphs[1]);
+
+        // Above and below the bar, both made smaller and centred over each other
+        assert!(numerator.2 < 0.0 && denominator.2 > 0.0);
+        assert_eq!(numerator.3, FRACTION_SCALE);
+        assert_eq!(numerator.1 + 5.0 * FRACTION_SCALE, layout.width / 2.0);
+        assert_eq!(denominator.1 + 10.0 * FRACTION_SCALE, layout.width / 2.0);
+        assert_eq!(lines(&layout), 1);
+        assert!(layout.ascent > 24.0);
+    }
+
+    #[test]
+    fn raises_superscripts_and_lowers_subscripts() {
+        let layout = lay_out("x_i^2");
+        let glyphs = glyphs(&layout);
+        assert_eq!(glyphs[0], ("x", 0.0, 0.0, 1.0));
+
+        let (sup, sub) = (glyphs[1], glyphs[2]);
+        assert_eq!((sup.0, sub.0), ("2", "i"));
+        assert!(sup.2 < 0.0 && sub.2 > 0.0);
+        assert_eq!((sup.1, sub.1), (10.0, 10.0));
+        assert_eq!((sup.3, sub.3), (SCRIPT_SCALE, SCRIPT_SCALE));
+    }
+
+    #[test]
+    fn draws_square_root_signs_over_the_radicand() {
+        let layout = lay_out("\\sqrt{x}");
+        let glyphs = glyphs(&layout);
+
+        // The radicand comes after the sign, under the bar drawn above it
+        assert!(glyphs[0].1 > 0.0);
+        assert_eq!(lines(&layout), 4);
+        assert!(layout.ascent > 24.0);
+        assert!(layout.width > 10.0);
+    }
+
+    #[test]
+    fn aligns_matrix_columns() {
+        let layout =
+            lay_out("\\begin{bmatrix} a & \\text{bbb} \\\\ \\text{ccc} & d \\end{bmatrix}");
+        let glyphs = glyphs(&layout);
+        let centre = |glyph: (&str, f32, f32, f32)| glyph.1 + 5.0 * glyph.0.len() as f32;
+
+        // Cells are centred in their column, and rows are stacked downwards
+        assert_eq!(centre(glyphs[0]), centre(glyphs[2]));
+        assert_eq!(centre(glyphs[1]), centre(glyphs[3]));
+        assert_eq!(glyphs[0].2, glyphs[1].2);
+        assert!(glyphs[2].2 > glyphs[0].2);
+        // Brackets are three lines on each side
+        assert_eq!(lines(&layout), 6);
+    }
+
+    #[test]
+    fn keeps_text_in_one_glyph() {
+        let layout = lay_out("\\text{if x > 0}");
+        assert_eq!(glyphs(&layout), vec![("if x > 0", 0.0, 0.0, 1.0)]);
+        assert_eq!(layout.width, 80.0);
+    }
+}
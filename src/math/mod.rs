@@ -0,0 +1,5 @@
+pub mod layout;
+pub mod parser;
+
+// Typesetting for the math tool
+// Nothing in here depends on SDL, the font is only reached through layout::Metrics
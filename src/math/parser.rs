@@ -0,0 +1,602 @@
+// Parses a subset of LaTeX math into a tree that can be laid out
+//
+// Supported: letters, digits and operators, `^` and `_` scripts, `\frac{}{}`, `\sqrt{}`,
+// Greek letters, common symbols, `\sum`, `\prod` and `\int` with limits, `\text{}`, spacing
+// commands and the matrix environments `matrix`, `pmatrix`, `bmatrix` and `vmatrix`.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SymbolKind {
+    Variable, // Drawn in italics
+    Upright,  // Digits, upright Greek letters and text
+    Binary,   // Spaced on either side, e.g. +
+    Relation, // Spaced on either side, e.g. =
+    Punctuation,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    Symbol(String, SymbolKind),
+    Space(f32), // In ems
+    Row(Vec<Node>),
+    Frac(Box<Node>, Box<Node>),
+    Sqrt(Box<Node>),
+    Scripts {
+        base: Box<Node>,
+        sup: Option<Box<Node>>,
+        sub: Option<Box<Node>>,
+    },
+    // Large operators, whose limits go above and below unless they are integrals
+    BigOp {
+        symbol: String,
+        limits: bool,
+        sup: Option<Box<Node>>,
+        sub: Option<Box<Node>>,
+    },
+    Matrix {
+        rows: Vec<Vec<Node>>,
+        delimiters: Delimiters,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Delimiters {
+    None,
+    Parentheses,
+    Brackets,
+    Bars,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Char(char),
+    Command(String),
+    Open,
+    Close,
+    Sup,
+    Sub,
+    Align,  // &
+    NewRow, // \\
+    Space,  // Any run of whitespace, which only matters inside \text
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let mut name = String::new();
+                while let Some(&next) = chars.peek() {
+                    if !next.is_ascii_alphabetic() {
+                        break;
+                    }
+                    name.push(next);
+                    chars.next();
+                }
+
+                // Commands made of a single symbol, like \\ and \,
+                if name.is_empty() {
+                    match chars.next() {
+                        Some('\\') => tokens.push(Token::NewRow),
+                        Some(symbol) => tokens.push(Token::Command(symbol.to_string())),
+                        None => tokens.push(Token::Char('\\')),
+                    }
+                } else {
+                    tokens.push(Token::Command(name));
+                }
+            }
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            '^' => tokens.push(Token::Sup),
+            '_' => tokens.push(Token::Sub),
+            '&' => tokens.push(Token::Align),
+            c if c.is_whitespace() => {
+                if tokens.last() != Some(&Token::Space) {
+                    tokens.push(Token::Space)
+                }
+            }
+            c => tokens.push(Token::Char(c)),
+        }
+    }
+
+    tokens
+}
+
+pub fn parse(source: &str) -> Result<Node, String> {
+    let mut parser = Parser {
+        tokens: tokenize(source),
+        position: 0,
+    };
+
+    let row = parser.parse_row()?;
+    match parser.peek() {
+        None => Ok(row),
+        Some(Token::Close) => Err("Unmatched '}'.".to_string()),
+        Some(Token::Align) | Some(Token::NewRow) => {
+            Err("'&' and '\\\\' are only allowed in matrices.".to_string())
+        }
+        Some(token) => Err(format!("Unexpected {:?}.", token)),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+// Whitespace is skipped by peek and next, and only read by \text through next_raw
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens[self.position.min(self.tokens.len())..]
+            .iter()
+            .find(|token| **token != Token::Space)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        while self.tokens.get(self.position) == Some(&Token::Space) {
+            self.position += 1;
+        }
+        self.next_raw()
+    }
+
+    fn next_raw(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), String> {
+        match self.next() {
+            Some(t) if t == token => Ok(()),
+            Some(t) => Err(format!("Expected {:?} but found {:?}.", token, t)),
+            None => Err(format!("Expected {:?} but reached the end.", token)),
+        }
+    }
+
+    // Parses atoms until the end of a group, a cell or the input
+    fn parse_row(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+
+        loop {
+            match self.peek() {
+                None | Some(Token::Close) | Some(Token::Align) | Some(Token::NewRow) => break,
+                Some(Token::Command(name)) if name == "end" => break,
+                _ => nodes.push(self.parse_atom()?),
+            }
+        }
+
+        Ok(match nodes.len() {
+            1 => nodes.pop().unwrap(),
+            _ => Node::Row(nodes),
+        })
+    }
+
+    // A base followed by any scripts attached to it
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        let base = self.parse_base()?;
+
+        let mut sup = None;
+        let mut sub = None;
+        loop {
+            match self.peek() {
+                Some(Token::Sup) if sup.is_none() => {
+                    self.next();
+                    sup = Some(Box::new(self.parse_argument()?));
+                }
+                Some(Token::Sub) if sub.is_none() => {
+                    self.next();
+                    sub = Some(Box::new(self.parse_argument()?));
+                }
+                Some(Token::Sup) | Some(Token::Sub) => return Err("Double script.".to_string()),
+                _ => break,
+            }
+        }
+
+        if sup.is_none() && sub.is_none() {
+            return Ok(base);
+        }
+
+        Ok(match base {
+            Node::BigOp { symbol, limits, .. } => Node::BigOp {
+                symbol,
+                limits,
+                sup,
+                sub,
+            },
+            base => Node::Scripts {
+                base: Box::new(base),
+                sup,
+                sub,
+            },
+        })
+    }
+
+    // The argument of a command or script, either a group or a single symbol
+    fn parse_argument(&mut self) -> Result<Node, String> {
+        match self.peek() {
+            Some(Token::Open) => {
+                self.next();
+                let row = self.parse_row()?;
+                self.expect(Token::Close)?;
+                Ok(row)
+            }
+            Some(Token::Sup) | Some(Token::Sub) | None => Err("Missing argument.".to_string()),
+            _ => self.parse_base(),
+        }
+    }
+
+    fn parse_base(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some(Token::Char(c)) => Ok(char_symbol(c)),
+            Some(Token::Open) => {
+                let row = self.parse_row()?;
+                self.expect(Token::Close)?;
+                Ok(row)
+            }
+            Some(Token::Command(name)) => self.parse_command(&name),
+            Some(token) => Err(format!("Unexpected {:?}.", token)),
+            None => Err("Unexpected end of input.".to_string()),
+        }
+    }
+
+    fn parse_command(&mut self, name: &str) -> Result<Node, String> {
+        match name {
+            "frac" => {
+                let numerator = self.parse_argument()?;
+                let denominator = self.parse_argument()?;
+                Ok(Node::Frac(Box::new(numerator), Box::new(denominator)))
+            }
+            "sqrt" => Ok(Node::Sqrt(Box::new(self.parse_argument()?))),
+            "sum" | "prod" | "int" | "oint" => Ok(Node::BigOp {
+                symbol: match name {
+                    "sum" => "∑",
+                    "prod" => "∏",
+                    "int" => "∫",
+                    _ => "∮",
+                }
+                .to_string(),
+                limits: name == "sum" || name == "prod",
+                sup: None,
+                sub: None,
+            }),
+            "text" | "mathrm" => {
+                // The text is taken as is, without parsing it as math, and keeps its spaces
+                self.expect(Token::Open)?;
+                let mut text = String::new();
+                let mut depth = 0;
+                loop {
+                    match self.next_raw() {
+                        Some(Token::Close) if depth == 0 => break,
+                        Some(Token::Close) => depth -= 1,
+                        Some(Token::Open) => depth += 1,
+                        Some(Token::Char(c)) => text.push(c),
+                        Some(Token::Command(c)) => text.push_str(&c),
+                        Some(Token::Space) => text.push(' '),
+                        Some(Token::Sup) => text.push('^'),
+                        Some(Token::Sub) => text.push('_'),
+                        Some(Token::Align) => text.push('&'),
+                        Some(Token::NewRow) => {
+                            return Err("Line breaks aren't allowed in \\text.".to_string())
+                        }
+                        None => return Err("Unclosed \\text.".to_string()),
+                    }
+                }
+                Ok(Node::Symbol(text, SymbolKind::Upright))
+            }
+            "begin" => self.parse_matrix(),
+            "," => Ok(Node::Space(0.17)),
+            ":" | ">" => Ok(Node::Space(0.22)),
+            ";" => Ok(Node::Space(0.28)),
+            "quad" => Ok(Node::Space(1.0)),
+            "qquad" => Ok(Node::Space(2.0)),
+            "{" | "}" | "%" | "$" | "#" | "_" | "&" => {
+                Ok(Node::Symbol(name.to_string(), SymbolKind::Punctuation))
+            }
+            "sin" | "cos" | "tan" | "log" | "ln" | "exp" | "lim" | "max" | "min" | "det" => {
+                Ok(Node::Symbol(name.to_string(), SymbolKind::Upright))
+            }
+            _ => match command_symbol(name) {
+                Some(symbol) => Ok(symbol),
+                None => Err(format!("Unknown command \\{}.", name)),
+            },
+        }
+    }
+
+    // Reads a group's raw text, used for environment names
+    fn parse_name(&mut self) -> Result<String, String> {
+        self.expect(Token::Open)?;
+        let mut name = String::new();
+        loop {
+            match self.next() {
+                Some(Token::Close) => return Ok(name),
+                Some(Token::Char(c)) => name.push(c),
+                _ => return Err("Invalid environment name.".to_string()),
+            }
+        }
+    }
+
+    fn parse_matrix(&mut self) -> Result<Node, String> {
+        let environment = self.parse_name()?;
+        let delimiters = match environment.as_str() {
+            "matrix" => Delimiters::None,
+            "pmatrix" => Delimiters::Parentheses,
+            "bmatrix" => Delimiters::Brackets,
+            "vmatrix" => Delimiters::Bars,
+            _ => return Err(format!("Unknown environment {}.", environment)),
+        };
+
+        let mut rows = vec![vec![]];
+        loop {
+            let cell = self.parse_row()?;
+            rows.last_mut().unwrap().push(cell);
+
+            match self.next() {
+                Some(Token::Align) => (),
+                Some(Token::NewRow) => rows.push(vec![]),
+                Some(Token::Command(name)) if name == "end" => {
+                    if self.parse_name()? != environment {
+                        return Err(format!(
+                            "\\begin{{{}}} ended by another environment.",
+                            environment
+                        ));
+                    }
+                    break;
+                }
+                _ => return Err(format!("Missing \\end{{{}}}.", environment)),
+            }
+        }
+
+        // A trailing \\ leaves an empty row behind
+        if rows.len() > 1 && rows.last() == Some(&vec![Node::Row(vec![])]) {
+            rows.pop();
+        }
+
+        Ok(Node::Matrix { rows, delimiters })
+    }
+}
+
+fn char_symbol(c: char) -> Node {
+    let kind = match c {
+        'a'..='z' | 'A'..='Z' => SymbolKind::Variable,
+        '+' | '*' => SymbolKind::Binary,
+        '=' | '<' | '>' => SymbolKind::Relation,
+        ',' | ';' | '!' | '?' | '(' | ')' | '[' | ']' | '|' | '/' | '\'' | '.' => {
+            SymbolKind::Punctuation
+        }
+        _ => SymbolKind::Upright,
+    };
+
+    // Use a proper minus sign rather than a hyphen
+    match c {
+        '-' => Node::Symbol("−".to_string(), SymbolKind::Binary),
+        '*' => Node::Symbol("∗".to_string(), kind),
+        c => Node::Symbol(c.to_string(), kind),
+    }
+}
+
+fn command_symbol(name: &str) -> Option<Node> {
+    let lower_greek = [
+        ("alpha", "α"),
+        ("beta", "β"),
+        ("gamma", "γ"),
+        ("delta", "δ"),
+        ("epsilon", "ε"),
+        ("varepsilon", "ε"),
+        ("zeta", "ζ"),
+        ("eta", "η"),
+        ("theta", "θ"),
+        ("iota", "ι"),
+        ("kappa", "κ"),
+        ("lambda", "λ"),
+        ("mu", "μ"),
+        ("nu", "ν"),
+        ("xi", "ξ"),
+        ("pi", "π"),
+        ("rho", "ρ"),
+        ("sigma", "σ"),
+        ("tau", "τ"),
+        ("upsilon", "υ"),
+        ("phi", "φ"),
+        ("varphi", "φ"),
+        ("chi", "χ"),
+        ("psi", "ψ"),
+        ("omega", "ω"),
+    ];
+    let upper_greek = [
+        ("Gamma", "Γ"),
+        ("Delta", "Δ"),
+        ("Theta", "Θ"),
+        ("Lambda", "Λ"),
+        ("Xi", "Ξ"),
+        ("Pi", "Π"),
+        ("Sigma", "Σ"),
+        ("Upsilon", "Υ"),
+        ("Phi", "Φ"),
+        ("Psi", "Ψ"),
+        ("Omega", "Ω"),
+    ];
+    let binary = [
+        ("pm", "±"),
+        ("mp", "∓"),
+        ("times", "×"),
+        ("div", "÷"),
+        ("cdot", "·"),
+        ("cup", "∪"),
+        ("cap", "∩"),
+    ];
+    let relations = [
+        ("leq", "≤"),
+        ("le", "≤"),
+        ("geq", "≥"),
+        ("ge", "≥"),
+        ("neq", "≠"),
+        ("ne", "≠"),
+        ("approx", "≈"),
+        ("equiv", "≡"),
+        ("sim", "∼"),
+        ("to", "→"),
+        ("rightarrow", "→"),
+        ("leftarrow", "←"),
+        ("Rightarrow", "⇒"),
+        ("iff", "⇔"),
+        ("in", "∈"),
+        ("subset", "⊂"),
+    ];
+    let others = [
+        ("infty", "∞"),
+        ("partial", "∂"),
+        ("nabla", "∇"),
+        ("ldots", "…"),
+        ("cdots", "⋯"),
+        ("forall", "∀"),
+        ("exists", "∃"),
+        ("prime", "′"),
+    ];
+
+    let find = |table: &[(&str, &str)]| {
+        table
+            .iter()
+            .find(|(command, _)| *command == name)
+            .map(|(_, symbol)| symbol.to_string())
+    };
+
+    if let Some(s) = find(&lower_greek) {
+        return Some(Node::Symbol(s, SymbolKind::Variable));
+    }
+    if let Some(s) = find(&upper_greek) {
+        return Some(Node::Symbol(s, SymbolKind::Upright));
+    }
+    if let Some(s) = find(&binary) {
+        return Some(Node::Symbol(s, SymbolKind::Binary));
+    }
+    if let Some(s) = find(&relations) {
+        return Some(Node::Symbol(s, SymbolKind::Relation));
+    }
+    find(&others).map(|s| Node::Symbol(s, SymbolKind::Upright))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(text: &str, kind: SymbolKind) -> Node {
+        Node::Symbol(text.to_string(), kind)
+    }
+
+    fn var(name: &str) -> Node {
+        symbol(name, SymbolKind::Variable)
+    }
+
+    fn digit(d: &str) -> Node {
+        symbol(d, SymbolKind::Upright)
+    }
+
+    #[test]
+    fn parses_fractions() {
+        assert_eq!(
+            parse("\\frac{a}{b + 1}"),
+            Ok(Node::Frac(
+                Box::new(var("a")),
+                Box::new(Node::Row(vec![
+                    var("b"),
+                    symbol("+", SymbolKind::Binary),
+                    digit("1"),
+                ])),
+            ))
+        );
+        // Arguments may be single symbols without braces
+        assert_eq!(
+            parse("\\frac12"),
+            Ok(Node::Frac(Box::new(digit("1")), Box::new(digit("2"))))
+        );
+    }
+
+    #[test]
+    fn parses_scripts() {
+        assert_eq!(
+            parse("x_i^{2}"),
+            Ok(Node::Scripts {
+                base: Box::new(var("x")),
+                sup: Some(Box::new(digit("2"))),
+                sub: Some(Box::new(var("i"))),
+            })
+        );
+        assert_eq!(
+            parse("\\sum_{i=0}^n"),
+            Ok(Node::BigOp {
+                symbol: "∑".to_string(),
+                limits: true,
+                sup: Some(Box::new(var("n"))),
+                sub: Some(Box::new(Node::Row(vec![
+                    var("i"),
+                    symbol("=", SymbolKind::Relation),
+                    digit("0"),
+                ]))),
+            })
+        );
+        assert_eq!(parse("x^2^3"), Err("Double script.".to_string()));
+        assert_eq!(parse("x^"), Err("Missing argument.".to_string()));
+    }
+
+    #[test]
+    fn parses_square_roots() {
+        assert_eq!(
+            parse("\\sqrt{x^2}"),
+            Ok(Node::Sqrt(Box::new(Node::Scripts {
+                base: Box::new(var("x")),
+                sup: Some(Box::new(digit("2"))),
+                sub: None,
+            })))
+        );
+    }
+
+    #[test]
+    fn parses_matrices() {
+        assert_eq!(
+            parse("\\begin{pmatrix} a & b \\\\ c & d \\\\ \\end{pmatrix}"),
+            Ok(Node::Matrix {
+                rows: vec![vec![var("a"), var("b")], vec![var("c"), var("d")]],
+                delimiters: Delimiters::Parentheses,
+            })
+        );
+        assert_eq!(
+            parse("\\begin{matrix} a \\end{bmatrix}"),
+            Err("\\begin{matrix} ended by another environment.".to_string())
+        );
+        assert_eq!(
+            parse("\\begin{matrix} a & b"),
+            Err("Missing \\end{matrix}.".to_string())
+        );
+        assert_eq!(
+            parse("a & b"),
+            Err("'&' and '\\\\' are only allowed in matrices.".to_string())
+        );
+    }
+
+    #[test]
+    fn keeps_text_as_it_is() {
+        assert_eq!(
+            parse("\\text{if x > 0}"),
+            Ok(symbol("if x > 0", SymbolKind::Upright))
+        );
+        assert_eq!(
+            parse("\\text{a^b_c & {d}}"),
+            Ok(symbol("a^b_c & d", SymbolKind::Upright))
+        );
+        // Spaces are ignored outside of text
+        assert_eq!(parse("a  b"), Ok(Node::Row(vec![var("a"), var("b")])));
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert_eq!(parse("\\text{abc"), Err("Unclosed \\text.".to_string()));
+        assert_eq!(parse("a}"), Err("Unmatched '}'.".to_string()));
+        assert_eq!(
+            parse("{a"),
+            Err("Expected Close but reached the end.".to_string())
+        );
+        assert_eq!(parse("\\frac{a}"), Err("Missing argument.".to_string()));
+        assert_eq!(parse("\\foo"), Err("Unknown command \\foo.".to_string()));
+    }
+}
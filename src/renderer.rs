@@ -1,6 +1,8 @@
 use crate::drawable::DrawOptions;
+use crate::fonts::FontManager;
 use crate::position::Position;
 
+use sdl2::image::SaveSurface;
 use sdl2::pixels::Color;
 use sdl2::rect::{Point, Rect};
 use sdl2::render::{Texture, TextureCreator, WindowCanvas};
@@ -9,25 +11,43 @@ use sdl2::ttf::{Font, FontStyle};
 use sdl2::video::WindowContext;
 
 use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
 
 use uuid::Uuid;
 
+pub const MIN_ZOOM: f32 = 0.2;
+pub const MAX_ZOOM: f32 = 5.0;
+const SCROLL_STEP: f32 = 62.0; // Pixels on screen scrolled for each step of the mouse wheel
+const SCROLL_EASING: f32 = 14.0; // How quickly gliding slows down, as the part left each second
+
+// A piece of text in a single style, for rendering lines that mix styles
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextSpan {
+    pub text: String,
+    pub font_style: FontStyle,
+    pub color: Color,
+}
+
 pub struct Renderer<'c, 'tc, 'ttf> {
     canvas: &'c mut WindowCanvas,
     tex_creator: &'tc TextureCreator<WindowContext>,
     textures: HashMap<Uuid, Vec<Texture<'tc>>>,
-    fonts: HashMap<String, Font<'ttf, 'ttf>>,
-    camera: Rect,
-    scroll_max: i32,
+    fonts: FontManager<'ttf>,
+    camera: Rect,      // Its position is on the world, and its size is the window's
+    zoom: f32,         // Pixels on screen for each pixel on the world
+    scroll_area: Rect, // The part of the world that can be scrolled through
+    scroll_target: Option<Point>, // Where the camera is gliding to, on the world
 }
 
 impl<'c, 'tc, 'ttf> Renderer<'c, 'tc, 'ttf> {
-    // Only one Renderer will ever be constructed. The default settings set the window to be
+    // The app constructs one Renderer for its window. The default settings set the window to be
     // maximized to the default display, allowing the window to be resized.
-    pub(super) fn new(
+    // Exporters construct their own for hidden windows that are the size of a page.
+    pub(crate) fn new(
         canvas: &'c mut WindowCanvas,
         tex_creator: &'tc TextureCreator<WindowContext>,
-        fonts: HashMap<String, Font<'ttf, 'ttf>>,
+        fonts: FontManager<'ttf>,
     ) -> Renderer<'c, 'tc, 'ttf> {
         let camera = Rect::new(0, 0, canvas.window().size().0, canvas.window().size().1);
 
@@ -37,7 +57,9 @@ impl<'c, 'tc, 'ttf> Renderer<'c, 'tc, 'ttf> {
             textures: HashMap::new(),
             fonts,
             camera,
-            scroll_max: 0,
+            zoom: 1.0,
+            scroll_area: Rect::new(0, 0, 1, 1),
+            scroll_target: None,
         }
     }
 
@@ -90,6 +112,110 @@ impl<'c, 'tc, 'ttf> Renderer<'c, 'tc, 'ttf> {
         Ok(())
     }
 
+    // Drops every texture associated with id
+    pub fn remove_textures(&mut self, id: Uuid) {
+        self.textures.remove(&id);
+    }
+
+    pub fn fonts(&self) -> &FontManager<'ttf> {
+        &self.fonts
+    }
+
+    pub fn fonts_mut(&mut self) -> &mut FontManager<'ttf> {
+        &mut self.fonts
+    }
+
+    // Looks up a font by its family, style and point size, opening it if needed
+    fn font(
+        &self,
+        font_name: &str,
+        font_style: FontStyle,
+        point: u16,
+    ) -> Result<Rc<Font<'ttf, 'static>>, String> {
+        self.fonts.get(font_name, font_style, point)
+    }
+
+    // Splits text into the runs that the font can draw, and the runs of characters it has no
+    // glyphs for, which are drawn in the fallback font if that has them
+    fn font_runs<'t>(
+        &self,
+        text: &'t str,
+        font_name: &str,
+        font_style: FontStyle,
+        point: u16,
+    ) -> Result<Vec<(Rc<Font<'ttf, 'static>>, &'t str)>, String> {
+        let font = self.font(font_name, font_style, point)?;
+        if text.chars().all(|c| font.find_glyph(c).is_some()) {
+            return Ok(vec![(font, text)]);
+        }
+        let fallback = match self.fonts.fallback(font_style, point)? {
+            Some(fallback) => fallback,
+            None => return Ok(vec![(font, text)]),
+        };
+
+        let mut runs: Vec<(Rc<Font<'ttf, 'static>>, &'t str)> = Vec::new();
+        let mut start = 0;
+        let mut in_fallback = false;
+        for (i, c) in text.char_indices() {
+            let missing = font.find_glyph(c).is_none() && fallback.find_glyph(c).is_some();
+            if missing != in_fallback && i > start {
+                let run_font = if in_fallback { &fallback } else { &font };
+                runs.push((Rc::clone(run_font), &text[start..i]));
+                start = i;
+            }
+            in_fallback = missing;
+        }
+        let run_font = if in_fallback { &fallback } else { &font };
+        runs.push((Rc::clone(run_font), &text[start..]));
+
+        Ok(runs)
+    }
+
+    // The width and height that text would take up when rendered
+    pub fn text_size(
+        &self,
+        text: &str,
+        font_name: &str,
+        font_style: FontStyle,
+        point: u16,
+    ) -> Result<(u32, u32), String> {
+        let mut size = (0, 0);
+        for (font, run) in self.font_runs(text, font_name, font_style, point)? {
+            let (w, h) = font.size_of(run).map_err(|e| e.to_string())?;
+            size = (size.0 + w, size.1.max(h));
+        }
+
+        Ok(size)
+    }
+
+    // The ascent and descent of a font, both as distances from the baseline
+    pub fn font_extents(
+        &self,
+        font_name: &str,
+        font_style: FontStyle,
+        point: u16,
+    ) -> Result<(i32, i32), String> {
+        let font = self.font(font_name, font_style, point)?;
+
+        Ok((font.ascent(), -font.descent()))
+    }
+
+    // Renders text onto a surface of its own, for things like button labels that are put together
+    // before becoming textures
+    pub(crate) fn render_text(
+        &self,
+        text: &str,
+        font_name: &str,
+        font_style: FontStyle,
+        point: u16,
+        color: Color,
+    ) -> Result<Surface<'static>, String> {
+        self.font(font_name, font_style, point)?
+            .render(text)
+            .blended(color)
+            .map_err(|e| e.to_string())
+    }
+
     // Creates text texture and adds it to textures at the entry associated with id
     // If no index is given or if index is out of bounds, then it appends the texture to the vec
     // If there is no entry in textures associated with id, then a new entry is inserted
@@ -103,103 +229,91 @@ impl<'c, 'tc, 'ttf> Renderer<'c, 'tc, 'ttf> {
         point: u16,
         color: Color,
     ) -> Result<(u32, u32), String> {
-        // Get full font name with style
-        let mut font_name = font_name.clone();
-        if font_style.bits() & 1 == 1 {
-            font_name.push_str("-Bold");
-            if font_style.bits() & 2 == 2 {
-                font_name.push_str("Italic");
-            }
-        } else if font_style.bits() & 2 == 2 {
-            font_name.push_str("-Italic");
-        }
-        font_name.push('_');
-        font_name.push_str(&point.to_string());
-
-        let font = self
-            .fonts
-            .get(&font_name)
-            .ok_or_else(|| "Error retrieving font.".to_string())?;
-
-        let text_surface = font
-            .render(&text)
-            .blended(color)
-            .map_err(|e| e.to_string())?;
-
-        // Calculates the vertical offset so that the text lines up with the grid
-        let offset = font.ascent() - (crate::app::pages::SQUARE_SIZE as i32 - 1);
-
-        let mut adjusted_surface = Surface::new(
-            text_surface.width(),
-            (font.height() - offset) as u32,
-            text_surface.pixel_format_enum(),
-        )?;
+        let span = TextSpan {
+            text: text.clone(),
+            font_style,
+            color,
+        };
 
-        // Copy the surface but with its position adjusted
-        text_surface.blit(
-            Rect::new(
-                0,
-                offset,
-                text_surface.width(),
-                (font.height() - offset) as u32,
-            ),
-            &mut adjusted_surface,
-            None,
-        )?;
-
-        let size = adjusted_surface.size();
-
-        let texture = Texture::from_surface(&adjusted_surface, &self.tex_creator)
-            .map_err(|e| e.to_string())?;
+        self.create_text_spans(id, texture_index, &[span], font_name, point, None)
+    }
 
-        match self.textures.get_mut(&id) {
-            Some(textures) => match texture_index {
-                Some(i) => {
-                    if i < textures.len() {
-                        textures[i] = texture
-                    } else {
-                        textures.push(texture)
+    // Creates a texture for a line made of differently styled spans, lined up on one baseline
+    // With a cell width, every character is centered in a cell of that width instead of being
+    // laid out by the font, as in a monospaced grid
+    // Textures are stored the same way as with create_text
+    pub(crate) fn create_text_spans(
+        &mut self,
+        id: Uuid,
+        texture_index: Option<usize>,
+        spans: &[TextSpan],
+        font_name: &str,
+        point: u16,
+        cell_width: Option<u32>,
+    ) -> Result<(u32, u32), String> {
+        let rows = crate::app::pages::line_rows(point);
+        let offset_limit = (rows * crate::app::pages::SQUARE_SIZE) as i32 - 1;
+
+        // Render every piece on its own, cropped so that its baseline lines up with the grid
+        // Pieces in the fallback font line up on the same baseline
+        let mut pieces = Vec::new();
+        for span in spans {
+            for (font, run) in self.font_runs(&span.text, font_name, span.font_style, point)? {
+                let offset = font.ascent() - offset_limit;
+                let height = (font.height() - offset) as u32;
+
+                let texts: Vec<String> = match cell_width {
+                    Some(_) => run.chars().map(|c| c.to_string()).collect(),
+                    None => vec![run.to_string()],
+                };
+                for text in texts {
+                    // Spaces and empty text still take up room, but can't be rendered
+                    if text.trim().is_empty() {
+                        let width = font.size_of(&text).map_err(|e| e.to_string())?.0;
+                        pieces.push((None, width, height));
+                        continue;
                     }
+
+                    let mut surface = font
+                        .render(&text)
+                        .blended(span.color)
+                        .map_err(|e| e.to_string())?;
+                    surface.set_blend_mode(sdl2::render::BlendMode::None)?;
+                    let width = surface.width();
+                    pieces.push((Some((surface, offset)), width, height));
                 }
-                None => textures.push(texture),
-            },
-            None => {
-                self.textures.insert(id, vec![texture]);
             }
         }
 
-        Ok(size)
-    }
+        let width = match cell_width {
+            Some(cell) => cell * pieces.len() as u32,
+            None => pieces.iter().map(|p| p.1).sum(),
+        };
+        let height = pieces.iter().map(|p| p.2).max().unwrap_or(1);
+        let mut line_surface = Surface::new(
+            width.max(1),
+            height.max(1),
+            sdl2::pixels::PixelFormatEnum::ARGB8888,
+        )?;
 
-    pub fn text_overflow(
-        &self,
-        text: &String,
-        font_name: &String,
-        font_style: FontStyle,
-        point: u16,
-        max_width: u32,
-    ) -> Result<bool, String> {
-        // Get full font name with style
-        let mut font_name = font_name.clone();
-        if font_style.bits() & 1 == 1 {
-            font_name.push_str("-Bold");
-            if font_style.bits() & 2 == 2 {
-                font_name.push_str("Italic");
+        let mut x = 0;
+        for (piece, piece_width, piece_height) in pieces {
+            let advance = cell_width.unwrap_or(piece_width);
+            if let Some((surface, offset)) = piece {
+                let centering = (advance as i32 - piece_width as i32) / 2;
+                surface.blit(
+                    Rect::new(0, offset, piece_width, piece_height),
+                    &mut line_surface,
+                    Rect::new(x + centering, 0, piece_width, piece_height),
+                )?;
             }
-        } else if font_style.bits() & 2 == 2 {
-            font_name.push_str("-Italic");
+            x += advance as i32;
         }
-        font_name.push('_');
-        font_name.push_str(&point.to_string());
 
-        let font = self
-            .fonts
-            .get(&font_name)
-            .ok_or_else(|| "Error retrieving font.".to_string())?;
+        let size = line_surface.size();
+        self.create_texture(id, texture_index, line_surface)?;
 
-        let overflow = max_width as i32 - font.size_of(&text).map_err(|e| e.to_string())?.0 as i32;
-
-        Ok(overflow < 0)
+        Ok(size)
     }
 
     // Clears canvas
@@ -213,6 +327,16 @@ impl<'c, 'tc, 'ttf> Renderer<'c, 'tc, 'ttf> {
         self.canvas.present();
     }
 
+    // Writes what has been drawn to the canvas so far to a PNG file
+    pub fn save_png(&self, path: &std::path::Path) -> Result<(), String> {
+        let (width, height) = self.canvas.output_size()?;
+        let format = sdl2::pixels::PixelFormatEnum::ARGB8888;
+        let mut pixels = self.canvas.read_pixels(None, format)?;
+
+        let surface = Surface::from_data(&mut pixels, width, height, width * 4, format)?;
+        surface.save(path)
+    }
+
     // Returns the dimensions of the window.
     pub fn dimensions(&self) -> (u32, u32) {
         self.canvas.window().size()
@@ -226,21 +350,195 @@ impl<'c, 'tc, 'ttf> Renderer<'c, 'tc, 'ttf> {
         self.camera = rect
     }
 
-    // scrolls camera by dy amount
-    pub fn scroll(&mut self, dy: i32) {
-        // Keep the scrolling within the pages
-        let new_y = ((self.camera.y - dy * 62).max(0)).min(self.scroll_max);
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    // Zooms in or out, keeping the point of the world under a point on screen where it is
+    pub fn set_zoom(&mut self, zoom: f32, anchor: Position) -> Result<(), String> {
+        let before = self.to_world(anchor)?;
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        let after = self.to_world(anchor)?;
+
+        self.camera
+            .offset(before.x() - after.x(), before.y() - after.y());
+        self.scroll_target = None;
+        self.keep_in_bounds();
+
+        Ok(())
+    }
+
+    // The size of the part of the world that is on screen
+    pub fn visible_size(&self) -> (u32, u32) {
+        (
+            (self.camera.width() as f32 / self.zoom) as u32,
+            (self.camera.height() as f32 / self.zoom) as u32,
+        )
+    }
 
-        self.camera = Rect::new(
-            self.camera.x,
-            new_y,
-            self.camera.width(),
-            self.camera.height(),
+    // The part of the world that is on screen, rounded out to whole pixels
+    pub fn view(&self) -> Rect {
+        Rect::new(
+            self.camera.x(),
+            self.camera.y(),
+            (self.camera.width() as f32 / self.zoom).ceil() as u32,
+            (self.camera.height() as f32 / self.zoom).ceil() as u32,
         )
     }
 
-    pub fn set_scroll_max(&mut self, new_max: i32) {
-        self.scroll_max = new_max
+    // Glides the camera by dx and dy steps of the mouse wheel, which are up and to the right
+    // Steps taken while still gliding add on to where it is gliding to, so that quick turns of
+    // the wheel build up speed
+    pub fn scroll(&mut self, dx: i32, dy: i32) {
+        // Scrolls the same distance on screen at any zoom
+        let step = |d: i32| (d as f32 * SCROLL_STEP / self.zoom).round() as i32;
+        let from = self.scroll_target();
+        self.glide_to(Position::add(from, step(dx), -step(dy)));
+    }
+
+    // Moves the world along with the mouse being dragged across the screen
+    pub fn pan(&mut self, dx: i32, dy: i32) {
+        self.camera.offset(
+            -(dx as f32 / self.zoom).round() as i32,
+            -(dy as f32 / self.zoom).round() as i32,
+        );
+        self.scroll_target = None;
+        self.keep_in_bounds();
+    }
+
+    // Scrolls so that a point on the world is at the top-left of the window, as far as it can be
+    pub fn scroll_to(&mut self, position: Position) {
+        self.camera.set_x(position.x());
+        self.camera.set_y(position.y());
+        self.scroll_target = None;
+        self.keep_in_bounds();
+    }
+
+    // Like scroll_to, but glides there over the next few steps rather than jumping
+    pub fn glide_to(&mut self, position: Position) {
+        let (x, y) = self.in_bounds(position.x(), position.y());
+        self.scroll_target = Some(Point::new(x, y));
+    }
+
+    // The top-left of the camera once it has stopped gliding
+    pub fn scroll_target(&self) -> Position {
+        let target = self.scroll_target.unwrap_or(self.camera.top_left());
+        Position::FreeOnWorld(target.x(), target.y())
+    }
+
+    pub fn is_gliding(&self) -> bool {
+        self.scroll_target.is_some()
+    }
+
+    // Moves the camera on towards where it is gliding to, easing out as it gets closer
+    pub fn step_scroll(&mut self, time: Duration) {
+        let target = match self.scroll_target {
+            Some(target) => target,
+            None => return,
+        };
+
+        let part = 1.0 - (-SCROLL_EASING * time.as_secs_f32()).exp();
+        let dx = ((target.x() - self.camera.x()) as f32 * part).round() as i32;
+        let dy = ((target.y() - self.camera.y()) as f32 * part).round() as i32;
+
+        // The last pixel or two would take a while to round up to a step, so they are jumped
+        if dx == 0 && dy == 0 {
+            self.camera.reposition(target);
+            self.scroll_target = None;
+        } else {
+            self.camera.offset(dx, dy);
+        }
+    }
+
+    // Doesn't move the camera, which is left to keep_in_bounds
+    pub fn set_scroll_area(&mut self, area: Rect) {
+        self.scroll_area = area;
+    }
+
+    // Keeps the camera within the scroll area
+    // When the area is narrower than the window it is centered, and when it is shorter than the
+    // window it is at the top
+    pub fn keep_in_bounds(&mut self) {
+        let (x, y) = self.in_bounds(self.camera.x(), self.camera.y());
+        self.camera.set_x(x);
+        self.camera.set_y(y);
+
+        if let Some(target) = self.scroll_target {
+            let (x, y) = self.in_bounds(target.x(), target.y());
+            self.scroll_target = Some(Point::new(x, y));
+        }
+    }
+
+    // The closest top-left to x and y that keeps the camera within the scroll area
+    fn in_bounds(&self, x: i32, y: i32) -> (i32, i32) {
+        let area = self.scroll_area;
+        let (width, height) = self.visible_size();
+
+        let x = if area.width() <= width {
+            area.x() - (width - area.width()) as i32 / 2
+        } else {
+            x.clamp(area.x(), area.right() - width as i32)
+        };
+        let y = if area.height() <= height {
+            area.y()
+        } else {
+            y.clamp(area.y(), area.bottom() - height as i32)
+        };
+
+        (x, y)
+    }
+
+    // Where a position is on screen, with positions on the world moved by the camera and
+    // scaled by the zoom
+    pub fn to_screen(&self, position: Position) -> Result<Position, String> {
+        match position {
+            Position::FreeOnWorld(x, y) => Ok(Position::FreeOnScreen(
+                ((x - self.camera.x()) as f32 * self.zoom).round() as i32,
+                ((y - self.camera.y()) as f32 * self.zoom).round() as i32,
+            )),
+            _ => position.to_free_on_screen(Some(self.dimensions()), None),
+        }
+    }
+
+    // Where a position is on the world, undoing the camera's movement and zoom
+    pub fn to_world(&self, position: Position) -> Result<Position, String> {
+        if let Position::FreeOnWorld(..) = position {
+            return Ok(position);
+        }
+
+        let p = position.to_free_on_screen(Some(self.dimensions()), None)?;
+        Ok(Position::FreeOnWorld(
+            self.camera.x() + (p.x() as f32 / self.zoom).floor() as i32,
+            self.camera.y() + (p.y() as f32 / self.zoom).floor() as i32,
+        ))
+    }
+
+    // The rectangle on screen that a rectangle at a position covers
+    // Both of its corners are scaled rather than its size, so that rectangles next to each other
+    // on the world don't have gaps or overlaps between them on screen
+    fn screen_rect(&self, position: Position, size: (u32, u32)) -> Result<Rect, String> {
+        let start = self.to_screen(position)?;
+        let end = match position {
+            Position::FreeOnWorld(..) => {
+                self.to_screen(Position::add(position, size.0 as i32, size.1 as i32))?
+            }
+            _ => Position::add(start, size.0 as i32, size.1 as i32),
+        };
+
+        Ok(Rect::new(
+            start.x(),
+            start.y(),
+            (end.x() - start.x()).max(0) as u32,
+            (end.y() - start.y()).max(0) as u32,
+        ))
+    }
+
+    // How much a position's sizes are scaled by when drawn
+    fn scale(&self, position: Position) -> f32 {
+        match position {
+            Position::FreeOnWorld(..) => self.zoom,
+            _ => 1.0,
+        }
     }
 
     // Draws a texture associated with an object_id and the index into its texture Vec
@@ -250,27 +548,27 @@ impl<'c, 'tc, 'ttf> Renderer<'c, 'tc, 'ttf> {
         index: usize,
         options: DrawOptions,
     ) -> Result<(), String> {
-        let textures;
-        match self.textures.get(&object_id) {
-            Some(t) => textures = t,
-            None => return Err("Texture not found".to_string()),
+        if !self.textures.contains_key(&object_id) {
+            return Err("Texture not found".to_string());
         }
 
         // Convert from all positions to screen coordinates
-        let position = options
-            .position
-            .to_free_on_screen(Some(self.dimensions()), Some(self.camera))?;
+        let dst = self.screen_rect(options.position, options.size)?;
+        let scale = self.scale(options.position);
 
         self.canvas.copy_ex(
-            &textures[index],
+            &self.textures[&object_id][index],
             options.src,
-            Rect::new(position.x(), position.y(), options.size.0, options.size.1),
+            dst,
             match options.rotation {
                 Some(rotation) => rotation.0,
                 None => 0.0,
             },
             match options.rotation {
-                Some(rotation) => rotation.1,
+                Some((_, center)) => Point::new(
+                    (center.x() as f32 * scale) as i32,
+                    (center.y() as f32 * scale) as i32,
+                ),
                 None => Point::new(0, 0),
             },
             options.flip_h,
@@ -288,10 +586,37 @@ impl<'c, 'tc, 'ttf> Renderer<'c, 'tc, 'ttf> {
     ) -> Result<(), String> {
         self.canvas.set_draw_color(color);
 
-        let position = position.to_free_on_screen(Some(self.dimensions()), Some(self.camera))?;
+        let rect = self.screen_rect(position, size)?;
+        self.canvas.fill_rect(rect)
+    }
 
-        self.canvas
-            .fill_rect(Rect::new(position.x(), position.y(), size.0, size.1))
+    // Draws a straight line, thickening it with parallel lines on either side
+    pub fn draw_line(
+        &mut self,
+        start: Position,
+        end: Position,
+        thickness: u32,
+        color: Color,
+    ) -> Result<(), String> {
+        self.canvas.set_draw_color(color);
+
+        // Lines on the world get thicker as they are zoomed into, but never disappear
+        let thickness = ((thickness as f32 * self.scale(start)).round() as u32).max(1);
+        let start = self.to_screen(start)?;
+        let end = self.to_screen(end)?;
+
+        // Offset perpendicular to the line's main direction so diagonals keep their thickness
+        let steep = (end.y() - start.y()).abs() > (end.x() - start.x()).abs();
+        let first = -(thickness as i32 / 2);
+        for offset in first..(first + thickness as i32) {
+            let (dx, dy) = if steep { (offset, 0) } else { (0, offset) };
+            self.canvas.draw_line(
+                Point::new(start.x() + dx, start.y() + dy),
+                Point::new(end.x() + dx, end.y() + dy),
+            )?;
+        }
+
+        Ok(())
     }
 
     pub fn draw_rect(
@@ -303,17 +628,18 @@ impl<'c, 'tc, 'ttf> Renderer<'c, 'tc, 'ttf> {
     ) -> Result<(), String> {
         self.canvas.set_draw_color(color);
 
-        let mut position =
-            position.to_free_on_screen(Some(self.dimensions()), Some(self.camera))?;
-        let mut size = size;
+        let mut rect = self.screen_rect(position, size)?;
 
         // Since cannot set thickness of rectangle use canvas.draw_fill_rect
-        // This instead draws <thickness> concentric rectangles outwards
+        // This instead draws <thickness> concentric rectangles outwards, each a pixel on screen
         for _ in 0..thickness {
-            self.canvas
-                .draw_rect(Rect::new(position.x(), position.y(), size.0, size.1))?;
-            position = Position::add(position, -1, -1);
-            size = (size.0 + 2, size.1 + 2);
+            self.canvas.draw_rect(rect)?;
+            rect = Rect::new(
+                rect.x() - 1,
+                rect.y() - 1,
+                rect.width() + 2,
+                rect.height() + 2,
+            );
         }
 
         Ok(())
@@ -0,0 +1,48 @@
+// Helpers for tests that need SDL, such as those building marks or editing documents
+// SDL is run with its dummy video driver and a software renderer, so no display is needed
+
+use crate::app::pages::Pages;
+use crate::config::Config;
+use crate::editor::Editor;
+use crate::fonts::FontManager;
+use crate::renderer::Renderer;
+
+use std::sync::Mutex;
+
+// SDL can only be initialized once at a time, so tests using it take turns
+static SDL_LOCK: Mutex<()> = Mutex::new(());
+
+// Runs a test with a renderer for a small hidden window
+pub fn with_renderer(test: impl FnOnce(&sdl2::VideoSubsystem, &mut Renderer)) {
+    let _lock = SDL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    sdl2::hint::set("SDL_VIDEODRIVER", "dummy");
+    let sdl = sdl2::init().unwrap();
+    let video = sdl.video().unwrap();
+    let ttf = sdl2::ttf::init().unwrap();
+    let _image = sdl2::image::init(sdl2::image::InitFlag::PNG).unwrap();
+
+    let window = video.window("test", 800, 600).hidden().build().unwrap();
+    let mut canvas = window.into_canvas().software().build().unwrap();
+    let tex_creator = canvas.texture_creator();
+    let fonts = FontManager::new(&ttf, &Config::default()).unwrap();
+    let mut renderer = Renderer::new(&mut canvas, &tex_creator, fonts);
+
+    test(&video, &mut renderer);
+}
+
+// Runs a test with an editor for a new document of two A5 pages
+pub fn with_editor(test: impl FnOnce(&mut Editor, &mut Renderer)) {
+    with_renderer(|video, renderer| {
+        let pages = Pages::new((29, 42), renderer).unwrap();
+        let mut editor = Editor::new(pages, video.text_input(), video.clipboard());
+        editor.add_page();
+
+        test(&mut editor, renderer);
+    });
+}
+
+// A path in the system's temporary folder that is unique to a test
+pub fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("squarepad-test-{}-{}", std::process::id(), name))
+}